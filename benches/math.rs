@@ -0,0 +1,62 @@
+// cargo bench --features simd
+// The `_scalar` benches run the portable path for comparison
+#![feature(test)]
+
+extern crate test;
+extern crate engine_rs;
+
+use test::{ Bencher, black_box };
+use engine_rs::math::vec::*;
+use engine_rs::math::mat::*;
+use engine_rs::math::quat::*;
+
+const BATCH: usize = 10000;
+
+fn matrix() -> Mat4 {
+	Mat4::trs(Vec3::new(1.0, 2.0, 3.0), Quat::new(Vec3::new(1.0, 2.0, 3.0).normalized(), 0.7), Vec3::new(2.0, 0.5, 1.5))
+		* Mat4::perspective(1.2, 1.5, 0.1, 100.0)
+}
+
+fn points() -> Vec<Vec4> {
+	(0..BATCH).map(|i| Vec4::new(i as f32, (i * 2) as f32, (i * 3) as f32, 1.0)).collect()
+}
+
+#[bench]
+fn mat4_mul(b: &mut Bencher) {
+	let (m, n) = (matrix(), matrix().transposed());
+	b.iter(|| black_box(m) * black_box(n));
+}
+
+#[bench]
+fn mat4_mul_scalar(b: &mut Bencher) {
+	let (m, n) = (matrix(), matrix().transposed());
+	b.iter(|| black_box(m).mul_scalar(&black_box(n)));
+}
+
+#[bench]
+fn mat4_inverted(b: &mut Bencher) {
+	let m = matrix();
+	b.iter(|| black_box(m).inverted());
+}
+
+#[bench]
+fn mat4_inverted_scalar(b: &mut Bencher) {
+	let m = matrix();
+	b.iter(|| black_box(m).inverted_scalar());
+}
+
+#[bench]
+fn transform_vec4s(b: &mut Bencher) {
+	let m = matrix();
+	let mut ps = points();
+	b.iter(|| m.transform_vec4s(black_box(&mut ps)));
+}
+
+#[bench]
+fn transform_vec4s_scalar(b: &mut Bencher) {
+	let m = matrix();
+	let mut ps = points();
+	b.iter(|| for p in black_box(&mut ps).iter_mut() {
+		*p = m.mul_vec4_scalar(*p);
+	});
+}
\ No newline at end of file
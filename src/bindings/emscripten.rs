@@ -66,6 +66,19 @@ pub const EMSCRIPTEN_RESULT_FAILED: i32              = -6;
 pub const EMSCRIPTEN_RESULT_NO_DATA: i32             = -7;
 pub const EMSCRIPTEN_RESULT_TIMED_OUT: i32           = -8;
 
+pub const EMSCRIPTEN_FULLSCREEN_SCALE_DEFAULT: i32            = 0;
+pub const EMSCRIPTEN_FULLSCREEN_SCALE_STRETCH: i32            = 1;
+pub const EMSCRIPTEN_FULLSCREEN_SCALE_ASPECT: i32             = 2;
+pub const EMSCRIPTEN_FULLSCREEN_SCALE_CENTER: i32             = 3;
+
+pub const EMSCRIPTEN_FULLSCREEN_CANVAS_SCALE_NONE: i32        = 0;
+pub const EMSCRIPTEN_FULLSCREEN_CANVAS_SCALE_STDDEF: i32      = 1;
+pub const EMSCRIPTEN_FULLSCREEN_CANVAS_SCALE_HIDEF: i32       = 2;
+
+pub const EMSCRIPTEN_FULLSCREEN_FILTERING_DEFAULT: i32        = 0;
+pub const EMSCRIPTEN_FULLSCREEN_FILTERING_NEAREST: i32        = 1;
+pub const EMSCRIPTEN_FULLSCREEN_FILTERING_BILINEAR: i32       = 2;
+
 pub type emscripten_align1_short = i16;
 pub type emscripten_align4_int64 = i64;
 pub type emscripten_align2_int64 = i64;
@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ self, Read, Write };
+
+use math::vec::*;
+use core::keyboard::*;
+use core::mouse::*;
+use core::touch::*;
+use core::gamepad::*;
+use core::input::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Binding {
+	Key(KeyCode),
+	Mouse(MouseButton),
+
+	// Button on any connected pad
+	Gamepad(GamepadButton),
+
+	// Any touch inside a region of the screen, in normalized [0, 1] coordinates
+	TouchRegion(Vec2, Vec2),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Chord {
+	pub binding: Binding,
+	pub modifiers: Modifiers,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AxisBinding {
+	// Negative and positive keys
+	Keys(KeyCode, KeyCode),
+
+	// Per-frame mouse movement multiplied by a sensitivity
+	MouseX(f32),
+	MouseY(f32),
+
+	// Per-frame wheel movement in pixels multiplied by a sensitivity
+	WheelX(f32),
+	WheelY(f32),
+
+	// Pad axis on any connected pad, after the pad's dead zone, multiplied by a scale
+	Gamepad(GamepadAxis, f32),
+}
+
+#[derive(Clone, Debug)]
+pub struct Axis {
+	pub bindings: Vec<AxisBinding>,
+	pub dead_zone: f32,
+}
+
+// Maps named actions and axes to physical inputs.
+// When chords on the same input overlap, only the one needing the most held modifiers fires,
+// so `save = KeyS +ctrl` and `back = KeyS` don't both trigger on Ctrl+S.
+// Can be rebound at runtime and stored in a plain text config:
+//
+//	[action jump]
+//	key Space
+//	key KeyW +shift
+//
+//	[axis move_x]
+//	deadzone 0.1
+//	keys KeyA KeyD
+pub struct ActionMap {
+	actions: HashMap<String, Vec<Chord>>,
+	axes: HashMap<String, Axis>,
+}
+
+impl Binding {
+	fn held(&self, input: &Input) -> bool {
+		match *self {
+			Binding::Key(k) => input.key_held(k),
+			Binding::Mouse(b) => input.mouse_held(b),
+			Binding::Gamepad(b) => input.gamepads().iter().any(|p| p.held(b)),
+			Binding::TouchRegion(min, max) => any_in_region(input, input.touches(), min, max),
+		}
+	}
+
+	fn pressed(&self, input: &Input) -> bool {
+		match *self {
+			Binding::Key(k) => input.key_pressed(k),
+			Binding::Mouse(b) => input.mouse_pressed(b),
+			Binding::Gamepad(b) => input.gamepads().iter().any(|p| p.pressed(b)),
+			Binding::TouchRegion(min, max) => any_in_region(input, input.touches_started(), min, max),
+		}
+	}
+
+	fn released(&self, input: &Input) -> bool {
+		match *self {
+			Binding::Key(k) => input.key_released(k),
+			Binding::Mouse(b) => input.mouse_released(b),
+			Binding::Gamepad(b) => input.gamepads().iter().any(|p| p.released(b)),
+			Binding::TouchRegion(min, max) => any_in_region(input, input.touches_ended(), min, max),
+		}
+	}
+}
+
+fn any_in_region(input: &Input, touches: &[Touch], min: Vec2, max: Vec2) -> bool {
+	let size = input.screen_size();
+	if size.x <= 0 || size.y <= 0 { return false }
+
+	touches.iter().any(|t| {
+		let x = t.pos.x as f32 / size.x as f32;
+		let y = t.pos.y as f32 / size.y as f32;
+		x >= min.x && x <= max.x && y >= min.y && y <= max.y
+	})
+}
+
+impl AxisBinding {
+	fn value(&self, input: &Input) -> f32 {
+		match *self {
+			AxisBinding::Keys(neg, pos) => {
+				let mut v = 0.0;
+				if input.key_held(neg) { v -= 1.0 }
+				if input.key_held(pos) { v += 1.0 }
+				v
+			}
+
+			AxisBinding::MouseX(s) => input.mouse_delta().x as f32 * s,
+			AxisBinding::MouseY(s) => input.mouse_delta().y as f32 * s,
+			AxisBinding::WheelX(s) => input.wheel_delta().x * s,
+			AxisBinding::WheelY(s) => input.wheel_delta().y * s,
+
+			// Largest deflection wins so an idle second pad doesn't cancel the first
+			AxisBinding::Gamepad(a, s) => {
+				input.gamepads().iter()
+					.map(|p| p.axis(a) * s)
+					.fold(0.0, |acc, v| if v.abs() > acc.abs() { v } else { acc })
+			}
+		}
+	}
+}
+
+impl Axis {
+	pub fn new() -> Axis {
+		Axis { bindings: Vec::new(), dead_zone: 0.0 }
+	}
+
+	pub fn value(&self, input: &Input) -> f32 {
+		let v: f32 = self.bindings.iter().map(|b| b.value(input)).sum();
+		apply_dead_zone(v, self.dead_zone)
+	}
+}
+
+// Zeroes values inside the dead zone and rescales the rest so the output stays continuous
+pub fn apply_dead_zone(v: f32, dead_zone: f32) -> f32 {
+	if dead_zone <= 0.0 { return v }
+	if dead_zone >= 1.0 { return 0.0 }
+
+	let m = v.abs();
+	if m <= dead_zone {
+		0.0
+	} else {
+		v.signum() * (m - dead_zone) / (1.0 - dead_zone)
+	}
+}
+
+// Names are written into config headers, so they can't be empty or contain whitespace or brackets
+pub fn is_valid_name(name: &str) -> bool {
+	!name.is_empty() && !name.starts_with('#') && !name.chars().any(|c| c.is_whitespace() || c == '[' || c == ']')
+}
+
+fn check_name(name: &str) {
+	assert!(is_valid_name(name), "invalid action or axis name {:?}", name);
+}
+
+impl ActionMap {
+	pub fn new() -> ActionMap {
+		ActionMap {
+			actions: HashMap::new(),
+			axes: HashMap::new(),
+		}
+	}
+
+	pub fn bind(&mut self, action: &str, binding: Binding) {
+		self.bind_chord(action, binding, Modifiers::none());
+	}
+
+	// Panics if `action` isn't a valid name
+	pub fn bind_chord(&mut self, action: &str, binding: Binding, modifiers: Modifiers) {
+		check_name(action);
+		let chord = Chord { binding, modifiers };
+		let chords = self.actions.entry(action.to_owned()).or_insert_with(Vec::new);
+		if !chords.contains(&chord) {
+			chords.push(chord);
+		}
+	}
+
+	pub fn unbind(&mut self, action: &str, binding: Binding) {
+		if let Some(chords) = self.actions.get_mut(action) {
+			chords.retain(|c| c.binding != binding);
+		}
+	}
+
+	// Replaces every chord using `old` with `new`, keeping their modifiers
+	pub fn rebind(&mut self, action: &str, old: Binding, new: Binding) {
+		if let Some(chords) = self.actions.get_mut(action) {
+			for c in chords.iter_mut().filter(|c| c.binding == old) {
+				c.binding = new;
+			}
+		}
+	}
+
+	pub fn clear_action(&mut self, action: &str) {
+		self.actions.remove(action);
+	}
+
+	pub fn bindings(&self, action: &str) -> &[Chord] {
+		self.actions.get(action).map(|c| c.as_slice()).unwrap_or(&[])
+	}
+
+	// Panics if `axis` isn't a valid name
+	pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) {
+		check_name(axis);
+		let axis = self.axes.entry(axis.to_owned()).or_insert_with(Axis::new);
+		if !axis.bindings.contains(&binding) {
+			axis.bindings.push(binding);
+		}
+	}
+
+	pub fn unbind_axis(&mut self, axis: &str, binding: AxisBinding) {
+		if let Some(axis) = self.axes.get_mut(axis) {
+			axis.bindings.retain(|b| *b != binding);
+		}
+	}
+
+	pub fn set_dead_zone(&mut self, axis: &str, dead_zone: f32) {
+		check_name(axis);
+		self.axes.entry(axis.to_owned()).or_insert_with(Axis::new).dead_zone = dead_zone;
+	}
+
+	pub fn clear_axis(&mut self, axis: &str) {
+		self.axes.remove(axis);
+	}
+
+	pub fn axis_bindings(&self, axis: &str) -> Option<&Axis> {
+		self.axes.get(axis)
+	}
+
+	pub fn held(&self, input: &Input, action: &str) -> bool {
+		self.any_chord(input, action, |b| b.held(input))
+	}
+
+	pub fn pressed(&self, input: &Input, action: &str) -> bool {
+		self.any_chord(input, action, |b| b.pressed(input))
+	}
+
+	pub fn released(&self, input: &Input, action: &str) -> bool {
+		self.any_chord(input, action, |b| b.released(input))
+	}
+
+	pub fn axis(&self, input: &Input, axis: &str) -> f32 {
+		self.axes.get(axis).map(|a| a.value(input)).unwrap_or(0.0)
+	}
+
+	fn any_chord<F>(&self, input: &Input, action: &str, f: F) -> bool where F: Fn(&Binding) -> bool {
+		let mods = input.modifiers();
+		self.bindings(action).iter()
+			.any(|c| c.modifiers.satisfied_by(mods) && !self.shadowed(c, mods) && f(&c.binding))
+	}
+
+	// True if a chord on the same input, in any action, needs more of the held modifiers
+	fn shadowed(&self, chord: &Chord, held: Modifiers) -> bool {
+		self.actions.values().flat_map(|c| c.iter()).any(|o| {
+			o.binding == chord.binding
+				&& o.modifiers.satisfied_by(held)
+				&& o.modifiers.count() > chord.modifiers.count()
+		})
+	}
+
+	// Returns the first input pressed this frame, for "press a key to rebind" screens
+	pub fn capture(input: &Input) -> Option<Binding> {
+		if let Some(k) = input.pressed_keys().next() {
+			return Some(Binding::Key(*k))
+		}
+
+		if let Some(b) = input.pressed_buttons().next() {
+			return Some(Binding::Mouse(*b))
+		}
+
+		for pad in input.gamepads() {
+			let count = pad.state.buttons.len() as u32;
+			if let Some(b) = (0..count).map(GamepadButton::from_index).find(|&b| pad.pressed(b)) {
+				return Some(Binding::Gamepad(b))
+			}
+		}
+
+		None
+	}
+
+	pub fn to_config(&self) -> String {
+		let mut out = String::new();
+
+		let mut actions: Vec<_> = self.actions.iter().collect();
+		actions.sort_by(|a, b| a.0.cmp(b.0));
+
+		for (name, chords) in actions {
+			out.push_str(&format!("[action {}]\n", name));
+			for c in chords.iter() {
+				out.push_str(&binding_to_string(&c.binding));
+				if c.modifiers.shift { out.push_str(" +shift") }
+				if c.modifiers.ctrl { out.push_str(" +ctrl") }
+				if c.modifiers.alt { out.push_str(" +alt") }
+				if c.modifiers.meta { out.push_str(" +meta") }
+				out.push('\n');
+			}
+			out.push('\n');
+		}
+
+		let mut axes: Vec<_> = self.axes.iter().collect();
+		axes.sort_by(|a, b| a.0.cmp(b.0));
+
+		for (name, axis) in axes {
+			out.push_str(&format!("[axis {}]\n", name));
+			out.push_str(&format!("deadzone {}\n", axis.dead_zone));
+			for b in axis.bindings.iter() {
+				out.push_str(&axis_binding_to_string(b));
+				out.push('\n');
+			}
+			out.push('\n');
+		}
+
+		out
+	}
+
+	pub fn from_config(src: &str) -> Result<ActionMap, String> {
+		enum Section { None, Action(String), Axis(String) }
+
+		let mut map = ActionMap::new();
+		let mut section = Section::None;
+
+		for (i, line) in src.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') { continue }
+
+			let err = |msg: &str| Err(format!("line {}: {}", i + 1, msg));
+
+			if line.starts_with('[') {
+				if !line.ends_with(']') {
+					return err("unterminated section header")
+				}
+
+				let mut parts = line[1..line.len() - 1].split_whitespace();
+				section = match (parts.next(), parts.next(), parts.next()) {
+					(Some("action"), Some(name), None) | (Some("axis"), Some(name), None) if !is_valid_name(name) => {
+						return err("invalid action or axis name")
+					}
+					(Some("action"), Some(name), None) => {
+						map.actions.entry(name.to_owned()).or_insert_with(Vec::new);
+						Section::Action(name.to_owned())
+					}
+					(Some("axis"), Some(name), None) => {
+						map.axes.entry(name.to_owned()).or_insert_with(Axis::new);
+						Section::Axis(name.to_owned())
+					}
+					_ => return err("expected [action <name>] or [axis <name>]")
+				};
+				continue
+			}
+
+			let tokens: Vec<&str> = line.split_whitespace().collect();
+
+			match section {
+				Section::None => return err("binding outside of a section"),
+
+				Section::Action(ref name) => {
+					let mut modifiers = Modifiers::none();
+					let mut rest = Vec::new();
+					for t in tokens {
+						match t {
+							"+shift" => modifiers.shift = true,
+							"+ctrl" => modifiers.ctrl = true,
+							"+alt" => modifiers.alt = true,
+							"+meta" => modifiers.meta = true,
+							t => rest.push(t),
+						}
+					}
+
+					match parse_binding(&rest) {
+						Some(b) => map.bind_chord(name, b, modifiers),
+						None => return err("invalid action binding")
+					}
+				}
+
+				Section::Axis(ref name) => {
+					if tokens[0] == "deadzone" {
+						match tokens.get(1).and_then(|t| t.parse().ok()) {
+							Some(dz) if tokens.len() == 2 => map.set_dead_zone(name, dz),
+							_ => return err("invalid dead zone")
+						}
+						continue
+					}
+
+					match parse_axis_binding(&tokens) {
+						Some(b) => map.bind_axis(name, b),
+						None => return err("invalid axis binding")
+					}
+				}
+			}
+		}
+
+		Ok(map)
+	}
+
+	pub fn save(&self, path: &str) -> io::Result<()> {
+		let mut file = File::create(path)?;
+		file.write_all(self.to_config().as_bytes())
+	}
+
+	pub fn load(path: &str) -> Result<ActionMap, String> {
+		let mut src = String::new();
+		File::open(path)
+			.and_then(|mut f| f.read_to_string(&mut src))
+			.map_err(|e| format!("{}: {}", path, e))?;
+
+		ActionMap::from_config(&src)
+	}
+}
+
+fn binding_to_string(b: &Binding) -> String {
+	match *b {
+		Binding::Key(k) => format!("key {}", k.code()),
+		Binding::Mouse(b) => format!("mouse {}", b.name()),
+		Binding::Gamepad(b) => format!("pad {}", b.name()),
+		Binding::TouchRegion(min, max) => format!("touch {} {} {} {}", min.x, min.y, max.x, max.y),
+	}
+}
+
+fn axis_binding_to_string(b: &AxisBinding) -> String {
+	match *b {
+		AxisBinding::Keys(neg, pos) => format!("keys {} {}", neg.code(), pos.code()),
+		AxisBinding::MouseX(s) => format!("mouse_x {}", s),
+		AxisBinding::MouseY(s) => format!("mouse_y {}", s),
+		AxisBinding::WheelX(s) => format!("wheel_x {}", s),
+		AxisBinding::WheelY(s) => format!("wheel_y {}", s),
+		AxisBinding::Gamepad(a, s) => format!("pad_axis {} {}", a.name(), s),
+	}
+}
+
+fn parse_floats(tokens: &[&str]) -> Option<Vec<f32>> {
+	tokens.iter().map(|t| t.parse().ok()).collect()
+}
+
+fn parse_binding(tokens: &[&str]) -> Option<Binding> {
+	match tokens.split_first() {
+		Some((&"key", &[k])) => KeyCode::from_code(k).map(Binding::Key),
+		Some((&"mouse", &[b])) => MouseButton::from_name(b).map(Binding::Mouse),
+		Some((&"pad", &[b])) => GamepadButton::from_name(b).map(Binding::Gamepad),
+		Some((&"touch", args)) if args.len() == 4 => {
+			let v = parse_floats(args)?;
+			Some(Binding::TouchRegion(Vec2::new(v[0], v[1]), Vec2::new(v[2], v[3])))
+		}
+		_ => None
+	}
+}
+
+fn parse_axis_binding(tokens: &[&str]) -> Option<AxisBinding> {
+	match tokens.split_first() {
+		Some((&"keys", &[neg, pos])) => {
+			Some(AxisBinding::Keys(KeyCode::from_code(neg)?, KeyCode::from_code(pos)?))
+		}
+		Some((&"mouse_x", &[s])) => s.parse().ok().map(AxisBinding::MouseX),
+		Some((&"mouse_y", &[s])) => s.parse().ok().map(AxisBinding::MouseY),
+		Some((&"wheel_x", &[s])) => s.parse().ok().map(AxisBinding::WheelX),
+		Some((&"wheel_y", &[s])) => s.parse().ok().map(AxisBinding::WheelY),
+		Some((&"pad_axis", &[a, s])) => Some(AxisBinding::Gamepad(GamepadAxis::from_name(a)?, s.parse().ok()?)),
+		_ => None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::event::*;
+
+	fn key_down(code: KeyCode, modifiers: Modifiers) -> Event {
+		let mut k = KeyEvent::new(code, Key::new("a"));
+		k.modifiers = modifiers;
+		Event::KeyDown(k)
+	}
+
+	fn ctrl() -> Modifiers {
+		Modifiers { ctrl: true, ..Modifiers::none() }
+	}
+
+	#[test]
+	fn most_specific_chord_wins() {
+		let mut map = ActionMap::new();
+		map.bind_chord("save", Binding::Key(KeyCode::KeyS), ctrl());
+		map.bind("back", Binding::Key(KeyCode::KeyS));
+
+		let mut input = Input::new();
+		input.update(&[key_down(KeyCode::ControlLeft, ctrl()), key_down(KeyCode::KeyS, ctrl())]);
+		assert!(map.pressed(&input, "save"));
+		assert!(!map.pressed(&input, "back"));
+
+		let mut input = Input::new();
+		input.update(&[key_down(KeyCode::KeyS, Modifiers::none())]);
+		assert!(!map.pressed(&input, "save"));
+		assert!(map.pressed(&input, "back"));
+	}
+
+	#[test]
+	fn unrelated_modifiers_dont_block_plain_chords() {
+		let mut map = ActionMap::new();
+		map.bind("forward", Binding::Key(KeyCode::KeyW));
+
+		let shift = Modifiers { shift: true, ..Modifiers::none() };
+		let mut input = Input::new();
+		input.update(&[key_down(KeyCode::KeyW, Modifiers::none()), key_down(KeyCode::ShiftLeft, shift)]);
+		assert!(map.held(&input, "forward"));
+	}
+
+	#[test]
+	fn config_round_trip() {
+		let mut map = ActionMap::new();
+		map.bind("jump", Binding::Key(KeyCode::Space));
+		map.bind("jump", Binding::Gamepad(GamepadButton::South));
+		map.bind_chord("save", Binding::Key(KeyCode::KeyS), ctrl());
+		map.bind("fire", Binding::Mouse(MouseButton::Left));
+		map.bind("fire", Binding::TouchRegion(Vec2::new(0.5, 0.0), Vec2::new(1.0, 1.0)));
+		map.bind_axis("move_x", AxisBinding::Keys(KeyCode::KeyA, KeyCode::KeyD));
+		map.bind_axis("move_x", AxisBinding::Gamepad(GamepadAxis::LeftX, 1.0));
+		map.set_dead_zone("move_x", 0.25);
+		map.bind_axis("look_y", AxisBinding::MouseY(0.5));
+
+		let config = map.to_config();
+		let loaded = ActionMap::from_config(&config).unwrap();
+		assert_eq!(loaded.to_config(), config);
+		assert_eq!(loaded.bindings("save"), map.bindings("save"));
+		assert_eq!(loaded.axis_bindings("move_x").unwrap().dead_zone, 0.25);
+	}
+
+	#[test]
+	fn invalid_config_is_an_error() {
+		assert!(ActionMap::from_config("key Space").is_err());
+		assert!(ActionMap::from_config("[action jump\nkey Space").is_err());
+		assert!(ActionMap::from_config("[action move left]").is_err());
+		assert!(ActionMap::from_config("[action jump]\nkey NotAKey").is_err());
+		assert!(ActionMap::from_config("[action #x]\nkey Space").is_err());
+		assert!(ActionMap::from_config("[action a[b]\nkey Space").is_err());
+		assert!(ActionMap::from_config("[axis #x]\ndeadzone 0.2").is_err());
+	}
+
+	#[test]
+	fn names_are_checked() {
+		assert!(is_valid_name("move_x"));
+		assert!(!is_valid_name(""));
+		assert!(!is_valid_name("move left"));
+		assert!(!is_valid_name("jump]"));
+	}
+
+	#[test]
+	#[should_panic]
+	fn binding_an_invalid_name_panics() {
+		ActionMap::new().bind("move left", Binding::Key(KeyCode::KeyA));
+	}
+}
\ No newline at end of file
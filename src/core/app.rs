@@ -0,0 +1,485 @@
+#[cfg(target_os = "emscripten")]
+use bindings::emscripten::*;
+use math::vec::*;
+use core::event::*;
+use core::input::*;
+use core::replay::*;
+
+pub trait App {
+	fn init(&mut self, _state: &mut LoopState) {}
+
+	// Once per frame with the frame's events, `dt` in seconds
+	fn update(&mut self, _state: &mut LoopState, _events: &[Event], _dt: f32) {}
+
+	// Zero or more times per frame with a constant `dt`
+	fn fixed_update(&mut self, _state: &mut LoopState, _dt: f32) {}
+
+	// `alpha` is how far between the last two fixed updates this frame is, in [0, 1)
+	fn render(&mut self, _state: &mut LoopState, _alpha: f32) {}
+
+	fn resize(&mut self, _state: &mut LoopState, _size: Vec2i) {}
+
+	fn shutdown(&mut self, _state: &mut LoopState) {}
+
+	// The page is closing; flush save data here. No further frames will run
+	fn before_unload(&mut self, _state: &mut LoopState) {}
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LoopConfig {
+	// Seconds per fixed update
+	pub fixed_dt: f64,
+
+	// Frames longer than this are clamped, so a stall doesn't trigger a burst of fixed updates
+	pub max_frame_time: f64,
+
+	// Fixed updates per frame before the remaining backlog is dropped
+	pub max_fixed_steps: u32,
+
+	pub time_scale: f64,
+
+	// Pause while the page is hidden and resume when it's shown again
+	pub pause_when_hidden: bool,
+}
+
+impl Default for LoopConfig {
+	fn default() -> LoopConfig {
+		LoopConfig {
+			fixed_dt: 1.0 / 60.0,
+			max_frame_time: 0.25,
+			max_fixed_steps: 8,
+			time_scale: 1.0,
+			pause_when_hidden: true,
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameStats {
+	pub frame_count: u64,
+	pub fixed_step_count: u64,
+
+	// Fixed updates run this frame
+	pub fixed_steps: u32,
+
+	// Seconds of simulation dropped by clamping, since start
+	pub dropped_time: f64,
+
+	// Scaled, unpaused time in seconds since start
+	pub elapsed: f64,
+
+	// Unclamped duration of the last frame in seconds
+	pub frame_time: f64,
+
+	// Exponential moving average
+	pub fps: f64,
+}
+
+// Shared with the App on every call
+pub struct LoopState {
+	pub config: LoopConfig,
+	pub input: Input,
+	pub stats: FrameStats,
+	pub screen_size: Vec2i,
+
+	paused: bool,
+	auto_paused: bool,
+	quit: bool,
+}
+
+impl LoopState {
+	pub fn new(config: LoopConfig) -> LoopState {
+		LoopState {
+			config,
+			input: Input::new(),
+			stats: FrameStats::default(),
+			screen_size: Vec2i::zero(),
+
+			paused: false,
+			auto_paused: false,
+			quit: false,
+		}
+	}
+
+	// While paused, events are still processed and frames rendered, but no time passes
+	pub fn pause(&mut self) { self.paused = true }
+	pub fn resume(&mut self) { self.paused = false; self.auto_paused = false }
+	pub fn is_paused(&self) -> bool { self.paused }
+
+	// Stops the loop after the current frame
+	pub fn quit(&mut self) { self.quit = true }
+	pub fn is_quitting(&self) -> bool { self.quit }
+}
+
+pub struct Runner<A: App> {
+	pub app: A,
+	pub state: LoopState,
+	pub events: EventLoop,
+
+	recording: Option<Recording>,
+	accumulator: f64,
+	last_time: Option<f64>,
+	initialised: bool,
+	finished: bool,
+}
+
+impl<A: App> Runner<A> {
+	pub fn new(app: A, config: LoopConfig) -> Runner<A> {
+		#[cfg(target_os = "emscripten")]
+		let events = EventLoop::new();
+		#[cfg(not(target_os = "emscripten"))]
+		let events = EventLoop::headless();
+
+		Runner::with_event_loop(app, config, events)
+	}
+
+	// Drive with `step` and synthetic events, without a browser
+	pub fn headless(app: A, config: LoopConfig) -> Runner<A> {
+		Runner::with_event_loop(app, config, EventLoop::headless())
+	}
+
+	pub fn with_event_loop(app: A, config: LoopConfig, events: EventLoop) -> Runner<A> {
+		Runner {
+			app,
+			state: LoopState::new(config),
+			events,
+
+			recording: None,
+			accumulator: 0.0,
+			last_time: None,
+			initialised: false,
+			finished: false,
+		}
+	}
+
+	pub fn is_finished(&self) -> bool { self.finished }
+
+	// Captures each following frame's events and duration for `Recording::play`
+	pub fn start_recording(&mut self) {
+		self.recording = Some(Recording::new());
+	}
+
+	pub fn stop_recording(&mut self) -> Option<Recording> {
+		self.recording.take()
+	}
+
+	pub fn is_recording(&self) -> bool { self.recording.is_some() }
+
+	// Runs one frame at time `now` in seconds. Returns false once the app has quit
+	pub fn step(&mut self, now: f64) -> bool {
+		let frame_time = match self.last_time {
+			Some(last) => (now - last).max(0.0),
+			None => 0.0
+		};
+		self.last_time = Some(now);
+		self.step_dt(frame_time)
+	}
+
+	// Runs one frame lasting `frame_time` seconds. Returns false once the app has quit
+	pub fn step_dt(&mut self, frame_time: f64) -> bool {
+		if self.finished { return false }
+
+		if !self.initialised {
+			self.initialised = true;
+			self.app.init(&mut self.state);
+		}
+
+		let events: Vec<Event> = self.events.poll_events().collect();
+		if let Some(ref mut recording) = self.recording {
+			recording.record_frame(frame_time, &events);
+		}
+
+		self.state.input.update(&events);
+
+		for e in events.iter() {
+			match *e {
+				Event::Resize(size) => {
+					self.state.screen_size = size;
+					self.app.resize(&mut self.state, size);
+				}
+
+				Event::Hidden => {
+					if self.state.config.pause_when_hidden && !self.state.paused {
+						self.state.paused = true;
+						self.state.auto_paused = true;
+					}
+				}
+
+				Event::Visible => {
+					if self.state.auto_paused {
+						self.state.resume();
+					}
+				}
+
+				Event::BeforeUnload => {
+					self.app.before_unload(&mut self.state);
+				}
+
+				_ => {}
+			}
+		}
+
+		let config = self.state.config;
+		let clamped = frame_time.min(config.max_frame_time);
+		let dt = if self.state.paused { 0.0 } else { clamped * config.time_scale };
+
+		{
+			let stats = &mut self.state.stats;
+			stats.frame_count += 1;
+			stats.frame_time = frame_time;
+			stats.dropped_time += frame_time - clamped;
+			stats.elapsed += dt;
+
+			if frame_time > 0.0 {
+				let fps = 1.0 / frame_time;
+				stats.fps = if stats.fps == 0.0 { fps } else { stats.fps * 0.9 + fps * 0.1 };
+			}
+		}
+
+		self.app.update(&mut self.state, &events, dt as f32);
+
+		// `update` may have paused, and time can't build up while nothing consumes it
+		if !self.state.paused {
+			self.accumulator += dt;
+		}
+
+		let mut steps = 0;
+		while self.accumulator >= config.fixed_dt && !self.state.paused {
+			if steps >= config.max_fixed_steps {
+				// Give up on catching up rather than spiralling
+				let backlog = self.accumulator - self.accumulator % config.fixed_dt;
+				self.state.stats.dropped_time += backlog;
+				self.accumulator -= backlog;
+				break
+			}
+
+			self.app.fixed_update(&mut self.state, config.fixed_dt as f32);
+			self.accumulator -= config.fixed_dt;
+			steps += 1;
+		}
+
+		self.state.stats.fixed_steps = steps;
+		self.state.stats.fixed_step_count += steps as u64;
+
+		// Rounding to f32 can turn an accumulator just under `fixed_dt` into 1
+		let alpha = ((self.accumulator / config.fixed_dt) as f32).min(1.0 - ::std::f32::EPSILON);
+		self.app.render(&mut self.state, alpha);
+
+		if self.state.quit {
+			self.finish();
+			return false
+		}
+
+		true
+	}
+
+	// Calls `shutdown` once. Also done by `step` when the app quits
+	pub fn finish(&mut self) {
+		if self.finished { return }
+		self.finished = true;
+
+		if self.initialised {
+			self.app.shutdown(&mut self.state);
+		}
+	}
+
+	// Hands control to the browser's requestAnimationFrame loop. Never returns
+	#[cfg(target_os = "emscripten")]
+	pub fn run(self) where A: 'static {
+		let runner = Box::into_raw(Box::new(self));
+
+		unsafe {
+			// Run one last zero-length frame so the app sees BeforeUnload
+			(*runner).events.set_unload_handler(Box::new(move || {
+				(*runner).step_dt(0.0);
+			}));
+
+			emscripten_set_main_loop_arg(Some(main_loop_frame::<A>), runner as *mut CVoid, 0, 1);
+		}
+	}
+
+	// Runs until the app quits, sleeping to roughly match the fixed rate
+	#[cfg(not(target_os = "emscripten"))]
+	pub fn run(mut self) where A: 'static {
+		use std::thread::sleep;
+		use std::time::{ Duration, Instant };
+
+		let start = Instant::now();
+		while self.step(seconds_since(start)) {
+			let wait = self.state.config.fixed_dt - self.accumulator;
+			if wait > 0.0 {
+				sleep(Duration::from_millis((wait * 1000.0) as u64));
+			}
+		}
+	}
+}
+
+impl<A: App> Drop for Runner<A> {
+	fn drop(&mut self) {
+		self.finish();
+	}
+}
+
+#[cfg(target_os = "emscripten")]
+unsafe extern "C" fn main_loop_frame<A: App>(ud: *mut CVoid) {
+	let runner = ud as *mut Runner<A>;
+
+	if !(*runner).step(now_seconds()) {
+		emscripten_cancel_main_loop();
+		drop(Box::from_raw(runner));
+	}
+}
+
+// Monotonic time in seconds
+#[cfg(target_os = "emscripten")]
+pub fn now_seconds() -> f64 {
+	unsafe { emscripten_get_now() / 1000.0 }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+fn seconds_since(start: ::std::time::Instant) -> f64 {
+	let d = start.elapsed();
+	d.as_secs() as f64 + d.subsec_nanos() as f64 * 1e-9
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Default)]
+	struct Counter {
+		updates: Vec<f32>,
+		fixed: u32,
+		alphas: Vec<f32>,
+		pause_in_update: bool,
+	}
+
+	impl App for Counter {
+		fn update(&mut self, state: &mut LoopState, _: &[Event], dt: f32) {
+			self.updates.push(dt);
+			if self.pause_in_update { state.pause() }
+		}
+
+		fn fixed_update(&mut self, _: &mut LoopState, _: f32) {
+			self.fixed += 1;
+		}
+
+		fn render(&mut self, _: &mut LoopState, alpha: f32) {
+			assert!(alpha >= 0.0 && alpha < 1.0, "alpha {}", alpha);
+			self.alphas.push(alpha);
+		}
+	}
+
+	// Binary fractions, so the accumulator is exact
+	fn config() -> LoopConfig {
+		LoopConfig {
+			fixed_dt: 0.125,
+			max_frame_time: 1.0,
+			max_fixed_steps: 4,
+			.. LoopConfig::default()
+		}
+	}
+
+	fn runner() -> Runner<Counter> {
+		Runner::headless(Counter::default(), config())
+	}
+
+	#[test]
+	fn accumulates_partial_steps() {
+		let mut r = runner();
+		r.step_dt(0.3125);
+		assert_eq!(r.app.fixed, 2);
+		assert_eq!(r.app.alphas[0], 0.5);
+
+		r.step_dt(0.0625);
+		assert_eq!(r.app.fixed, 3);
+		assert_eq!(r.app.alphas[1], 0.0);
+
+		r.step_dt(0.09375);
+		assert_eq!(r.app.fixed, 3);
+		assert_eq!(r.app.alphas[2], 0.75);
+		assert_eq!(r.state.stats.frame_count, 3);
+		assert_eq!(r.state.stats.fixed_step_count, 3);
+	}
+
+	#[test]
+	fn step_uses_time_between_calls() {
+		let mut r = runner();
+		r.step(10.0);
+		r.step(10.375);
+		assert_eq!(r.app.updates, vec![0.0, 0.375]);
+		assert_eq!(r.app.fixed, 3);
+	}
+
+	#[test]
+	fn long_frames_are_clamped() {
+		let mut r = runner();
+		r.step_dt(5.0);
+		assert_eq!(r.app.updates, vec![1.0]);
+
+		// Only max_fixed_steps of the 8 owed are run, and the other 4 are dropped along with the clamped 4s
+		assert_eq!(r.app.fixed, 4);
+		assert_eq!(r.state.stats.fixed_steps, 4);
+		assert_eq!(r.state.stats.dropped_time, 4.5);
+		assert_eq!(r.app.alphas, vec![0.0]);
+	}
+
+	#[test]
+	fn time_scale() {
+		let mut r = runner();
+		r.state.config.time_scale = 0.5;
+		r.step_dt(0.5);
+		assert_eq!(r.app.fixed, 2);
+		assert_eq!(r.state.stats.elapsed, 0.25);
+	}
+
+	#[test]
+	fn alpha_stays_below_one() {
+		// 0.3 - 3 * 0.1 leaves the accumulator a hair under 0.1, which rounds to 1 as an f32
+		let mut r = runner();
+		r.state.config.fixed_dt = 0.1;
+		r.step_dt(0.3);
+		assert!(r.app.alphas[0] < 1.0);
+	}
+
+	#[test]
+	fn pause_and_resume() {
+		let mut r = runner();
+		r.state.pause();
+		r.step_dt(0.5);
+		assert_eq!(r.app.updates, vec![0.0]);
+		assert_eq!(r.app.fixed, 0);
+		assert_eq!(r.state.stats.elapsed, 0.0);
+
+		r.state.resume();
+		r.step_dt(0.375);
+		assert_eq!(r.app.fixed, 3);
+	}
+
+	#[test]
+	fn pausing_in_update_keeps_alpha_in_range() {
+		let mut r = runner();
+		r.step_dt(0.0625);
+		r.app.pause_in_update = true;
+
+		// The frame's time would otherwise sit in the accumulator with nothing to consume it
+		r.step_dt(0.75);
+		r.step_dt(0.75);
+		assert_eq!(r.app.fixed, 0);
+		assert_eq!(r.app.alphas, vec![0.5, 0.5, 0.5]);
+	}
+
+	#[test]
+	fn hidden_pauses_until_visible() {
+		let mut r = runner();
+		r.events.push(Event::Hidden);
+		r.step_dt(0.5);
+		assert!(r.state.is_paused());
+		assert_eq!(r.app.fixed, 0);
+
+		r.events.push(Event::Visible);
+		r.step_dt(0.25);
+		assert!(!r.state.is_paused());
+		assert_eq!(r.app.fixed, 2);
+	}
+}
\ No newline at end of file
@@ -1,5 +1,6 @@
 use bindings::emscripten::*;
 use bindings::gl;
+use gfx::color::*;
 
 pub struct Context { ctx: EMSCRIPTEN_WEBGL_CONTEXT_HANDLE }
 
@@ -54,9 +55,11 @@ impl Context {
 		}
 	}
 
-	pub fn set_clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+	// Takes a Color, Vec4 or (r, g, b, a)
+	pub fn set_clear_color<C: Into<Color>>(&self, color: C) {
+		let c = color.into();
 		unsafe {
-			gl::ClearColor(r, g, b, a);
+			gl::ClearColor(c.r, c.g, c.b, c.a);
 		}
 	}
 
@@ -66,6 +69,19 @@ impl Context {
 		}
 	}
 
+	// Enables a WebGL extension such as "WEBGL_depth_texture". False if it isn't supported
+	pub fn enable_extension(&self, name: &str) -> bool {
+		// No extension name has a NUL in it
+		let name = match ::std::ffi::CString::new(name) {
+			Ok(name) => name,
+			Err(_) => return false
+		};
+
+		unsafe {
+			emscripten_webgl_enable_extension(self.ctx, name.as_ptr() as _) != 0
+		}
+	}
+
 	pub fn destroy(&self) {
 		unsafe {
 			emscripten_webgl_destroy_context(self.ctx);
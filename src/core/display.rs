@@ -0,0 +1,123 @@
+use std::mem::zeroed;
+use std::ptr::null_mut;
+
+use bindings::emscripten::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FullscreenScale {
+	// Leave the canvas at its size and let the browser place it
+	Default,
+	Stretch,
+	// Stretch while keeping the aspect ratio, letterboxing the rest
+	Aspect,
+	Center,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FullscreenResolution {
+	// Keep the current drawing buffer size
+	Unchanged,
+	// Resize the drawing buffer to the screen size in CSS pixels
+	Standard,
+	// Resize the drawing buffer to the screen size in device pixels
+	HighDpi,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FullscreenFilter {
+	Default,
+	Nearest,
+	Bilinear,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FullscreenOptions {
+	pub scale: FullscreenScale,
+	pub resolution: FullscreenResolution,
+	pub filter: FullscreenFilter,
+}
+
+impl Default for FullscreenOptions {
+	fn default() -> FullscreenOptions {
+		FullscreenOptions {
+			scale: FullscreenScale::Aspect,
+			resolution: FullscreenResolution::Standard,
+			filter: FullscreenFilter::Default,
+		}
+	}
+}
+
+impl FullscreenOptions {
+	fn strategy(&self) -> EmscriptenFullscreenStrategy {
+		EmscriptenFullscreenStrategy {
+			scaleMode: match self.scale {
+				FullscreenScale::Default => EMSCRIPTEN_FULLSCREEN_SCALE_DEFAULT,
+				FullscreenScale::Stretch => EMSCRIPTEN_FULLSCREEN_SCALE_STRETCH,
+				FullscreenScale::Aspect => EMSCRIPTEN_FULLSCREEN_SCALE_ASPECT,
+				FullscreenScale::Center => EMSCRIPTEN_FULLSCREEN_SCALE_CENTER,
+			},
+			canvasResolutionScaleMode: match self.resolution {
+				FullscreenResolution::Unchanged => EMSCRIPTEN_FULLSCREEN_CANVAS_SCALE_NONE,
+				FullscreenResolution::Standard => EMSCRIPTEN_FULLSCREEN_CANVAS_SCALE_STDDEF,
+				FullscreenResolution::HighDpi => EMSCRIPTEN_FULLSCREEN_CANVAS_SCALE_HIDEF,
+			},
+			filteringMode: match self.filter {
+				FullscreenFilter::Default => EMSCRIPTEN_FULLSCREEN_FILTERING_DEFAULT,
+				FullscreenFilter::Nearest => EMSCRIPTEN_FULLSCREEN_FILTERING_NEAREST,
+				FullscreenFilter::Bilinear => EMSCRIPTEN_FULLSCREEN_FILTERING_BILINEAR,
+			},
+			canvasResizedCallback: None,
+			canvasResizedCallbackUserData: null_mut(),
+		}
+	}
+}
+
+// Browsers only allow fullscreen and pointer lock from inside an input handler,
+// so requests made from the main loop are deferred until the next key or click.
+// These return false only if the request failed outright.
+
+fn succeeded(result: i32) -> bool {
+	result == EMSCRIPTEN_RESULT_SUCCESS || result == EMSCRIPTEN_RESULT_DEFERRED
+}
+
+pub fn request_fullscreen(options: FullscreenOptions) -> bool {
+	let strategy = options.strategy();
+	unsafe {
+		succeeded(emscripten_request_fullscreen_strategy(b"#canvas\0".as_ptr() as _, 1, &strategy))
+	}
+}
+
+pub fn exit_fullscreen() -> bool {
+	unsafe { succeeded(emscripten_exit_fullscreen()) }
+}
+
+pub fn is_fullscreen() -> bool {
+	unsafe {
+		let mut fse: EmscriptenFullscreenChangeEvent = zeroed();
+		emscripten_get_fullscreen_status(&mut fse) == EMSCRIPTEN_RESULT_SUCCESS && fse.isFullscreen != 0
+	}
+}
+
+pub fn toggle_fullscreen(options: FullscreenOptions) -> bool {
+	if is_fullscreen() {
+		exit_fullscreen()
+	} else {
+		request_fullscreen(options)
+	}
+}
+
+// While locked the cursor is hidden and mouse movement arrives as `Event::RawMotion`
+pub fn request_pointer_lock() -> bool {
+	unsafe { succeeded(emscripten_request_pointerlock(b"#canvas\0".as_ptr() as _, 1)) }
+}
+
+pub fn exit_pointer_lock() -> bool {
+	unsafe { succeeded(emscripten_exit_pointerlock()) }
+}
+
+pub fn is_pointer_locked() -> bool {
+	unsafe {
+		let mut ple: EmscriptenPointerlockChangeEvent = zeroed();
+		emscripten_get_pointerlock_status(&mut ple) == EMSCRIPTEN_RESULT_SUCCESS && ple.isActive != 0
+	}
+}
\ No newline at end of file
@@ -1,68 +1,265 @@
 // Original code from <https://github.com/manpat>
-use std::mem::transmute;
-use std::ffi::CStr;
+use std::cell::RefCell;
+use std::mem::{ transmute, replace, zeroed };
+use std::ptr::{ null, null_mut };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::vec;
 
 use bindings::emscripten::*;
 use math::vec::*;
+use core::display::*;
 
-#[derive(Copy, Clone, Debug)]
-pub enum KeyCode {
-	Space, Tab, Backspace,
-	Insert, Delete,
-	PageUp, PageDown,
-	Home, End,
-	Escape, Enter,
+pub use core::keyboard::*;
+pub use core::mouse::*;
+pub use core::touch::*;
+pub use core::gamepad::*;
 
-	Shift, Control,
+#[derive(Clone, Debug)]
+pub enum Event {
+	Resize(Vec2i),
+
+	Down(MouseEvent),
+	Up(MouseEvent),
+	Move(Vec2i),
+	DoubleClick(MouseEvent),
+	Wheel(WheelEvent),
+
+	// Pointer entered/left the canvas
+	MouseEnter(Vec2i),
+	MouseLeave(Vec2i),
+
+	// Relative movement while the pointer is locked, in place of Move
+	RawMotion(Vec2i),
+
+	TouchStart(Touch),
+	TouchMove(Touch),
+	TouchEnd(Touch),
+	TouchCancel(Touch),
+
+	GamepadConnected(GamepadState),
+	GamepadDisconnected(i32),
+
+	// Sampled once per frame for every connected pad by `poll_gamepads`
+	GamepadUpdate(GamepadState),
+
+	// Page visibility, e.g. switching tabs or minimising
+	Hidden,
+	Visible,
+
+	FocusGained,
+	FocusLost,
 
-	Left, Right, Up, Down,
+	// The page is being closed or reloaded. No further frames will run
+	BeforeUnload,
 
-	Digit(i32),
-	Symbol(char),
-	Alpha(char),
-	F(i32),
+	FullscreenChanged(bool),
+	PointerLockChanged(bool),
+
+	KeyDown(KeyEvent),
+	KeyUp(KeyEvent),
+
+	// Typed character, sent after the KeyDown that produced it
+	Text(char),
 }
 
-pub enum Event {
-	Resize(Vec2i),
+// Owns the event storage that the browser callbacks push into.
+// The queue is boxed so its address stays fixed for the callbacks even when
+// the EventLoop is moved, and the callbacks are unregistered on drop.
+pub struct EventLoop {
+	shared: Box<Shared>,
+	browser: bool,
+}
 
-	Down(Vec2i),
-	Up(Vec2i),
-	Move(Vec2i),
+// Everything the browser callbacks can reach
+struct Shared {
+	queue: RefCell<Vec<Event>>,
+	unload_handler: RefCell<Option<Box<dyn FnMut()>>>,
+}
+
+impl Shared {
+	fn new() -> Shared {
+		Shared {
+			queue: RefCell::new(Vec::new()),
+			unload_handler: RefCell::new(None),
+		}
+	}
+}
+
+// Callbacks are global per target, so only one browser EventLoop can be alive
+static BROWSER_LOOP_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+impl EventLoop {
+	// Receives browser input for the canvas, window and document
+	pub fn new() -> EventLoop {
+		if BROWSER_LOOP_ACTIVE.swap(true, Ordering::SeqCst) {
+			panic!("Only one browser EventLoop can exist at a time");
+		}
+
+		let event_loop = EventLoop {
+			shared: Box::new(Shared::new()),
+			browser: true,
+		};
+
+		unsafe {
+			register_callbacks(event_loop.user_data());
+		}
+
+		event_loop
+	}
+
+	// Only receives events pushed by hand, for tests and replays
+	pub fn headless() -> EventLoop {
+		EventLoop {
+			shared: Box::new(Shared::new()),
+			browser: false,
+		}
+	}
+
+	pub fn is_headless(&self) -> bool { !self.browser }
+
+	// Queues a synthetic event, delivered by the next `poll_events`
+	pub fn push(&self, e: Event) {
+		self.shared.queue.borrow_mut().push(e);
+	}
+
+	pub fn pending(&self) -> usize {
+		self.shared.queue.borrow().len()
+	}
+
+	// Takes every event received since the last call
+	pub fn poll_events(&mut self) -> vec::IntoIter<Event> {
+		if self.browser {
+			self.poll_gamepads();
+		}
+
+		replace(&mut *self.shared.queue.borrow_mut(), Vec::new()).into_iter()
+	}
 
-	KeyDown(KeyCode),
-	KeyUp(KeyCode),
+	// The main loop gets no frame after `BeforeUnload`, so this is called
+	// synchronously from the browser callback, right after the event is queued
+	pub fn set_unload_handler(&self, handler: Box<dyn FnMut()>) {
+		*self.shared.unload_handler.borrow_mut() = Some(handler);
+	}
+
+	// The gamepad API has no change events, so pads have to be sampled every frame
+	fn poll_gamepads(&self) {
+		unsafe {
+			for i in 0..emscripten_get_num_gamepads() {
+				let mut ge: EmscriptenGamepadEvent = zeroed();
+				if emscripten_get_gamepad_status(i, &mut ge) != EMSCRIPTEN_RESULT_SUCCESS {
+					continue
+				}
+
+				if ge.connected != 0 {
+					self.push(Event::GamepadUpdate(GamepadState::from_ems(&ge)));
+				}
+			}
+		}
+	}
+
+	fn user_data(&self) -> *mut CVoid {
+		&*self.shared as *const Shared as *mut CVoid
+	}
 }
 
-pub unsafe fn initialise_ems_event_queue(queue: &mut Vec<Event>) {
-	use std::ptr::null;
+impl Drop for EventLoop {
+	fn drop(&mut self) {
+		if self.browser {
+			unsafe {
+				unregister_callbacks();
+			}
+			BROWSER_LOOP_ACTIVE.store(false, Ordering::SeqCst);
+		}
+	}
+}
 
+const WINDOW_TARGET: &'static [u8] = b"#window\0";
+const CANVAS_TARGET: &'static [u8] = b"#canvas\0";
+const DOCUMENT_TARGET: &'static [u8] = b"#document\0";
+
+unsafe fn register_callbacks(ud: *mut CVoid) {
 	js! { b"Module.canvas = document.getElementById('canvas')\0" };
 
-	let evt_ptr = transmute(queue);
+	let window_target = WINDOW_TARGET.as_ptr() as _;
+	let canvas_target = CANVAS_TARGET.as_ptr() as _;
+	let document_target = DOCUMENT_TARGET.as_ptr() as _;
 
-	let window_target = b"#window\0".as_ptr() as _;
-	let canvas_target = b"#canvas\0".as_ptr() as _;
+	on_resize(0, null(), ud);
+	emscripten_set_resize_callback(window_target, ud, 1, Some(on_resize));
+
+	emscripten_set_mousemove_callback(canvas_target, ud, 1, Some(on_mouse_move));
+	emscripten_set_mousedown_callback(canvas_target, ud, 1, Some(on_mouse_down));
+	emscripten_set_mouseup_callback(canvas_target, ud, 1, Some(on_mouse_up));
+	emscripten_set_dblclick_callback(canvas_target, ud, 1, Some(on_mouse_dblclick));
+	emscripten_set_mouseenter_callback(canvas_target, ud, 1, Some(on_mouse_enter));
+	emscripten_set_mouseleave_callback(canvas_target, ud, 1, Some(on_mouse_leave));
+	emscripten_set_wheel_callback(canvas_target, ud, 1, Some(on_wheel));
+
+	emscripten_set_touchstart_callback(canvas_target, ud, 1, Some(on_touch_start));
+	emscripten_set_touchmove_callback(canvas_target, ud, 1, Some(on_touch_move));
+	emscripten_set_touchend_callback(canvas_target, ud, 1, Some(on_touch_end));
+	emscripten_set_touchcancel_callback(canvas_target, ud, 1, Some(on_touch_cancel));
+
+	emscripten_set_keydown_callback(window_target, ud, 1, Some(on_key_down));
+	emscripten_set_keyup_callback(window_target, ud, 1, Some(on_key_up));
+
+	emscripten_set_gamepadconnected_callback(ud, 1, Some(on_gamepad_connected));
+	emscripten_set_gamepaddisconnected_callback(ud, 1, Some(on_gamepad_disconnected));
+
+	emscripten_set_fullscreenchange_callback(document_target, ud, 1, Some(on_fullscreen_change));
+	emscripten_set_pointerlockchange_callback(document_target, ud, 1, Some(on_pointer_lock_change));
+
+	emscripten_set_visibilitychange_callback(ud, 1, Some(on_visibility_change));
+	emscripten_set_focus_callback(window_target, ud, 1, Some(on_focus));
+	emscripten_set_blur_callback(window_target, ud, 1, Some(on_blur));
+	emscripten_set_beforeunload_callback(ud, Some(on_before_unload));
+}
+
+unsafe fn unregister_callbacks() {
+	let window_target = WINDOW_TARGET.as_ptr() as _;
+	let canvas_target = CANVAS_TARGET.as_ptr() as _;
+	let document_target = DOCUMENT_TARGET.as_ptr() as _;
+	let ud = null_mut();
 
-	on_resize(0, null(), evt_ptr);
-	emscripten_set_resize_callback(window_target, evt_ptr, 1, Some(on_resize));
+	emscripten_set_resize_callback(window_target, ud, 1, None);
 
-	emscripten_set_mousemove_callback(canvas_target, evt_ptr, 1, Some(on_mouse_move));
-	emscripten_set_mousedown_callback(canvas_target, evt_ptr, 1, Some(on_mouse_down));
-	emscripten_set_mouseup_callback(canvas_target, evt_ptr, 1, Some(on_mouse_up));
+	emscripten_set_mousemove_callback(canvas_target, ud, 1, None);
+	emscripten_set_mousedown_callback(canvas_target, ud, 1, None);
+	emscripten_set_mouseup_callback(canvas_target, ud, 1, None);
+	emscripten_set_dblclick_callback(canvas_target, ud, 1, None);
+	emscripten_set_mouseenter_callback(canvas_target, ud, 1, None);
+	emscripten_set_mouseleave_callback(canvas_target, ud, 1, None);
+	emscripten_set_wheel_callback(canvas_target, ud, 1, None);
 
-	emscripten_set_touchstart_callback(canvas_target, evt_ptr, 1, Some(on_touch_start));
-	emscripten_set_touchmove_callback(canvas_target, evt_ptr, 1, Some(on_touch_move));
-	emscripten_set_touchend_callback(canvas_target, evt_ptr, 1, Some(on_touch_end));
-	emscripten_set_touchcancel_callback(canvas_target, evt_ptr, 1, Some(on_touch_end));
+	emscripten_set_touchstart_callback(canvas_target, ud, 1, None);
+	emscripten_set_touchmove_callback(canvas_target, ud, 1, None);
+	emscripten_set_touchend_callback(canvas_target, ud, 1, None);
+	emscripten_set_touchcancel_callback(canvas_target, ud, 1, None);
 
-	emscripten_set_keydown_callback(window_target, evt_ptr, 1, Some(on_key_down));
-	emscripten_set_keyup_callback(window_target, evt_ptr, 1, Some(on_key_up));
+	emscripten_set_keydown_callback(window_target, ud, 1, None);
+	emscripten_set_keyup_callback(window_target, ud, 1, None);
+
+	emscripten_set_gamepadconnected_callback(ud, 1, None);
+	emscripten_set_gamepaddisconnected_callback(ud, 1, None);
+
+	emscripten_set_fullscreenchange_callback(document_target, ud, 1, None);
+	emscripten_set_pointerlockchange_callback(document_target, ud, 1, None);
+
+	emscripten_set_visibilitychange_callback(ud, 1, None);
+	emscripten_set_focus_callback(window_target, ud, 1, None);
+	emscripten_set_blur_callback(window_target, ud, 1, None);
+	emscripten_set_beforeunload_callback(ud, None);
+}
+
+// Callbacks only ever run between frames, never while Rust code holds the queue,
+// but the RefCell still turns any overlap into a panic rather than aliasing
+unsafe fn push_event(ud: *mut CVoid, e: Event) {
+	let shared = &*(ud as *const Shared);
+	shared.queue.borrow_mut().push(e);
 }
 
 unsafe extern "C"
 fn on_resize(_: i32, _e: *const EmscriptenUiEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
 
 	let canvas_target = b"#canvas\0".as_ptr() as _;
 	let (mut width, mut height) = (0.0, 0.0);
@@ -70,7 +267,7 @@ fn on_resize(_: i32, _e: *const EmscriptenUiEvent, ud: *mut CVoid) -> i32 {
 	emscripten_get_element_css_size(canvas_target, &mut width, &mut height);
 	emscripten_set_canvas_size(width as i32, height as i32);
 
-	event_queue.push(Event::Resize(Vec2i::new(width as i32, height as i32)));
+	push_event(ud, Event::Resize(Vec2i::new(width as i32, height as i32)));
 	
 	1
 }
@@ -78,80 +275,106 @@ fn on_resize(_: i32, _e: *const EmscriptenUiEvent, ud: *mut CVoid) -> i32 {
 
 unsafe extern "C"
 fn on_mouse_move(_: i32, e: *const EmscriptenMouseEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
 	let e: &EmscriptenMouseEvent = transmute(e);
 
-	event_queue.push(Event::Move(Vec2i::new(e.clientX as _, e.clientY as _)));
+	if is_pointer_locked() {
+		push_event(ud, Event::RawMotion(Vec2i::new(e.movementX, e.movementY)));
+	} else {
+		push_event(ud, Event::Move(Vec2i::new(e.canvasX, e.canvasY)));
+	}
 	
 	1
 }
 unsafe extern "C"
 fn on_mouse_down(_: i32, e: *const EmscriptenMouseEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
 	let e: &EmscriptenMouseEvent = transmute(e);
 
-	event_queue.push(Event::Down(Vec2i::new(e.clientX as _, e.clientY as _)));
+	push_event(ud, Event::Down(MouseEvent::from_ems(e)));
 	
 	1
 }
 unsafe extern "C"
 fn on_mouse_up(_: i32, e: *const EmscriptenMouseEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
 	let e: &EmscriptenMouseEvent = transmute(e);
 
-	event_queue.push(Event::Up(Vec2i::new(e.clientX as _, e.clientY as _)));
+	push_event(ud, Event::Up(MouseEvent::from_ems(e)));
 	
 	1
 }
+unsafe extern "C"
+fn on_mouse_dblclick(_: i32, e: *const EmscriptenMouseEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenMouseEvent = transmute(e);
 
+	push_event(ud, Event::DoubleClick(MouseEvent::from_ems(e)));
+	
+	1
+}
+unsafe extern "C"
+fn on_mouse_enter(_: i32, e: *const EmscriptenMouseEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenMouseEvent = transmute(e);
 
+	push_event(ud, Event::MouseEnter(Vec2i::new(e.canvasX, e.canvasY)));
+	
+	1
+}
 unsafe extern "C"
-fn on_touch_move(_: i32, e: *const EmscriptenTouchEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
-	let e: &EmscriptenTouchEvent = transmute(e);
+fn on_mouse_leave(_: i32, e: *const EmscriptenMouseEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenMouseEvent = transmute(e);
 
-	if e.touches[0].identifier != 0 { return 0 }
+	push_event(ud, Event::MouseLeave(Vec2i::new(e.canvasX, e.canvasY)));
+	
+	1
+}
+unsafe extern "C"
+fn on_wheel(_: i32, e: *const EmscriptenWheelEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenWheelEvent = transmute(e);
 
-	let pos = Vec2i::new(e.touches[0].clientX as _, e.touches[0].clientY as _);
-	event_queue.push(Event::Move(pos));
+	push_event(ud, Event::Wheel(WheelEvent::from_ems(e)));
 	
 	1
 }
 
+
 unsafe extern "C"
 fn on_touch_start(_: i32, e: *const EmscriptenTouchEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
 	let e: &EmscriptenTouchEvent = transmute(e);
 
-	if e.touches[0].identifier != 0 { return 0 }
-
-	let pos = Vec2i::new(e.touches[0].clientX as _, e.touches[0].clientY as _);
-	event_queue.push(Event::Down(pos));
+	for t in Touch::changed(e) {
+		push_event(ud, Event::TouchStart(t));
+	}
 	
 	1
 }
 
 unsafe extern "C"
-fn on_touch_end(_: i32, e: *const EmscriptenTouchEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
+fn on_touch_move(_: i32, e: *const EmscriptenTouchEvent, ud: *mut CVoid) -> i32 {
 	let e: &EmscriptenTouchEvent = transmute(e);
 
-	if e.numTouches > 1 {
-		use std::mem::uninitialized;
+	for t in Touch::changed(e) {
+		push_event(ud, Event::TouchMove(t));
+	}
+	
+	1
+}
 
-		// TODO: Make this requestable
-		let mut fse: EmscriptenFullscreenChangeEvent = uninitialized();
-		emscripten_get_fullscreen_status(&mut fse);
+unsafe extern "C"
+fn on_touch_end(_: i32, e: *const EmscriptenTouchEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenTouchEvent = transmute(e);
 
-		if fse.isFullscreen == 0 {
-			js!{ b"Module.requestFullscreen(1,1,0)" };
-		}
+	for t in Touch::changed(e) {
+		push_event(ud, Event::TouchEnd(t));
 	}
+	
+	1
+}
 
-	if e.touches[0].identifier != 0 { return 0 }
+unsafe extern "C"
+fn on_touch_cancel(_: i32, e: *const EmscriptenTouchEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenTouchEvent = transmute(e);
 
-	let pos = Vec2i::new(e.touches[0].clientX as _, e.touches[0].clientY as _);
-	event_queue.push(Event::Up(pos));
+	for t in Touch::changed(e) {
+		push_event(ud, Event::TouchCancel(t));
+	}
 	
 	1
 }
@@ -159,109 +382,208 @@ fn on_touch_end(_: i32, e: *const EmscriptenTouchEvent, ud: *mut CVoid) -> i32 {
 
 unsafe extern "C"
 fn on_key_down(_: i32, e: *const EmscriptenKeyboardEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
 	let e: &EmscriptenKeyboardEvent = transmute(e);
 
-	if e.repeat != 0 { return 1 }
+	let key_event = KeyEvent::from_ems(e);
 
-	let keycode = KeyCode::from_c_str(e.code.as_ptr(), e.key.as_ptr());
+	// Reload and devtools keys are still reported, so their KeyUp has a matching KeyDown,
+	// but the browser keeps its default behaviour for them
+	let browser_default = match key_event.code {
+		KeyCode::F5 | KeyCode::F6 | KeyCode::F12 => true,
+		_ => false
+	};
 
-	match keycode {
-		Some(KeyCode::F(5))
-		| Some(KeyCode::F(6))
-		| Some(KeyCode::F(12)) => {
-			return 0
-		}
+	let text = key_event.text();
 
-		_ => {}
+	push_event(ud, Event::KeyDown(key_event));
+	if let Some(c) = text {
+		push_event(ud, Event::Text(c));
 	}
 
-	// println!("{:?} - {:?}, {:?}", keycode, CStr::from_ptr(e.key.as_ptr()),
-	// 	CStr::from_ptr(e.code.as_ptr()));
-
-	if let Some(keycode) = keycode {
-		event_queue.push(Event::KeyDown(keycode));
-		1
-	} else {
-		0
-	}
+	if browser_default { 0 } else { 1 }
 }
 
 unsafe extern "C"
 fn on_key_up(_: i32, e: *const EmscriptenKeyboardEvent, ud: *mut CVoid) -> i32 {
-	let event_queue: &mut Vec<Event> = transmute(ud);
 	let e: &EmscriptenKeyboardEvent = transmute(e);
 
-	let keycode = KeyCode::from_c_str(e.code.as_ptr(), e.key.as_ptr());
+	push_event(ud, Event::KeyUp(KeyEvent::from_ems(e)));
 
-	if let Some(keycode) = keycode {
-		event_queue.push(Event::KeyUp(keycode));
-		1
-	} else {
-		0
-	}
+	1
 }
 
 
-impl KeyCode {
-	pub fn from_c_str(code_str: *const CChar, key_str: *const CChar) -> Option<KeyCode> {
-		use std::ascii::AsciiExt;
+unsafe extern "C"
+fn on_gamepad_connected(_: i32, e: *const EmscriptenGamepadEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenGamepadEvent = transmute(e);
 
-		let code = unsafe{ CStr::from_ptr(code_str) };
-		let key = unsafe{ CStr::from_ptr(key_str) };
+	push_event(ud, Event::GamepadConnected(GamepadState::from_ems(e)));
 
-		let code = code.to_str().unwrap();
+	1
+}
 
-		match code {
-			"Space" => return Some(KeyCode::Space),
+unsafe extern "C"
+fn on_gamepad_disconnected(_: i32, e: *const EmscriptenGamepadEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenGamepadEvent = transmute(e);
 
-			"Insert" => return Some(KeyCode::Insert),
-			"Delete" => return Some(KeyCode::Delete),
-			"PageUp" => return Some(KeyCode::PageUp),
-			"PageDown" => return Some(KeyCode::PageDown),
+	push_event(ud, Event::GamepadDisconnected(e.index));
 
-			"Home" => return Some(KeyCode::Home),
-			"End" => return Some(KeyCode::End),
-			"Escape" => return Some(KeyCode::Escape),
-			"Enter" => return Some(KeyCode::Enter),
+	1
+}
 
-			"Tab" => return Some(KeyCode::Tab),
-			"Backspace" => return Some(KeyCode::Backspace),
 
-			"ArrowLeft" => return Some(KeyCode::Left),
-			"ArrowRight" => return Some(KeyCode::Right),
-			"ArrowUp" => return Some(KeyCode::Up),
-			"ArrowDown" => return Some(KeyCode::Down),
+unsafe extern "C"
+fn on_fullscreen_change(_: i32, e: *const EmscriptenFullscreenChangeEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenFullscreenChangeEvent = transmute(e);
 
-			x if x.len() == 4 && x.starts_with("Key") => {
-				return Some(KeyCode::Alpha(x.chars().nth(3).unwrap()))
-			}
+	// The fullscreen strategy resizes the canvas itself, so report its new size
+	let (mut width, mut height, mut is_fullscreen) = (0, 0, 0);
+	emscripten_get_canvas_size(&mut width, &mut height, &mut is_fullscreen);
 
-			x if x.starts_with("Digit") => {
-				return code[5..].parse().ok().map(|n| KeyCode::Digit(n))
-			}
+	push_event(ud, Event::FullscreenChanged(e.isFullscreen != 0));
+	push_event(ud, Event::Resize(Vec2i::new(width, height)));
 
-			_ => {}
+	1
+}
+
+unsafe extern "C"
+fn on_pointer_lock_change(_: i32, e: *const EmscriptenPointerlockChangeEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenPointerlockChangeEvent = transmute(e);
+
+	push_event(ud, Event::PointerLockChanged(e.isActive != 0));
+
+	1
+}
+
+
+unsafe extern "C"
+fn on_visibility_change(_: i32, e: *const EmscriptenVisibilityChangeEvent, ud: *mut CVoid) -> i32 {
+	let e: &EmscriptenVisibilityChangeEvent = transmute(e);
+
+	push_event(ud, if e.hidden != 0 { Event::Hidden } else { Event::Visible });
+
+	1
+}
+
+unsafe extern "C"
+fn on_focus(_: i32, _e: *const EmscriptenFocusEvent, ud: *mut CVoid) -> i32 {
+	push_event(ud, Event::FocusGained);
+
+	1
+}
+
+unsafe extern "C"
+fn on_blur(_: i32, _e: *const EmscriptenFocusEvent, ud: *mut CVoid) -> i32 {
+	push_event(ud, Event::FocusLost);
+
+	1
+}
+
+unsafe extern "C"
+fn on_before_unload(_: i32, _: *const CVoid, ud: *mut CVoid) -> *const CChar {
+	push_event(ud, Event::BeforeUnload);
+
+	// Taken out while it runs so it can call back into the loop, e.g. to replace itself
+	let shared = &*(ud as *const Shared);
+	let handler = shared.unload_handler.borrow_mut().take();
+	if let Some(mut handler) = handler {
+		handler();
+
+		let mut slot = shared.unload_handler.borrow_mut();
+		if slot.is_none() {
+			*slot = Some(handler);
 		}
+	}
+
+	// No "leave this page?" prompt
+	null()
+}
 
-		let key = key.to_str().unwrap();
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::rc::Rc;
+	use std::cell::Cell;
+
+	fn moves(events: Vec<Event>) -> Vec<i32> {
+		events.into_iter().map(|e| match e {
+			Event::Move(p) => p.x,
+			e => panic!("unexpected {:?}", e),
+		}).collect()
+	}
 
-		match key {
-			"Shift" => return Some(KeyCode::Shift),
-			"Control" => return Some(KeyCode::Control),
+	#[test]
+	fn injected_events_keep_their_order() {
+		let mut events = EventLoop::headless();
+		assert!(events.is_headless());
 
-			"F1" | "F2" | "F3" | "F4" | "F5" | "F6" | "F7" | "F8"
-			| "F9" | "F10" | "F11" | "F12" => {
-				return Some(KeyCode::F( key[1..].parse().unwrap() ))
-			}
+		events.push(Event::Move(Vec2i::new(1, 0)));
+		events.push(Event::Move(Vec2i::new(2, 0)));
+		events.push(Event::Move(Vec2i::new(3, 0)));
+		assert_eq!(events.pending(), 3);
+
+		assert_eq!(moves(events.poll_events().collect()), vec![1, 2, 3]);
+		assert_eq!(events.pending(), 0);
+		assert_eq!(events.poll_events().count(), 0);
+	}
+
+	#[test]
+	fn callbacks_and_injected_events_share_one_queue() {
+		let mut events = EventLoop::headless();
+		events.push(Event::Move(Vec2i::new(1, 0)));
+		unsafe {
+			push_event(events.user_data(), Event::Move(Vec2i::new(2, 0)));
+		}
+		events.push(Event::Move(Vec2i::new(3, 0)));
+
+		assert_eq!(moves(events.poll_events().collect()), vec![1, 2, 3]);
+	}
+
+	#[test]
+	fn events_pushed_while_handling_wait_for_the_next_poll() {
+		let mut events = EventLoop::headless();
+		events.push(Event::Move(Vec2i::new(1, 0)));
+
+		let first: Vec<Event> = events.poll_events().collect();
+		events.push(Event::Move(Vec2i::new(2, 0)));
+		assert_eq!(moves(first), vec![1]);
+		assert_eq!(moves(events.poll_events().collect()), vec![2]);
+	}
+
+	#[test]
+	fn the_queue_moves_with_the_loop() {
+		let events = EventLoop::headless();
+		let ud = events.user_data();
+
+		let mut moved = vec![events];
+		unsafe {
+			push_event(ud, Event::Move(Vec2i::new(7, 0)));
+		}
+		assert_eq!(moves(moved[0].poll_events().collect()), vec![7]);
+	}
 
-			x if x.is_ascii_punctuation() => {
-				return Some(KeyCode::Symbol(x.chars().next().unwrap()))
+	#[test]
+	fn unload_handler_can_replace_itself() {
+		let events = EventLoop::headless();
+		let first = Rc::new(Cell::new(0));
+		let second = Rc::new(Cell::new(0));
+
+		let ptr = &events as *const EventLoop;
+		let (f, s) = (first.clone(), second.clone());
+		events.set_unload_handler(Box::new(move || {
+			f.set(f.get() + 1);
+			let s = s.clone();
+			unsafe {
+				(*ptr).set_unload_handler(Box::new(move || s.set(s.get() + 1)));
 			}
+		}));
 
-			_ => {}
+		unsafe {
+			on_before_unload(0, null(), events.user_data());
+			on_before_unload(0, null(), events.user_data());
 		}
 
-		None
+		assert_eq!((first.get(), second.get()), (1, 1));
+		assert_eq!(events.pending(), 2);
 	}
 }
\ No newline at end of file
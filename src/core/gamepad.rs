@@ -0,0 +1,333 @@
+use std::ffi::CStr;
+
+use bindings::emscripten::*;
+use math::vec::*;
+use core::action::apply_dead_zone;
+
+// Button indices of the W3C "standard" gamepad mapping
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+	South, East, West, North,
+	LeftBumper, RightBumper,
+	LeftTrigger, RightTrigger,
+	Select, Start,
+	LeftStick, RightStick,
+	DPadUp, DPadDown, DPadLeft, DPadRight,
+	Home,
+	Other(u32),
+}
+
+const STANDARD_BUTTONS: [GamepadButton; 17] = [
+	GamepadButton::South, GamepadButton::East, GamepadButton::West, GamepadButton::North,
+	GamepadButton::LeftBumper, GamepadButton::RightBumper,
+	GamepadButton::LeftTrigger, GamepadButton::RightTrigger,
+	GamepadButton::Select, GamepadButton::Start,
+	GamepadButton::LeftStick, GamepadButton::RightStick,
+	GamepadButton::DPadUp, GamepadButton::DPadDown, GamepadButton::DPadLeft, GamepadButton::DPadRight,
+	GamepadButton::Home,
+];
+
+impl GamepadButton {
+	pub fn from_index(i: u32) -> GamepadButton {
+		STANDARD_BUTTONS.get(i as usize).cloned().unwrap_or(GamepadButton::Other(i))
+	}
+
+	pub fn index(&self) -> u32 {
+		match *self {
+			GamepadButton::Other(i) => i,
+			b => STANDARD_BUTTONS.iter().position(|&s| s == b).unwrap() as u32
+		}
+	}
+
+	pub fn name(&self) -> String {
+		match *self {
+			GamepadButton::Other(i) => format!("Other{}", i),
+			b => format!("{:?}", b),
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<GamepadButton> {
+		if name.starts_with("Other") {
+			return name[5..].parse().ok().map(GamepadButton::Other)
+		}
+		STANDARD_BUTTONS.iter().find(|b| b.name() == name).cloned()
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+	LeftX, LeftY,
+	RightX, RightY,
+	Other(u32),
+}
+
+impl GamepadAxis {
+	pub fn from_index(i: u32) -> GamepadAxis {
+		match i {
+			0 => GamepadAxis::LeftX,
+			1 => GamepadAxis::LeftY,
+			2 => GamepadAxis::RightX,
+			3 => GamepadAxis::RightY,
+			x => GamepadAxis::Other(x)
+		}
+	}
+
+	pub fn index(&self) -> u32 {
+		match *self {
+			GamepadAxis::LeftX => 0,
+			GamepadAxis::LeftY => 1,
+			GamepadAxis::RightX => 2,
+			GamepadAxis::RightY => 3,
+			GamepadAxis::Other(x) => x,
+		}
+	}
+
+	pub fn name(&self) -> String {
+		match *self {
+			GamepadAxis::Other(i) => format!("Other{}", i),
+			a => format!("{:?}", a),
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<GamepadAxis> {
+		match name {
+			"LeftX" => Some(GamepadAxis::LeftX),
+			"LeftY" => Some(GamepadAxis::LeftY),
+			"RightX" => Some(GamepadAxis::RightX),
+			"RightY" => Some(GamepadAxis::RightY),
+			x if x.starts_with("Other") => x[5..].parse().ok().map(GamepadAxis::Other),
+			_ => None
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadStick { Left, Right }
+
+// Snapshot of a pad, as sampled once per frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct GamepadState {
+	pub index: i32,
+	pub id: String,
+	pub standard_mapping: bool,
+	pub timestamp: f64,
+
+	pub buttons: Vec<bool>,
+	pub analog_buttons: Vec<f32>,
+	pub axes: Vec<f32>,
+}
+
+impl GamepadState {
+	// Empty standard-mapping pad, for tests and replays
+	pub fn new(index: i32) -> GamepadState {
+		GamepadState {
+			index,
+			id: String::new(),
+			standard_mapping: true,
+			timestamp: 0.0,
+
+			buttons: vec![false; STANDARD_BUTTONS.len()],
+			analog_buttons: vec![0.0; STANDARD_BUTTONS.len()],
+			axes: vec![0.0; 4],
+		}
+	}
+
+	pub fn from_ems(e: &EmscriptenGamepadEvent) -> GamepadState {
+		let num_buttons = (e.numButtons.max(0) as usize).min(e.digitalButton.len());
+		let num_axes = (e.numAxes.max(0) as usize).min(e.axis.len());
+
+		let id = unsafe{ CStr::from_ptr(e.id.as_ptr()) };
+		let mapping = unsafe{ CStr::from_ptr(e.mapping.as_ptr()) };
+
+		GamepadState {
+			index: e.index,
+			id: id.to_string_lossy().into_owned(),
+			standard_mapping: mapping.to_bytes() == b"standard",
+			timestamp: e.timestamp,
+
+			buttons: e.digitalButton[..num_buttons].iter().map(|&b| b != 0).collect(),
+			analog_buttons: e.analogButton[..num_buttons].iter().map(|&b| b as f32).collect(),
+			axes: e.axis[..num_axes].iter().map(|&a| a as f32).collect(),
+		}
+	}
+
+	pub fn set_button(&mut self, b: GamepadButton, value: f32) {
+		let i = b.index() as usize;
+		if i >= self.buttons.len() {
+			self.buttons.resize(i + 1, false);
+			self.analog_buttons.resize(i + 1, 0.0);
+		}
+		self.buttons[i] = value > 0.5;
+		self.analog_buttons[i] = value;
+	}
+
+	pub fn set_axis(&mut self, a: GamepadAxis, value: f32) {
+		let i = a.index() as usize;
+		if i >= self.axes.len() {
+			self.axes.resize(i + 1, 0.0);
+		}
+		self.axes[i] = value;
+	}
+
+	pub fn button(&self, b: GamepadButton) -> bool {
+		self.buttons.get(b.index() as usize).cloned().unwrap_or(false)
+	}
+
+	pub fn analog_button(&self, b: GamepadButton) -> f32 {
+		self.analog_buttons.get(b.index() as usize).cloned().unwrap_or(0.0)
+	}
+
+	pub fn axis(&self, a: GamepadAxis) -> f32 {
+		self.axes.get(a.index() as usize).cloned().unwrap_or(0.0)
+	}
+}
+
+// A connected pad as seen by `Input`, with the previous frame kept for edge queries
+#[derive(Clone, Debug)]
+pub struct Gamepad {
+	pub state: GamepadState,
+	prev: GamepadState,
+	pub dead_zone: f32,
+}
+
+pub const DEFAULT_GAMEPAD_DEAD_ZONE: f32 = 0.15;
+
+impl Gamepad {
+	pub fn new(state: GamepadState) -> Gamepad {
+		Gamepad {
+			prev: state.clone(),
+			state,
+			dead_zone: DEFAULT_GAMEPAD_DEAD_ZONE,
+		}
+	}
+
+	pub fn index(&self) -> i32 { self.state.index }
+	pub fn id(&self) -> &str { &self.state.id }
+
+	pub fn begin_frame(&mut self) {
+		self.prev = self.state.clone();
+	}
+
+	pub fn held(&self, b: GamepadButton) -> bool { self.state.button(b) }
+	pub fn pressed(&self, b: GamepadButton) -> bool { self.state.button(b) && !self.prev.button(b) }
+	pub fn released(&self, b: GamepadButton) -> bool { !self.state.button(b) && self.prev.button(b) }
+
+	pub fn analog_button(&self, b: GamepadButton) -> f32 { self.state.analog_button(b) }
+
+	pub fn axis_raw(&self, a: GamepadAxis) -> f32 { self.state.axis(a) }
+
+	// Axis with the dead zone applied along that axis alone
+	pub fn axis(&self, a: GamepadAxis) -> f32 {
+		apply_dead_zone(self.state.axis(a), self.dead_zone).max(-1.0).min(1.0)
+	}
+
+	// Both axes of a stick with a radial dead zone, which avoids snapping to the cardinal directions
+	pub fn stick(&self, s: GamepadStick) -> Vec2 {
+		let (x, y) = match s {
+			GamepadStick::Left => (GamepadAxis::LeftX, GamepadAxis::LeftY),
+			GamepadStick::Right => (GamepadAxis::RightX, GamepadAxis::RightY),
+		};
+
+		let v = Vec2::new(self.state.axis(x), self.state.axis(y));
+		let m = v.len();
+		let scaled = apply_dead_zone(m, self.dead_zone).min(1.0);
+		if scaled > 0.0 { v * (scaled / m) } else { Vec2::zero() }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::event::*;
+	use core::input::*;
+
+	fn approx(a: f32, b: f32) -> bool { (a - b).abs() < 1e-5 }
+
+	#[test]
+	fn synthetic_updates_give_button_edges() {
+		let mut input = Input::new();
+		input.update(&[Event::GamepadConnected(GamepadState::new(0))]);
+		assert_eq!(input.gamepads().len(), 1);
+
+		let mut state = GamepadState::new(0);
+		state.set_button(GamepadButton::South, 1.0);
+		input.update(&[Event::GamepadUpdate(state.clone())]);
+		{
+			let pad = input.gamepad(0).unwrap();
+			assert!(pad.pressed(GamepadButton::South));
+			assert!(pad.held(GamepadButton::South));
+			assert!(!pad.pressed(GamepadButton::East));
+		}
+
+		input.update(&[Event::GamepadUpdate(state.clone())]);
+		assert!(!input.gamepad(0).unwrap().pressed(GamepadButton::South));
+		assert!(input.gamepad(0).unwrap().held(GamepadButton::South));
+
+		state.set_button(GamepadButton::South, 0.2);
+		input.update(&[Event::GamepadUpdate(state)]);
+		{
+			let pad = input.gamepad(0).unwrap();
+			assert!(pad.released(GamepadButton::South));
+			assert!(!pad.held(GamepadButton::South));
+			assert!(approx(pad.analog_button(GamepadButton::South), 0.2));
+		}
+
+		input.update(&[Event::GamepadDisconnected(0)]);
+		assert!(input.gamepad(0).is_none());
+	}
+
+	#[test]
+	fn update_without_connect_adds_the_pad() {
+		let mut input = Input::new();
+		input.update(&[Event::GamepadUpdate(GamepadState::new(2))]);
+		assert_eq!(input.gamepad(2).unwrap().index(), 2);
+	}
+
+	#[test]
+	fn axis_dead_zone() {
+		let mut state = GamepadState::new(0);
+		state.set_axis(GamepadAxis::LeftX, 0.1);
+		state.set_axis(GamepadAxis::LeftY, -0.575);
+		state.set_axis(GamepadAxis::RightX, 1.0);
+
+		let mut pad = Gamepad::new(state);
+		pad.dead_zone = 0.15;
+		assert_eq!(pad.axis(GamepadAxis::LeftX), 0.0);
+		assert!(approx(pad.axis_raw(GamepadAxis::LeftX), 0.1));
+		assert!(approx(pad.axis(GamepadAxis::LeftY), -0.5));
+		assert!(approx(pad.axis(GamepadAxis::RightX), 1.0));
+	}
+
+	#[test]
+	fn stick_dead_zone_is_radial() {
+		let mut state = GamepadState::new(0);
+		state.set_axis(GamepadAxis::LeftX, 0.1);
+		state.set_axis(GamepadAxis::LeftY, 0.1);
+		state.set_axis(GamepadAxis::RightX, 0.3);
+		state.set_axis(GamepadAxis::RightY, 0.4);
+
+		let mut pad = Gamepad::new(state);
+		pad.dead_zone = 0.2;
+
+		// Inside the circle even though neither axis alone is past the dead zone
+		assert_eq!(pad.stick(GamepadStick::Left), Vec2::zero());
+
+		// Direction is kept and the magnitude rescaled: (0.5 - 0.2) / 0.8
+		let v = pad.stick(GamepadStick::Right);
+		assert!(approx(v.len(), 0.375));
+		assert!(approx(v.x / v.y, 0.75));
+	}
+
+	#[test]
+	fn input_dead_zone_applies_to_every_pad() {
+		let mut state = GamepadState::new(1);
+		state.set_axis(GamepadAxis::LeftX, 0.3);
+
+		let mut input = Input::new();
+		input.update(&[Event::GamepadUpdate(state)]);
+		assert!(input.gamepad(1).unwrap().axis(GamepadAxis::LeftX) > 0.0);
+
+		input.set_gamepad_dead_zone(0.5);
+		assert_eq!(input.gamepad(1).unwrap().axis(GamepadAxis::LeftX), 0.0);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,348 @@
+use std::f32::consts::PI;
+use std::vec;
+
+use math::vec::*;
+use core::event::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Gesture {
+	Tap(Vec2),
+	DoubleTap(Vec2),
+	LongPress(Vec2),
+	Swipe { start: Vec2, end: Vec2, velocity: Vec2 },
+
+	// Relative to the previous Pinch/Rotate of the same two fingers
+	Pinch { center: Vec2, scale: f32 },
+	Rotate { center: Vec2, angle: f32 },
+}
+
+// Thresholds in seconds and pixels
+#[derive(Copy, Clone, Debug)]
+pub struct GestureConfig {
+	pub tap_max_time: f64,
+	pub tap_max_distance: f32,
+	pub double_tap_interval: f64,
+	pub long_press_time: f64,
+	pub swipe_min_distance: f32,
+	pub swipe_max_time: f64,
+}
+
+impl Default for GestureConfig {
+	fn default() -> GestureConfig {
+		GestureConfig {
+			tap_max_time: 0.25,
+			tap_max_distance: 10.0,
+			double_tap_interval: 0.3,
+			long_press_time: 0.5,
+			swipe_min_distance: 50.0,
+			swipe_max_time: 0.5,
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Tracked {
+	id: i32,
+	pos: Vec2,
+}
+
+// Turns TouchStart/Move/End/Cancel events into gestures.
+// Touch events carry no timestamps, so the current time in seconds is passed in.
+// `update` must be called every frame for long presses to fire.
+pub struct GestureRecognizer {
+	pub config: GestureConfig,
+
+	touches: Vec<Tracked>,
+	gestures: Vec<Gesture>,
+
+	start_time: f64,
+	start_pos: Vec2,
+	moved: bool,
+	long_pressed: bool,
+	multi_touch: bool,
+
+	last_tap: Option<(f64, Vec2)>,
+
+	pair_span: f32,
+	pair_angle: f32,
+}
+
+impl GestureRecognizer {
+	pub fn new() -> GestureRecognizer {
+		GestureRecognizer::with_config(GestureConfig::default())
+	}
+
+	pub fn with_config(config: GestureConfig) -> GestureRecognizer {
+		GestureRecognizer {
+			config,
+
+			touches: Vec::new(),
+			gestures: Vec::new(),
+
+			start_time: 0.0,
+			start_pos: Vec2::zero(),
+			moved: false,
+			long_pressed: false,
+			multi_touch: false,
+
+			last_tap: None,
+
+			pair_span: 0.0,
+			pair_angle: 0.0,
+		}
+	}
+
+	pub fn handle_event(&mut self, e: &Event, now: f64) {
+		match *e {
+			Event::TouchStart(t) => self.touch_start(t, now),
+			Event::TouchMove(t) => self.touch_move(t),
+			Event::TouchEnd(t) => self.touch_end(t, now, false),
+			Event::TouchCancel(t) => self.touch_end(t, now, true),
+			_ => {}
+		}
+	}
+
+	pub fn update(&mut self, now: f64) {
+		if self.touches.len() != 1 || self.moved || self.long_pressed || self.multi_touch {
+			return
+		}
+
+		if now - self.start_time >= self.config.long_press_time {
+			self.long_pressed = true;
+			let pos = self.touches[0].pos;
+			self.gestures.push(Gesture::LongPress(pos));
+		}
+	}
+
+	pub fn gestures(&self) -> &[Gesture] { &self.gestures }
+
+	pub fn drain(&mut self) -> vec::Drain<'_, Gesture> {
+		self.gestures.drain(..)
+	}
+
+	fn touch_start(&mut self, t: Touch, now: f64) {
+		let pos = t.pos.as_vec2();
+		self.touches.retain(|o| o.id != t.id);
+		self.touches.push(Tracked { id: t.id, pos });
+
+		if self.touches.len() == 1 {
+			self.start_time = now;
+			self.start_pos = pos;
+			self.moved = false;
+			self.long_pressed = false;
+			self.multi_touch = false;
+		} else {
+			self.multi_touch = true;
+			self.reset_pair();
+		}
+	}
+
+	fn touch_move(&mut self, t: Touch) {
+		let pos = t.pos.as_vec2();
+		match self.touches.iter_mut().find(|o| o.id == t.id) {
+			Some(o) => o.pos = pos,
+			None => return
+		}
+
+		if self.touches.len() == 1 {
+			if (pos - self.start_pos).len() > self.config.tap_max_distance {
+				self.moved = true;
+			}
+			return
+		}
+
+		let (center, span, angle) = self.pair();
+
+		if self.pair_span > 0.0 && span > 0.0 && span != self.pair_span {
+			self.gestures.push(Gesture::Pinch { center, scale: span / self.pair_span });
+		}
+
+		let mut delta = angle - self.pair_angle;
+		if delta > PI { delta -= 2.0 * PI }
+		if delta < -PI { delta += 2.0 * PI }
+
+		if delta != 0.0 {
+			self.gestures.push(Gesture::Rotate { center, angle: delta });
+		}
+
+		self.pair_span = span;
+		self.pair_angle = angle;
+	}
+
+	fn touch_end(&mut self, t: Touch, now: f64, cancelled: bool) {
+		let pos = t.pos.as_vec2();
+		let found = self.touches.iter().any(|o| o.id == t.id);
+		self.touches.retain(|o| o.id != t.id);
+
+		if !found { return }
+
+		if self.touches.len() >= 2 {
+			self.reset_pair();
+		}
+
+		if !self.touches.is_empty() || cancelled || self.multi_touch || self.long_pressed {
+			return
+		}
+
+		let dt = now - self.start_time;
+		let offset = pos - self.start_pos;
+
+		if !self.moved && dt <= self.config.tap_max_time {
+			self.gestures.push(Gesture::Tap(pos));
+
+			match self.last_tap {
+				Some((time, last)) if now - time <= self.config.double_tap_interval
+					&& (pos - last).len() <= self.config.tap_max_distance * 2.0 => {
+					self.gestures.push(Gesture::DoubleTap(pos));
+					self.last_tap = None;
+				}
+
+				_ => {
+					self.last_tap = Some((now, pos));
+				}
+			}
+
+		} else if offset.len() >= self.config.swipe_min_distance && dt <= self.config.swipe_max_time {
+			let velocity = if dt > 0.0 { offset / dt as f32 } else { Vec2::zero() };
+			self.gestures.push(Gesture::Swipe { start: self.start_pos, end: pos, velocity });
+		}
+	}
+
+	fn pair(&self) -> (Vec2, f32, f32) {
+		let a = self.touches[0].pos;
+		let b = self.touches[1].pos;
+		let d = b - a;
+		((a + b) * 0.5, d.len(), d.angle())
+	}
+
+	fn reset_pair(&mut self) {
+		let (_, span, angle) = self.pair();
+		self.pair_span = span;
+		self.pair_angle = angle;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn touch(id: i32, x: i32, y: i32) -> Touch {
+		Touch { id, pos: Vec2i::new(x, y) }
+	}
+
+	fn tap_at(g: &mut GestureRecognizer, x: i32, y: i32, now: f64) {
+		g.handle_event(&Event::TouchStart(touch(0, x, y)), now);
+		g.handle_event(&Event::TouchEnd(touch(0, x, y)), now + 0.1);
+	}
+
+	#[test]
+	fn tap() {
+		let mut g = GestureRecognizer::new();
+		tap_at(&mut g, 10, 20, 0.0);
+		assert_eq!(g.drain().collect::<Vec<_>>(), vec![Gesture::Tap(Vec2::new(10.0, 20.0))]);
+	}
+
+	#[test]
+	fn double_tap() {
+		let mut g = GestureRecognizer::new();
+		tap_at(&mut g, 10, 20, 0.0);
+		tap_at(&mut g, 12, 21, 0.2);
+		assert_eq!(g.gestures(), &[
+			Gesture::Tap(Vec2::new(10.0, 20.0)),
+			Gesture::Tap(Vec2::new(12.0, 21.0)),
+			Gesture::DoubleTap(Vec2::new(12.0, 21.0)),
+		]);
+
+		// Too late for a second double tap with the first
+		g.drain();
+		tap_at(&mut g, 12, 21, 1.0);
+		tap_at(&mut g, 12, 21, 2.0);
+		assert!(!g.gestures().iter().any(|g| match *g { Gesture::DoubleTap(_) => true, _ => false }));
+	}
+
+	#[test]
+	fn long_press() {
+		let mut g = GestureRecognizer::new();
+		g.handle_event(&Event::TouchStart(touch(0, 5, 5)), 0.0);
+		g.update(0.4);
+		assert!(g.gestures().is_empty());
+
+		g.update(0.6);
+		g.update(0.7);
+		assert_eq!(g.gestures(), &[Gesture::LongPress(Vec2::new(5.0, 5.0))]);
+
+		// Lifting afterwards isn't also a tap
+		g.handle_event(&Event::TouchEnd(touch(0, 5, 5)), 0.8);
+		assert_eq!(g.gestures().len(), 1);
+	}
+
+	#[test]
+	fn swipe() {
+		let mut g = GestureRecognizer::new();
+		g.handle_event(&Event::TouchStart(touch(0, 0, 0)), 1.0);
+		g.handle_event(&Event::TouchMove(touch(0, 50, 0)), 1.1);
+		g.handle_event(&Event::TouchEnd(touch(0, 100, 0)), 1.2);
+
+		assert_eq!(g.gestures().len(), 1);
+		match g.gestures()[0] {
+			Gesture::Swipe { start, end, velocity } => {
+				assert_eq!(start, Vec2::zero());
+				assert_eq!(end, Vec2::new(100.0, 0.0));
+				assert!((velocity.x - 500.0).abs() < 1e-2 && velocity.y == 0.0);
+			}
+			g => panic!("{:?}", g)
+		}
+	}
+
+	#[test]
+	fn slow_swipes_and_moved_taps_are_rejected() {
+		let mut g = GestureRecognizer::new();
+		g.handle_event(&Event::TouchStart(touch(0, 0, 0)), 0.0);
+		g.handle_event(&Event::TouchMove(touch(0, 100, 0)), 0.5);
+		g.handle_event(&Event::TouchEnd(touch(0, 100, 0)), 1.0);
+
+		// Moved past the tap distance but not far enough to swipe
+		g.handle_event(&Event::TouchStart(touch(0, 0, 0)), 2.0);
+		g.handle_event(&Event::TouchMove(touch(0, 20, 0)), 2.05);
+		g.handle_event(&Event::TouchEnd(touch(0, 20, 0)), 2.1);
+
+		// Cancelled
+		g.handle_event(&Event::TouchStart(touch(0, 0, 0)), 3.0);
+		g.handle_event(&Event::TouchCancel(touch(0, 0, 0)), 3.1);
+
+		assert!(g.gestures().is_empty(), "{:?}", g.gestures());
+	}
+
+	#[test]
+	fn pinch() {
+		let mut g = GestureRecognizer::new();
+		g.handle_event(&Event::TouchStart(touch(0, 0, 0)), 0.0);
+		g.handle_event(&Event::TouchStart(touch(1, 100, 0)), 0.0);
+		g.handle_event(&Event::TouchMove(touch(1, 200, 0)), 0.1);
+
+		assert_eq!(g.gestures(), &[Gesture::Pinch { center: Vec2::new(100.0, 0.0), scale: 2.0 }]);
+
+		// Lifting the fingers isn't a tap
+		g.handle_event(&Event::TouchEnd(touch(0, 0, 0)), 0.2);
+		g.handle_event(&Event::TouchEnd(touch(1, 200, 0)), 0.2);
+		assert_eq!(g.gestures().len(), 1);
+	}
+
+	#[test]
+	fn rotate() {
+		let mut g = GestureRecognizer::new();
+		g.handle_event(&Event::TouchStart(touch(0, -50, 0)), 0.0);
+		g.handle_event(&Event::TouchStart(touch(1, 50, 0)), 0.0);
+		g.handle_event(&Event::TouchMove(touch(0, 0, -50)), 0.1);
+		g.handle_event(&Event::TouchMove(touch(1, 0, 50)), 0.1);
+
+		// The first move also changes the span; the quarter turn is split between the two moves
+		let total: f32 = g.gestures().iter().map(|g| match *g { Gesture::Rotate { angle, .. } => angle, _ => 0.0 }).sum();
+		assert!((total - PI / 2.0).abs() < 1e-5, "{:?}", g.gestures());
+
+		match g.gestures().last() {
+			Some(&Gesture::Rotate { center, .. }) => assert_eq!(center, Vec2::zero()),
+			gs => panic!("{:?}", gs)
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,442 @@
+use std::collections::HashSet;
+use std::collections::hash_set;
+
+use math::vec::*;
+use core::event::*;
+
+// Polled input state built from the event queue.
+// Call `update` once per frame; pressed/released queries are only true
+// for the frame in which the transition happened.
+pub struct Input {
+	keys_held: HashSet<KeyCode>,
+	keys_pressed: HashSet<KeyCode>,
+	keys_released: HashSet<KeyCode>,
+	modifiers: Modifiers,
+	text: String,
+
+	buttons_held: HashSet<MouseButton>,
+	buttons_pressed: HashSet<MouseButton>,
+	buttons_released: HashSet<MouseButton>,
+	double_clicked: HashSet<MouseButton>,
+
+	mouse_pos: Vec2i,
+	mouse_delta: Vec2i,
+	has_mouse_pos: bool,
+	mouse_inside: bool,
+	wheel_delta: Vec3,
+	pointer_locked: bool,
+	fullscreen: bool,
+
+	touches: Vec<Touch>,
+	touches_started: Vec<Touch>,
+	touches_ended: Vec<Touch>,
+
+	// Drive the left mouse button from the first touch. Off unless `set_mouse_emulation` enables it
+	emulate_mouse: bool,
+	primary_touch: Option<i32>,
+
+	gamepads: Vec<Gamepad>,
+	gamepad_dead_zone: f32,
+
+	screen_size: Vec2i,
+}
+
+impl Input {
+	pub fn new() -> Input {
+		Input {
+			keys_held: HashSet::new(),
+			keys_pressed: HashSet::new(),
+			keys_released: HashSet::new(),
+			modifiers: Modifiers::none(),
+			text: String::new(),
+
+			buttons_held: HashSet::new(),
+			buttons_pressed: HashSet::new(),
+			buttons_released: HashSet::new(),
+			double_clicked: HashSet::new(),
+
+			mouse_pos: Vec2i::zero(),
+			mouse_delta: Vec2i::zero(),
+			has_mouse_pos: false,
+			mouse_inside: false,
+			wheel_delta: Vec3::zero(),
+			pointer_locked: false,
+			fullscreen: false,
+
+			touches: Vec::new(),
+			touches_started: Vec::new(),
+			touches_ended: Vec::new(),
+
+			emulate_mouse: false,
+			primary_touch: None,
+
+			gamepads: Vec::new(),
+			gamepad_dead_zone: DEFAULT_GAMEPAD_DEAD_ZONE,
+
+			screen_size: Vec2i::zero(),
+		}
+	}
+
+	// Starts a new frame and applies this frame's events, e.g. from `EventLoop::poll_events`
+	pub fn update(&mut self, events: &[Event]) {
+		self.begin_frame();
+		for e in events {
+			self.handle_event(e);
+		}
+	}
+
+	// Clears the per-frame transitions. `update` calls this for you
+	pub fn begin_frame(&mut self) {
+		self.keys_pressed.clear();
+		self.keys_released.clear();
+		self.text.clear();
+
+		self.buttons_pressed.clear();
+		self.buttons_released.clear();
+		self.double_clicked.clear();
+		self.mouse_delta = Vec2i::zero();
+		self.wheel_delta = Vec3::zero();
+
+		self.touches_started.clear();
+		self.touches_ended.clear();
+
+		for pad in self.gamepads.iter_mut() {
+			pad.begin_frame();
+		}
+	}
+
+	pub fn handle_event(&mut self, e: &Event) {
+		match *e {
+			Event::Resize(size) => {
+				self.screen_size = size;
+			}
+
+			Event::KeyDown(ref k) => {
+				self.modifiers = k.modifiers;
+				if self.keys_held.insert(k.code) {
+					self.keys_pressed.insert(k.code);
+				}
+			}
+
+			Event::KeyUp(ref k) => {
+				self.modifiers = k.modifiers;
+				if self.keys_held.remove(&k.code) {
+					self.keys_released.insert(k.code);
+				}
+			}
+
+			Event::Text(c) => {
+				self.text.push(c);
+			}
+
+			Event::Down(m) => {
+				self.modifiers = m.modifiers;
+				self.press_button(m.button, m.pos);
+			}
+
+			Event::Up(m) => {
+				self.modifiers = m.modifiers;
+				self.release_button(m.button, m.pos);
+			}
+
+			Event::DoubleClick(m) => {
+				self.double_clicked.insert(m.button);
+			}
+
+			Event::Move(pos) => {
+				self.move_mouse(pos);
+			}
+
+			Event::Wheel(w) => {
+				self.wheel_delta += w.pixel_delta(self.screen_size.y as f32);
+			}
+
+			Event::MouseEnter(pos) => {
+				self.mouse_inside = true;
+				self.mouse_pos = pos;
+				self.has_mouse_pos = true;
+			}
+
+			Event::MouseLeave(_) => {
+				self.mouse_inside = false;
+			}
+
+			Event::RawMotion(delta) => {
+				self.mouse_delta += delta;
+			}
+
+			Event::PointerLockChanged(locked) => {
+				self.pointer_locked = locked;
+				self.has_mouse_pos = false;
+			}
+
+			Event::FullscreenChanged(fullscreen) => {
+				self.fullscreen = fullscreen;
+			}
+
+			// Key and button releases are never seen once the page loses focus
+			Event::FocusLost | Event::Hidden => {
+				self.reset();
+			}
+
+			Event::FocusGained | Event::Visible | Event::BeforeUnload => {}
+
+			Event::TouchStart(t) => {
+				self.touches.retain(|o| o.id != t.id);
+				self.touches.push(t);
+				self.touches_started.push(t);
+
+				if self.emulate_mouse && self.primary_touch.is_none() {
+					self.primary_touch = Some(t.id);
+					self.has_mouse_pos = false;
+					self.press_button(MouseButton::Left, t.pos);
+				}
+			}
+
+			Event::TouchMove(t) => {
+				if let Some(o) = self.touches.iter_mut().find(|o| o.id == t.id) {
+					o.pos = t.pos;
+				}
+
+				if self.primary_touch == Some(t.id) {
+					self.move_mouse(t.pos);
+				}
+			}
+
+			Event::GamepadConnected(ref state) | Event::GamepadUpdate(ref state) => {
+				match self.gamepads.iter().position(|p| p.index() == state.index) {
+					Some(i) => {
+						self.gamepads[i].state = state.clone();
+					}
+
+					None => {
+						let mut pad = Gamepad::new(state.clone());
+						pad.dead_zone = self.gamepad_dead_zone;
+						self.gamepads.push(pad);
+					}
+				}
+			}
+
+			Event::GamepadDisconnected(index) => {
+				self.gamepads.retain(|p| p.index() != index);
+			}
+
+			Event::TouchEnd(t) | Event::TouchCancel(t) => {
+				self.touches.retain(|o| o.id != t.id);
+				self.touches_ended.push(t);
+
+				if self.primary_touch == Some(t.id) {
+					self.primary_touch = None;
+					self.release_button(MouseButton::Left, t.pos);
+				}
+			}
+		}
+	}
+
+	fn press_button(&mut self, b: MouseButton, pos: Vec2i) {
+		self.move_mouse(pos);
+		if self.buttons_held.insert(b) {
+			self.buttons_pressed.insert(b);
+		}
+	}
+
+	fn release_button(&mut self, b: MouseButton, pos: Vec2i) {
+		self.move_mouse(pos);
+		if self.buttons_held.remove(&b) {
+			self.buttons_released.insert(b);
+		}
+	}
+
+	fn move_mouse(&mut self, pos: Vec2i) {
+		if self.has_mouse_pos {
+			self.mouse_delta += pos - self.mouse_pos;
+		}
+		self.mouse_pos = pos;
+		self.has_mouse_pos = true;
+	}
+
+	pub fn key_held(&self, k: KeyCode) -> bool { self.keys_held.contains(&k) }
+	pub fn key_pressed(&self, k: KeyCode) -> bool { self.keys_pressed.contains(&k) }
+	pub fn key_released(&self, k: KeyCode) -> bool { self.keys_released.contains(&k) }
+
+	pub fn pressed_keys(&self) -> hash_set::Iter<'_, KeyCode> { self.keys_pressed.iter() }
+
+	pub fn modifiers(&self) -> Modifiers { self.modifiers }
+
+	// Text typed this frame
+	pub fn text(&self) -> &str { &self.text }
+
+	pub fn mouse_held(&self, b: MouseButton) -> bool { self.buttons_held.contains(&b) }
+	pub fn mouse_pressed(&self, b: MouseButton) -> bool { self.buttons_pressed.contains(&b) }
+	pub fn mouse_released(&self, b: MouseButton) -> bool { self.buttons_released.contains(&b) }
+	pub fn double_clicked(&self, b: MouseButton) -> bool { self.double_clicked.contains(&b) }
+
+	pub fn pressed_buttons(&self) -> hash_set::Iter<'_, MouseButton> { self.buttons_pressed.iter() }
+
+	pub fn mouse_pos(&self) -> Vec2i { self.mouse_pos }
+	pub fn mouse_delta(&self) -> Vec2i { self.mouse_delta }
+	pub fn mouse_inside(&self) -> bool { self.mouse_inside }
+	pub fn pointer_locked(&self) -> bool { self.pointer_locked }
+	pub fn fullscreen(&self) -> bool { self.fullscreen }
+
+	// Wheel movement this frame, in pixels
+	pub fn wheel_delta(&self) -> Vec3 { self.wheel_delta }
+
+	pub fn touches(&self) -> &[Touch] { &self.touches }
+	pub fn touch(&self, id: i32) -> Option<Touch> { self.touches.iter().find(|t| t.id == id).cloned() }
+	pub fn touch_count(&self) -> usize { self.touches.len() }
+
+	// Touches that began this frame
+	pub fn touches_started(&self) -> &[Touch] { &self.touches_started }
+
+	// Touches that ended or were cancelled this frame
+	pub fn touches_ended(&self) -> &[Touch] { &self.touches_ended }
+
+	pub fn mouse_emulation(&self) -> bool { self.emulate_mouse }
+	pub fn set_mouse_emulation(&mut self, enabled: bool) {
+		if !enabled {
+			if let Some(id) = self.primary_touch.take() {
+				let pos = self.touch(id).map(|t| t.pos).unwrap_or(self.mouse_pos);
+				self.release_button(MouseButton::Left, pos);
+			}
+		}
+		self.emulate_mouse = enabled;
+	}
+
+	pub fn gamepads(&self) -> &[Gamepad] { &self.gamepads }
+	pub fn gamepad(&self, index: i32) -> Option<&Gamepad> { self.gamepads.iter().find(|p| p.index() == index) }
+
+	pub fn set_gamepad_dead_zone(&mut self, dead_zone: f32) {
+		self.gamepad_dead_zone = dead_zone;
+		for pad in self.gamepads.iter_mut() {
+			pad.dead_zone = dead_zone;
+		}
+	}
+
+	pub fn screen_size(&self) -> Vec2i { self.screen_size }
+
+	// Releases everything that is held, e.g. when the window loses focus
+	pub fn reset(&mut self) {
+		self.keys_released.extend(self.keys_held.drain());
+		self.modifiers = Modifiers::none();
+		self.buttons_released.extend(self.buttons_held.drain());
+		self.touches_ended.extend(self.touches.drain(..));
+		self.primary_touch = None;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key(code: KeyCode) -> KeyEvent {
+		KeyEvent::new(code, Key::new("a"))
+	}
+
+	#[test]
+	fn key_transitions_last_one_frame() {
+		let mut input = Input::new();
+
+		input.update(&[Event::KeyDown(key(KeyCode::KeyW))]);
+		assert!(input.key_pressed(KeyCode::KeyW));
+		assert!(input.key_held(KeyCode::KeyW));
+		assert!(!input.key_released(KeyCode::KeyW));
+
+		// Auto-repeat doesn't press again
+		let mut repeat = key(KeyCode::KeyW);
+		repeat.repeat = true;
+		input.update(&[Event::KeyDown(repeat)]);
+		assert!(!input.key_pressed(KeyCode::KeyW));
+		assert!(input.key_held(KeyCode::KeyW));
+
+		input.update(&[Event::KeyUp(key(KeyCode::KeyW))]);
+		assert!(!input.key_held(KeyCode::KeyW));
+		assert!(input.key_released(KeyCode::KeyW));
+
+		input.update(&[]);
+		assert!(!input.key_released(KeyCode::KeyW));
+	}
+
+	#[test]
+	fn press_and_release_in_one_frame() {
+		let mut input = Input::new();
+		input.update(&[Event::KeyDown(key(KeyCode::Space)), Event::KeyUp(key(KeyCode::Space))]);
+		assert!(input.key_pressed(KeyCode::Space));
+		assert!(input.key_released(KeyCode::Space));
+		assert!(!input.key_held(KeyCode::Space));
+	}
+
+	#[test]
+	fn release_without_press_is_ignored() {
+		let mut input = Input::new();
+		input.update(&[Event::KeyUp(key(KeyCode::KeyA))]);
+		assert!(!input.key_released(KeyCode::KeyA));
+	}
+
+	#[test]
+	fn mouse_delta_accumulates_per_frame() {
+		let mut input = Input::new();
+
+		// The first position has nothing to be relative to
+		input.update(&[Event::Move(Vec2i::new(10, 10))]);
+		assert_eq!(input.mouse_delta(), Vec2i::zero());
+
+		input.update(&[Event::Move(Vec2i::new(15, 12)), Event::Move(Vec2i::new(20, 8))]);
+		assert_eq!(input.mouse_pos(), Vec2i::new(20, 8));
+		assert_eq!(input.mouse_delta(), Vec2i::new(10, -2));
+
+		input.begin_frame();
+		assert_eq!(input.mouse_delta(), Vec2i::zero());
+		assert_eq!(input.mouse_pos(), Vec2i::new(20, 8));
+	}
+
+	#[test]
+	fn wheel_delta_in_pixels() {
+		let mut input = Input::new();
+		input.update(&[
+			Event::Resize(Vec2i::new(800, 600)),
+			Event::Wheel(WheelEvent::new(Vec3::new(0.0, 3.0, 0.0), WheelDeltaMode::Pixel)),
+			Event::Wheel(WheelEvent::new(Vec3::new(0.0, 1.0, 0.0), WheelDeltaMode::Line)),
+		]);
+		assert_eq!(input.wheel_delta(), Vec3::new(0.0, 3.0 + WHEEL_LINE_HEIGHT, 0.0));
+
+		input.update(&[Event::Wheel(WheelEvent::new(Vec3::new(0.0, -1.0, 0.0), WheelDeltaMode::Page))]);
+		assert_eq!(input.wheel_delta(), Vec3::new(0.0, -600.0, 0.0));
+
+		input.update(&[]);
+		assert_eq!(input.wheel_delta(), Vec3::zero());
+	}
+
+	#[test]
+	fn touches_only_drive_the_mouse_when_emulating() {
+		let touch = Touch { id: 3, pos: Vec2i::new(40, 50) };
+
+		let mut input = Input::new();
+		input.update(&[Event::TouchStart(touch)]);
+		assert_eq!(input.touch_count(), 1);
+		assert!(!input.mouse_pressed(MouseButton::Left));
+
+		let mut input = Input::new();
+		input.set_mouse_emulation(true);
+		input.update(&[Event::TouchStart(touch)]);
+		assert!(input.mouse_pressed(MouseButton::Left));
+		assert_eq!(input.mouse_pos(), touch.pos);
+
+		input.update(&[Event::TouchEnd(touch)]);
+		assert!(input.mouse_released(MouseButton::Left));
+	}
+
+	#[test]
+	fn focus_loss_releases_everything() {
+		let mut input = Input::new();
+		input.update(&[
+			Event::KeyDown(key(KeyCode::KeyD)),
+			Event::Down(MouseEvent::new(Vec2i::new(1, 1), MouseButton::Left)),
+		]);
+
+		input.update(&[Event::FocusLost]);
+		assert!(!input.key_held(KeyCode::KeyD));
+		assert!(input.key_released(KeyCode::KeyD));
+		assert!(input.mouse_released(MouseButton::Left));
+	}
+}
\ No newline at end of file
@@ -0,0 +1,309 @@
+use std::ffi::CStr;
+
+use bindings::emscripten::*;
+
+macro_rules! key_codes {
+	($($name:ident),+ $(,)*) => {
+		// Physical key, named after the DOM `KeyboardEvent.code` values.
+		// Independent of layout: `KeyA` is the key left of `KeyS` on a QWERTY board.
+		#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+		pub enum KeyCode {
+			$($name),+
+		}
+
+		impl KeyCode {
+			pub fn all() -> &'static [KeyCode] {
+				const ALL: &'static [KeyCode] = &[ $(KeyCode::$name),+ ];
+				ALL
+			}
+
+			// The DOM `code` string for this key
+			pub fn code(&self) -> &'static str {
+				match *self {
+					$(KeyCode::$name => stringify!($name)),+
+				}
+			}
+
+			pub fn from_code(code: &str) -> Option<KeyCode> {
+				match code {
+					$(stringify!($name) => Some(KeyCode::$name),)+
+
+					// Older browsers
+					"OSLeft" => Some(KeyCode::MetaLeft),
+					"OSRight" => Some(KeyCode::MetaRight),
+					"VolumeMute" => Some(KeyCode::AudioVolumeMute),
+					"VolumeDown" => Some(KeyCode::AudioVolumeDown),
+					"VolumeUp" => Some(KeyCode::AudioVolumeUp),
+
+					_ => None
+				}
+			}
+		}
+	};
+}
+
+key_codes! {
+	// Writing system
+	Backquote, Backslash, BracketLeft, BracketRight, Comma, Equal, Minus, Period, Quote, Semicolon, Slash,
+	IntlBackslash, IntlRo, IntlYen,
+
+	Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+
+	KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+	KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+
+	// Functional
+	AltLeft, AltRight, ControlLeft, ControlRight, MetaLeft, MetaRight, ShiftLeft, ShiftRight,
+	Backspace, CapsLock, ContextMenu, Enter, Space, Tab,
+	Convert, KanaMode, NonConvert, Lang1, Lang2, Lang3, Lang4, Lang5,
+
+	// Control pad and arrows
+	Delete, End, Help, Home, Insert, PageDown, PageUp,
+	ArrowDown, ArrowLeft, ArrowRight, ArrowUp,
+
+	// Numpad
+	NumLock,
+	Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+	NumpadAdd, NumpadBackspace, NumpadClear, NumpadClearEntry, NumpadComma, NumpadDecimal,
+	NumpadDivide, NumpadEnter, NumpadEqual, NumpadHash, NumpadMultiply,
+	NumpadParenLeft, NumpadParenRight, NumpadStar, NumpadSubtract,
+	NumpadMemoryAdd, NumpadMemoryClear, NumpadMemoryRecall, NumpadMemoryStore, NumpadMemorySubtract,
+
+	// Function
+	Escape,
+	F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+	F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24,
+	Fn, FnLock, PrintScreen, ScrollLock, Pause,
+
+	// Media
+	BrowserBack, BrowserFavorites, BrowserForward, BrowserHome, BrowserRefresh, BrowserSearch, BrowserStop,
+	Eject, LaunchApp1, LaunchApp2, LaunchMail,
+	MediaPlayPause, MediaSelect, MediaStop, MediaTrackNext, MediaTrackPrevious,
+	AudioVolumeDown, AudioVolumeMute, AudioVolumeUp,
+	Power, Sleep, WakeUp,
+
+	// Legacy
+	Hyper, Super, Turbo, Abort, Resume, Suspend,
+	Again, Copy, Cut, Find, Open, Paste, Props, Select, Undo,
+	Hiragana, Katakana,
+
+	Unidentified,
+}
+
+impl KeyCode {
+	pub fn digit(&self) -> Option<u32> {
+		match *self {
+			KeyCode::Digit0 | KeyCode::Numpad0 => Some(0),
+			KeyCode::Digit1 | KeyCode::Numpad1 => Some(1),
+			KeyCode::Digit2 | KeyCode::Numpad2 => Some(2),
+			KeyCode::Digit3 | KeyCode::Numpad3 => Some(3),
+			KeyCode::Digit4 | KeyCode::Numpad4 => Some(4),
+			KeyCode::Digit5 | KeyCode::Numpad5 => Some(5),
+			KeyCode::Digit6 | KeyCode::Numpad6 => Some(6),
+			KeyCode::Digit7 | KeyCode::Numpad7 => Some(7),
+			KeyCode::Digit8 | KeyCode::Numpad8 => Some(8),
+			KeyCode::Digit9 | KeyCode::Numpad9 => Some(9),
+			_ => None
+		}
+	}
+
+	// Uppercase letter for `KeyA`..`KeyZ`
+	pub fn letter(&self) -> Option<char> {
+		let code = self.code();
+		if code.len() == 4 && code.starts_with("Key") {
+			code.chars().nth(3)
+		} else {
+			None
+		}
+	}
+
+	pub fn function_key(&self) -> Option<u32> {
+		let code = self.code();
+		if code.len() > 1 && code.starts_with('F') {
+			code[1..].parse().ok()
+		} else {
+			None
+		}
+	}
+
+	pub fn is_modifier(&self) -> bool {
+		match *self {
+			KeyCode::ShiftLeft | KeyCode::ShiftRight
+			| KeyCode::ControlLeft | KeyCode::ControlRight
+			| KeyCode::AltLeft | KeyCode::AltRight
+			| KeyCode::MetaLeft | KeyCode::MetaRight => true,
+			_ => false
+		}
+	}
+}
+
+// Logical key, i.e. the DOM `KeyboardEvent.key` value.
+// Depends on layout and modifiers: shift + `KeyA` gives `Char('A')`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+	Char(char),
+	Named(String),
+}
+
+impl Key {
+	pub fn new(key: &str) -> Key {
+		let mut chars = key.chars();
+		match (chars.next(), chars.next()) {
+			(Some(c), None) => Key::Char(c),
+			_ => Key::Named(key.to_owned())
+		}
+	}
+
+	pub fn is_named(&self, name: &str) -> bool {
+		match *self {
+			Key::Named(ref n) => n == name,
+			_ => false
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KeyLocation {
+	Standard,
+	Left,
+	Right,
+	Numpad,
+}
+
+impl KeyLocation {
+	pub fn from_dom(location: u32) -> KeyLocation {
+		match location {
+			1 => KeyLocation::Left,
+			2 => KeyLocation::Right,
+			3 => KeyLocation::Numpad,
+			_ => KeyLocation::Standard
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+	pub shift: bool,
+	pub ctrl: bool,
+	pub alt: bool,
+	pub meta: bool,
+}
+
+impl Modifiers {
+	pub fn none() -> Modifiers { Modifiers::default() }
+
+	pub fn from_flags(shift: i32, ctrl: i32, alt: i32, meta: i32) -> Modifiers {
+		Modifiers {
+			shift: shift != 0,
+			ctrl: ctrl != 0,
+			alt: alt != 0,
+			meta: meta != 0,
+		}
+	}
+
+	pub fn any(&self) -> bool {
+		self.shift || self.ctrl || self.alt || self.meta
+	}
+
+	pub fn count(&self) -> u32 {
+		self.shift as u32 + self.ctrl as u32 + self.alt as u32 + self.meta as u32
+	}
+
+	// True if every modifier set in `self` is also set in `held`
+	pub fn satisfied_by(&self, held: Modifiers) -> bool {
+		(!self.shift || held.shift)
+			&& (!self.ctrl || held.ctrl)
+			&& (!self.alt || held.alt)
+			&& (!self.meta || held.meta)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyEvent {
+	pub code: KeyCode,
+	pub key: Key,
+	pub location: KeyLocation,
+	pub modifiers: Modifiers,
+	pub repeat: bool,
+}
+
+impl KeyEvent {
+	pub fn new(code: KeyCode, key: Key) -> KeyEvent {
+		KeyEvent {
+			code,
+			key,
+			location: KeyLocation::Standard,
+			modifiers: Modifiers::none(),
+			repeat: false,
+		}
+	}
+
+	pub fn from_ems(e: &EmscriptenKeyboardEvent) -> KeyEvent {
+		let code = unsafe{ CStr::from_ptr(e.code.as_ptr()) };
+		let key = unsafe{ CStr::from_ptr(e.key.as_ptr()) };
+
+		let code = code.to_str().ok()
+			.and_then(KeyCode::from_code)
+			.unwrap_or(KeyCode::Unidentified);
+
+		let key = Key::new(key.to_str().unwrap_or("Unidentified"));
+
+		KeyEvent {
+			code,
+			key,
+			location: KeyLocation::from_dom(e.location),
+			modifiers: Modifiers::from_flags(e.shiftKey, e.ctrlKey, e.altKey, e.metaKey),
+			repeat: e.repeat != 0,
+		}
+	}
+
+	// The character this key event types, if any.
+	// Shortcuts (ctrl or meta held) don't produce text, but AltGr arrives as ctrl+alt on Windows and does
+	pub fn text(&self) -> Option<char> {
+		let m = self.modifiers;
+		if (m.ctrl && !m.alt) || m.meta { return None }
+
+		match self.key {
+			Key::Char(c) if !c.is_control() => Some(c),
+			_ => None
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn typed(c: char, modifiers: Modifiers) -> Option<char> {
+		KeyEvent {
+			code: KeyCode::Unidentified,
+			key: Key::Char(c),
+			location: KeyLocation::Standard,
+			modifiers,
+			repeat: false,
+		}.text()
+	}
+
+	#[test]
+	fn shortcuts_dont_type() {
+		let ctrl = Modifiers { ctrl: true, .. Modifiers::none() };
+		let meta = Modifiers { meta: true, .. Modifiers::none() };
+		let ctrl_meta_alt = Modifiers { ctrl: true, alt: true, meta: true, .. Modifiers::none() };
+
+		assert_eq!(typed('a', Modifiers::none()), Some('a'));
+		assert_eq!(typed('c', ctrl), None);
+		assert_eq!(typed('c', meta), None);
+		assert_eq!(typed('c', ctrl_meta_alt), None);
+	}
+
+	#[test]
+	fn alt_gr_types() {
+		let alt_gr = Modifiers { ctrl: true, alt: true, .. Modifiers::none() };
+		let alt = Modifiers { alt: true, .. Modifiers::none() };
+
+		assert_eq!(typed('@', alt_gr), Some('@'));
+		assert_eq!(typed('\u{20ac}', alt), Some('\u{20ac}'));
+		assert_eq!(typed('\n', alt_gr), None);
+	}
+}
\ No newline at end of file
@@ -1,4 +1,14 @@
 #[macro_use]
 pub mod event;
+pub mod keyboard;
+pub mod mouse;
+pub mod touch;
+pub mod gesture;
+pub mod gamepad;
+pub mod input;
+pub mod action;
 pub mod context;
+pub mod display;
+pub mod app;
+pub mod replay;
 pub mod util;
\ No newline at end of file
@@ -0,0 +1,132 @@
+use bindings::emscripten::*;
+use math::vec::*;
+use core::keyboard::Modifiers;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+	Left,
+	Middle,
+	Right,
+	Back,
+	Forward,
+	Other(u16),
+}
+
+impl MouseButton {
+	// From the DOM `MouseEvent.button` index
+	pub fn from_dom(button: u16) -> MouseButton {
+		match button {
+			0 => MouseButton::Left,
+			1 => MouseButton::Middle,
+			2 => MouseButton::Right,
+			3 => MouseButton::Back,
+			4 => MouseButton::Forward,
+			x => MouseButton::Other(x)
+		}
+	}
+
+	pub fn to_dom(&self) -> u16 {
+		match *self {
+			MouseButton::Left => 0,
+			MouseButton::Middle => 1,
+			MouseButton::Right => 2,
+			MouseButton::Back => 3,
+			MouseButton::Forward => 4,
+			MouseButton::Other(x) => x,
+		}
+	}
+
+	pub fn name(&self) -> String {
+		match *self {
+			MouseButton::Other(x) => format!("Other{}", x),
+			b => format!("{:?}", b),
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<MouseButton> {
+		match name {
+			"Left" => Some(MouseButton::Left),
+			"Middle" => Some(MouseButton::Middle),
+			"Right" => Some(MouseButton::Right),
+			"Back" => Some(MouseButton::Back),
+			"Forward" => Some(MouseButton::Forward),
+			x if x.starts_with("Other") => x[5..].parse().ok().map(MouseButton::Other),
+			_ => None
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MouseEvent {
+	// Relative to the top-left corner of the canvas
+	pub pos: Vec2i,
+	pub button: MouseButton,
+	pub modifiers: Modifiers,
+}
+
+impl MouseEvent {
+	pub fn new(pos: Vec2i, button: MouseButton) -> MouseEvent {
+		MouseEvent { pos, button, modifiers: Modifiers::none() }
+	}
+
+	pub fn from_ems(e: &EmscriptenMouseEvent) -> MouseEvent {
+		MouseEvent {
+			pos: Vec2i::new(e.canvasX, e.canvasY),
+			button: MouseButton::from_dom(e.button),
+			modifiers: Modifiers::from_flags(e.shiftKey, e.ctrlKey, e.altKey, e.metaKey),
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WheelDeltaMode {
+	Pixel,
+	Line,
+	Page,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct WheelEvent {
+	pub pos: Vec2i,
+	pub delta: Vec3,
+	pub mode: WheelDeltaMode,
+	pub modifiers: Modifiers,
+}
+
+// Used to convert line and page deltas into pixels
+pub const WHEEL_LINE_HEIGHT: f32 = 16.0;
+
+impl WheelEvent {
+	pub fn new(delta: Vec3, mode: WheelDeltaMode) -> WheelEvent {
+		WheelEvent {
+			pos: Vec2i::zero(),
+			delta,
+			mode,
+			modifiers: Modifiers::none(),
+		}
+	}
+
+	pub fn from_ems(e: &EmscriptenWheelEvent) -> WheelEvent {
+		let mode = match e.deltaMode {
+			1 => WheelDeltaMode::Line,
+			2 => WheelDeltaMode::Page,
+			_ => WheelDeltaMode::Pixel
+		};
+
+		WheelEvent {
+			pos: Vec2i::new(e.mouse.canvasX, e.mouse.canvasY),
+			delta: Vec3::new(e.deltaX as f32, e.deltaY as f32, e.deltaZ as f32),
+			mode,
+			modifiers: Modifiers::from_flags(e.mouse.shiftKey, e.mouse.ctrlKey, e.mouse.altKey, e.mouse.metaKey),
+		}
+	}
+
+	// Delta in pixels, given the height of a page (usually the canvas height)
+	pub fn pixel_delta(&self, page_height: f32) -> Vec3 {
+		match self.mode {
+			WheelDeltaMode::Pixel => self.delta,
+			WheelDeltaMode::Line => self.delta * WHEEL_LINE_HEIGHT,
+			WheelDeltaMode::Page => self.delta * page_height,
+		}
+	}
+}
\ No newline at end of file
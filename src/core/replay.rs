@@ -0,0 +1,581 @@
+use std::fs::File;
+use std::io::{ self, Read, Write };
+
+use math::vec::*;
+use core::event::*;
+use core::app::*;
+
+// Per-frame input captured from a Runner, replayable through the same loop.
+//
+// File layout, little endian:
+//	"ERPL" u32:version u32:frame_count
+//	per frame: f64:frame_time u32:event_count events...
+//	per event: u8:tag payload...
+#[derive(Clone, Debug)]
+pub struct Recording {
+	pub frames: Vec<RecordedFrame>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordedFrame {
+	// Unclamped frame duration in seconds, as passed to `Runner::step_dt`
+	pub frame_time: f64,
+	pub events: Vec<Event>,
+}
+
+const MAGIC: &'static [u8] = b"ERPL";
+const VERSION: u32 = 1;
+
+impl Recording {
+	pub fn new() -> Recording {
+		Recording { frames: Vec::new() }
+	}
+
+	pub fn record_frame(&mut self, frame_time: f64, events: &[Event]) {
+		self.frames.push(RecordedFrame {
+			frame_time,
+			events: events.to_vec(),
+		});
+	}
+
+	pub fn duration(&self) -> f64 {
+		self.frames.iter().map(|f| f.frame_time).sum()
+	}
+
+	// Feeds every frame through `runner`, which should be headless so no live input
+	// gets mixed in. Returns the number of frames run before the app quit
+	pub fn play<A: App>(&self, runner: &mut Runner<A>) -> usize {
+		for (i, frame) in self.frames.iter().enumerate() {
+			for e in frame.events.iter() {
+				runner.events.push(e.clone());
+			}
+
+			if !runner.step_dt(frame.frame_time) {
+				return i + 1
+			}
+		}
+
+		self.frames.len()
+	}
+
+	// Runs a fresh app through the whole recording without a browser, for regression tests
+	pub fn replay<A: App>(&self, app: A, config: LoopConfig) -> Runner<A> {
+		let mut runner = Runner::headless(app, config);
+		self.play(&mut runner);
+		runner
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut w = ByteWriter { buf: Vec::new() };
+
+		w.buf.extend_from_slice(MAGIC);
+		w.u32(VERSION);
+		w.u32(self.frames.len() as u32);
+
+		for frame in self.frames.iter() {
+			w.f64(frame.frame_time);
+			w.u32(frame.events.len() as u32);
+			for e in frame.events.iter() {
+				write_event(&mut w, e);
+			}
+		}
+
+		w.buf
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Recording, String> {
+		let mut r = ByteReader { buf: bytes, pos: 0 };
+
+		if r.bytes(MAGIC.len())? != MAGIC {
+			return Err("not a recording".to_owned())
+		}
+
+		let version = r.u32()?;
+		if version != VERSION {
+			return Err(format!("unsupported recording version {}", version))
+		}
+
+		let frame_count = r.u32()?;
+		let mut frames = Vec::new();
+
+		for _ in 0..frame_count {
+			let frame_time = r.f64()?;
+			let event_count = r.u32()?;
+
+			let mut events = Vec::new();
+			for _ in 0..event_count {
+				events.push(read_event(&mut r)?);
+			}
+
+			frames.push(RecordedFrame { frame_time, events });
+		}
+
+		Ok(Recording { frames })
+	}
+
+	pub fn save(&self, path: &str) -> io::Result<()> {
+		let mut file = File::create(path)?;
+		file.write_all(&self.to_bytes())
+	}
+
+	pub fn load(path: &str) -> Result<Recording, String> {
+		let mut bytes = Vec::new();
+		File::open(path)
+			.and_then(|mut f| f.read_to_end(&mut bytes))
+			.map_err(|e| format!("{}: {}", path, e))?;
+
+		Recording::from_bytes(&bytes)
+	}
+}
+
+struct ByteWriter { buf: Vec<u8> }
+
+impl ByteWriter {
+	fn u8(&mut self, v: u8) { self.buf.push(v) }
+	fn bool(&mut self, v: bool) { self.u8(v as u8) }
+	fn u16(&mut self, v: u16) { self.buf.extend_from_slice(&[v as u8, (v >> 8) as u8]) }
+
+	fn u32(&mut self, v: u32) {
+		self.buf.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8])
+	}
+
+	fn u64(&mut self, v: u64) {
+		self.u32(v as u32);
+		self.u32((v >> 32) as u32);
+	}
+
+	fn i32(&mut self, v: i32) { self.u32(v as u32) }
+	fn f32(&mut self, v: f32) { self.u32(v.to_bits()) }
+	fn f64(&mut self, v: f64) { self.u64(v.to_bits()) }
+
+	fn str(&mut self, v: &str) {
+		self.u32(v.len() as u32);
+		self.buf.extend_from_slice(v.as_bytes());
+	}
+
+	fn vec2i(&mut self, v: Vec2i) {
+		self.i32(v.x);
+		self.i32(v.y);
+	}
+
+	fn modifiers(&mut self, m: Modifiers) {
+		self.u8(m.shift as u8 | (m.ctrl as u8) << 1 | (m.alt as u8) << 2 | (m.meta as u8) << 3)
+	}
+}
+
+struct ByteReader<'a> { buf: &'a [u8], pos: usize }
+
+impl<'a> ByteReader<'a> {
+	fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+		if self.pos + n > self.buf.len() {
+			return Err("unexpected end of recording".to_owned())
+		}
+		let b = &self.buf[self.pos..self.pos + n];
+		self.pos += n;
+		Ok(b)
+	}
+
+	fn u8(&mut self) -> Result<u8, String> { Ok(self.bytes(1)?[0]) }
+	fn bool(&mut self) -> Result<bool, String> { Ok(self.u8()? != 0) }
+
+	fn u16(&mut self) -> Result<u16, String> {
+		let b = self.bytes(2)?;
+		Ok(b[0] as u16 | (b[1] as u16) << 8)
+	}
+
+	fn u32(&mut self) -> Result<u32, String> {
+		let b = self.bytes(4)?;
+		Ok(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+	}
+
+	fn u64(&mut self) -> Result<u64, String> {
+		let lo = self.u32()? as u64;
+		let hi = self.u32()? as u64;
+		Ok(lo | hi << 32)
+	}
+
+	fn i32(&mut self) -> Result<i32, String> { Ok(self.u32()? as i32) }
+	fn f32(&mut self) -> Result<f32, String> { Ok(f32::from_bits(self.u32()?)) }
+	fn f64(&mut self) -> Result<f64, String> { Ok(f64::from_bits(self.u64()?)) }
+
+	fn str(&mut self) -> Result<String, String> {
+		let len = self.u32()? as usize;
+		let b = self.bytes(len)?;
+		String::from_utf8(b.to_vec()).map_err(|_| "invalid string in recording".to_owned())
+	}
+
+	fn vec2i(&mut self) -> Result<Vec2i, String> {
+		Ok(Vec2i::new(self.i32()?, self.i32()?))
+	}
+
+	fn modifiers(&mut self) -> Result<Modifiers, String> {
+		let m = self.u8()?;
+		Ok(Modifiers {
+			shift: m & 1 != 0,
+			ctrl: m & 2 != 0,
+			alt: m & 4 != 0,
+			meta: m & 8 != 0,
+		})
+	}
+
+	fn char(&mut self) -> Result<char, String> {
+		::std::char::from_u32(self.u32()?).ok_or("invalid char in recording".to_owned())
+	}
+}
+
+fn write_mouse_event(w: &mut ByteWriter, m: &MouseEvent) {
+	w.vec2i(m.pos);
+	w.u16(m.button.to_dom());
+	w.modifiers(m.modifiers);
+}
+
+fn read_mouse_event(r: &mut ByteReader) -> Result<MouseEvent, String> {
+	Ok(MouseEvent {
+		pos: r.vec2i()?,
+		button: MouseButton::from_dom(r.u16()?),
+		modifiers: r.modifiers()?,
+	})
+}
+
+fn write_touch(w: &mut ByteWriter, t: &Touch) {
+	w.i32(t.id);
+	w.vec2i(t.pos);
+}
+
+fn read_touch(r: &mut ByteReader) -> Result<Touch, String> {
+	Ok(Touch::new(r.i32()?, r.vec2i()?))
+}
+
+fn write_key_event(w: &mut ByteWriter, k: &KeyEvent) {
+	w.str(k.code.code());
+	match k.key {
+		Key::Char(c) => {
+			w.u8(0);
+			w.u32(c as u32);
+		}
+		Key::Named(ref n) => {
+			w.u8(1);
+			w.str(n);
+		}
+	}
+	w.u8(match k.location {
+		KeyLocation::Standard => 0,
+		KeyLocation::Left => 1,
+		KeyLocation::Right => 2,
+		KeyLocation::Numpad => 3,
+	});
+	w.modifiers(k.modifiers);
+	w.bool(k.repeat);
+}
+
+fn read_key_event(r: &mut ByteReader) -> Result<KeyEvent, String> {
+	let code = KeyCode::from_code(&r.str()?).unwrap_or(KeyCode::Unidentified);
+	let key = match r.u8()? {
+		0 => Key::Char(r.char()?),
+		_ => Key::Named(r.str()?),
+	};
+
+	Ok(KeyEvent {
+		code,
+		key,
+		location: KeyLocation::from_dom(r.u8()? as u32),
+		modifiers: r.modifiers()?,
+		repeat: r.bool()?,
+	})
+}
+
+fn write_gamepad(w: &mut ByteWriter, g: &GamepadState) {
+	w.i32(g.index);
+	w.str(&g.id);
+	w.bool(g.standard_mapping);
+	w.f64(g.timestamp);
+
+	w.u32(g.buttons.len() as u32);
+	for (&b, &a) in g.buttons.iter().zip(g.analog_buttons.iter()) {
+		w.bool(b);
+		w.f32(a);
+	}
+
+	w.u32(g.axes.len() as u32);
+	for &a in g.axes.iter() {
+		w.f32(a);
+	}
+}
+
+fn read_gamepad(r: &mut ByteReader) -> Result<GamepadState, String> {
+	let mut g = GamepadState::new(r.i32()?);
+	g.id = r.str()?;
+	g.standard_mapping = r.bool()?;
+	g.timestamp = r.f64()?;
+
+	g.buttons.clear();
+	g.analog_buttons.clear();
+	for _ in 0..r.u32()? {
+		g.buttons.push(r.bool()?);
+		g.analog_buttons.push(r.f32()?);
+	}
+
+	g.axes.clear();
+	for _ in 0..r.u32()? {
+		g.axes.push(r.f32()?);
+	}
+
+	Ok(g)
+}
+
+fn write_event(w: &mut ByteWriter, e: &Event) {
+	match *e {
+		Event::Resize(size) => { w.u8(0); w.vec2i(size); }
+
+		Event::Down(ref m) => { w.u8(1); write_mouse_event(w, m); }
+		Event::Up(ref m) => { w.u8(2); write_mouse_event(w, m); }
+		Event::Move(pos) => { w.u8(3); w.vec2i(pos); }
+		Event::DoubleClick(ref m) => { w.u8(4); write_mouse_event(w, m); }
+
+		Event::Wheel(ref wh) => {
+			w.u8(5);
+			w.vec2i(wh.pos);
+			w.f32(wh.delta.x);
+			w.f32(wh.delta.y);
+			w.f32(wh.delta.z);
+			w.u8(match wh.mode {
+				WheelDeltaMode::Pixel => 0,
+				WheelDeltaMode::Line => 1,
+				WheelDeltaMode::Page => 2,
+			});
+			w.modifiers(wh.modifiers);
+		}
+
+		Event::MouseEnter(pos) => { w.u8(6); w.vec2i(pos); }
+		Event::MouseLeave(pos) => { w.u8(7); w.vec2i(pos); }
+		Event::RawMotion(delta) => { w.u8(8); w.vec2i(delta); }
+
+		Event::TouchStart(ref t) => { w.u8(9); write_touch(w, t); }
+		Event::TouchMove(ref t) => { w.u8(10); write_touch(w, t); }
+		Event::TouchEnd(ref t) => { w.u8(11); write_touch(w, t); }
+		Event::TouchCancel(ref t) => { w.u8(12); write_touch(w, t); }
+
+		Event::GamepadConnected(ref g) => { w.u8(13); write_gamepad(w, g); }
+		Event::GamepadDisconnected(index) => { w.u8(14); w.i32(index); }
+		Event::GamepadUpdate(ref g) => { w.u8(15); write_gamepad(w, g); }
+
+		Event::FullscreenChanged(v) => { w.u8(16); w.bool(v); }
+		Event::PointerLockChanged(v) => { w.u8(17); w.bool(v); }
+
+		Event::Hidden => w.u8(18),
+		Event::Visible => w.u8(19),
+		Event::FocusGained => w.u8(20),
+		Event::FocusLost => w.u8(21),
+		Event::BeforeUnload => w.u8(22),
+
+		Event::KeyDown(ref k) => { w.u8(23); write_key_event(w, k); }
+		Event::KeyUp(ref k) => { w.u8(24); write_key_event(w, k); }
+		Event::Text(c) => { w.u8(25); w.u32(c as u32); }
+	}
+}
+
+fn read_event(r: &mut ByteReader) -> Result<Event, String> {
+	let e = match r.u8()? {
+		0 => Event::Resize(r.vec2i()?),
+
+		1 => Event::Down(read_mouse_event(r)?),
+		2 => Event::Up(read_mouse_event(r)?),
+		3 => Event::Move(r.vec2i()?),
+		4 => Event::DoubleClick(read_mouse_event(r)?),
+
+		5 => {
+			let pos = r.vec2i()?;
+			let delta = Vec3::new(r.f32()?, r.f32()?, r.f32()?);
+			let mode = match r.u8()? {
+				1 => WheelDeltaMode::Line,
+				2 => WheelDeltaMode::Page,
+				_ => WheelDeltaMode::Pixel,
+			};
+			Event::Wheel(WheelEvent { pos, delta, mode, modifiers: r.modifiers()? })
+		}
+
+		6 => Event::MouseEnter(r.vec2i()?),
+		7 => Event::MouseLeave(r.vec2i()?),
+		8 => Event::RawMotion(r.vec2i()?),
+
+		9 => Event::TouchStart(read_touch(r)?),
+		10 => Event::TouchMove(read_touch(r)?),
+		11 => Event::TouchEnd(read_touch(r)?),
+		12 => Event::TouchCancel(read_touch(r)?),
+
+		13 => Event::GamepadConnected(read_gamepad(r)?),
+		14 => Event::GamepadDisconnected(r.i32()?),
+		15 => Event::GamepadUpdate(read_gamepad(r)?),
+
+		16 => Event::FullscreenChanged(r.bool()?),
+		17 => Event::PointerLockChanged(r.bool()?),
+
+		18 => Event::Hidden,
+		19 => Event::Visible,
+		20 => Event::FocusGained,
+		21 => Event::FocusLost,
+		22 => Event::BeforeUnload,
+
+		23 => Event::KeyDown(read_key_event(r)?),
+		24 => Event::KeyUp(read_key_event(r)?),
+		25 => Event::Text(r.char()?),
+
+		x => return Err(format!("unknown event tag {}", x))
+	};
+
+	Ok(e)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key(code: KeyCode, key: Key) -> KeyEvent {
+		let mut k = KeyEvent::new(code, key);
+		k.modifiers.shift = true;
+		k
+	}
+
+	fn sample() -> Recording {
+		let mut pad = GamepadState::new(1);
+		pad.id = "pad".to_owned();
+		pad.set_button(GamepadButton::South, 1.0);
+		pad.set_axis(GamepadAxis::LeftX, -0.5);
+
+		let mut rec = Recording::new();
+		rec.record_frame(0.016, &[
+			Event::Resize(Vec2i::new(640, 480)),
+			Event::KeyDown(key(KeyCode::KeyW, Key::Char('W'))),
+			Event::Text('W'),
+			Event::Move(Vec2i::new(3, -4)),
+		]);
+		rec.record_frame(0.0, &[]);
+		rec.record_frame(0.033, &[
+			Event::Down(MouseEvent::new(Vec2i::new(10, 20), MouseButton::Right)),
+			Event::Wheel(WheelEvent::new(Vec3::new(0.0, -2.5, 0.0), WheelDeltaMode::Line)),
+			Event::TouchStart(Touch::new(4, Vec2i::new(1, 2))),
+			Event::GamepadUpdate(pad),
+			Event::GamepadDisconnected(1),
+			Event::KeyUp(key(KeyCode::Enter, Key::Named("Enter".to_owned()))),
+			Event::PointerLockChanged(true),
+			Event::Hidden,
+			Event::BeforeUnload,
+		]);
+		rec
+	}
+
+	fn same(a: &Recording, b: &Recording) -> bool {
+		format!("{:?}", a) == format!("{:?}", b)
+	}
+
+	#[test]
+	fn bytes_round_trip() {
+		let rec = sample();
+		let loaded = Recording::from_bytes(&rec.to_bytes()).unwrap();
+		assert!(same(&rec, &loaded));
+		assert_eq!(loaded.duration(), rec.duration());
+	}
+
+	#[test]
+	fn truncated_input_is_an_error() {
+		let bytes = sample().to_bytes();
+		for len in 0..bytes.len() {
+			assert!(Recording::from_bytes(&bytes[..len]).is_err(), "accepted {} of {} bytes", len, bytes.len());
+		}
+	}
+
+	#[test]
+	fn corrupt_input_is_an_error() {
+		let bytes = sample().to_bytes();
+
+		let mut bad_magic = bytes.clone();
+		bad_magic[0] = b'X';
+		assert!(Recording::from_bytes(&bad_magic).is_err());
+
+		let mut bad_version = bytes.clone();
+		bad_version[4] = 99;
+		assert!(Recording::from_bytes(&bad_version).is_err());
+
+		// First event tag, after the header, frame time and event count
+		let mut bad_tag = bytes.clone();
+		bad_tag[12 + 8 + 4] = 200;
+		assert!(Recording::from_bytes(&bad_tag).is_err());
+
+		// Huge counts run out of input rather than allocating
+		let mut huge = bytes.clone();
+		huge[8..12].copy_from_slice(&[0xff; 4]);
+		assert!(Recording::from_bytes(&huge).is_err());
+	}
+
+	#[test]
+	fn garbage_never_panics() {
+		let bytes = sample().to_bytes();
+		let mut seed = 0x2545_f491u32;
+		for _ in 0..2000 {
+			let mut b = bytes.clone();
+			for _ in 0..4 {
+				seed ^= seed << 13;
+				seed ^= seed >> 17;
+				seed ^= seed << 5;
+				let i = 12 + seed as usize % (b.len() - 12);
+				b[i] = (seed >> 24) as u8;
+			}
+			let _ = Recording::from_bytes(&b);
+		}
+	}
+
+	// Moves while W is held, one unit per fixed update, and quits on Escape
+	struct Walker {
+		position: f32,
+		frames: Vec<(u64, f32)>,
+	}
+
+	impl App for Walker {
+		fn fixed_update(&mut self, state: &mut LoopState, _dt: f32) {
+			if state.input.key_held(KeyCode::KeyW) {
+				self.position += 1.0;
+			}
+		}
+
+		fn render(&mut self, state: &mut LoopState, _alpha: f32) {
+			if state.input.key_pressed(KeyCode::Escape) {
+				state.quit();
+			}
+			self.frames.push((state.stats.fixed_step_count, self.position));
+		}
+	}
+
+	fn walker() -> Walker {
+		Walker { position: 0.0, frames: Vec::new() }
+	}
+
+	#[test]
+	fn headless_replay_is_deterministic() {
+		let config = LoopConfig::default();
+		let frame_times = [0.016, 0.017, 0.05, 0.0, 0.4, 0.016, 0.02];
+
+		let mut live = Runner::headless(walker(), config);
+		live.start_recording();
+		for (i, &t) in frame_times.iter().enumerate() {
+			match i {
+				1 => live.events.push(Event::KeyDown(KeyEvent::new(KeyCode::KeyW, Key::Char('w')))),
+				4 => live.events.push(Event::KeyUp(KeyEvent::new(KeyCode::KeyW, Key::Char('w')))),
+				6 => live.events.push(Event::KeyDown(KeyEvent::new(KeyCode::Escape, Key::Named("Escape".to_owned())))),
+				_ => {}
+			}
+			live.step_dt(t);
+		}
+		assert!(live.is_finished());
+
+		let rec = Recording::from_bytes(&live.stop_recording().unwrap().to_bytes()).unwrap();
+		assert_eq!(rec.frames.len(), frame_times.len());
+
+		let replayed = rec.replay(walker(), config);
+		assert!(replayed.is_finished());
+		assert!(replayed.app.position > 0.0);
+		assert_eq!(replayed.app.frames, live.app.frames);
+
+		let again = rec.replay(walker(), config);
+		assert_eq!(again.app.frames, replayed.app.frames);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,30 @@
+use bindings::emscripten::*;
+use math::vec::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Touch {
+	// Stable for as long as the finger stays down
+	pub id: i32,
+
+	// Relative to the top-left corner of the canvas
+	pub pos: Vec2i,
+}
+
+impl Touch {
+	pub fn new(id: i32, pos: Vec2i) -> Touch {
+		Touch { id, pos }
+	}
+
+	pub fn from_ems(t: &EmscriptenTouchPoint) -> Touch {
+		Touch::new(t.identifier, Vec2i::new(t.canvasX, t.canvasY))
+	}
+
+	// Touches that changed in this event, i.e. DOM `changedTouches`
+	pub fn changed(e: &EmscriptenTouchEvent) -> Vec<Touch> {
+		let count = (e.numTouches.max(0) as usize).min(e.touches.len());
+		e.touches[..count].iter()
+			.filter(|t| t.isChanged != 0)
+			.map(Touch::from_ems)
+			.collect()
+	}
+}
\ No newline at end of file
@@ -0,0 +1,407 @@
+use std::ops::{ Add, Sub, Mul };
+
+use math::vec::*;
+
+// RGBA with components nominally in [0, 1]. Nothing tracks which color space a value is in;
+// colors written by hand or parsed from hex are sRGB, while lighting and blending want linear
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Color { pub r: f32, pub g: f32, pub b: f32, pub a: f32 }
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+	if c <= 0.04045 {
+		c / 12.92
+	} else {
+		((c + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+	if c <= 0.0031308 {
+		c * 12.92
+	} else {
+		1.055 * c.powf(1.0 / 2.4) - 0.055
+	}
+}
+
+impl Color {
+	pub const TRANSPARENT: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+	pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+	pub const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+	pub const RED: Color = Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+	pub const GREEN: Color = Color { r: 0.0, g: 1.0, b: 0.0, a: 1.0 };
+	pub const BLUE: Color = Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 };
+
+	pub fn new(r: f32, g: f32, b: f32, a: f32) -> Color { Color { r, g, b, a } }
+	pub fn rgb(r: f32, g: f32, b: f32) -> Color { Color::new(r, g, b, 1.0) }
+	pub fn gray(v: f32) -> Color { Color::rgb(v, v, v) }
+
+	pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+		Color::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0)
+	}
+
+	pub fn to_rgba8(&self) -> [u8; 4] {
+		let q = |c: f32| (c.max(0.0).min(1.0) * 255.0 + 0.5) as u8;
+		[q(self.r), q(self.g), q(self.b), q(self.a)]
+	}
+
+	// 0xRRGGBBAA
+	pub fn from_u32(v: u32) -> Color {
+		Color::from_rgba8((v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8)
+	}
+
+	pub fn to_u32(&self) -> u32 {
+		let [r, g, b, a] = self.to_rgba8();
+		(r as u32) << 24 | (g as u32) << 16 | (b as u32) << 8 | a as u32
+	}
+
+	// Bytes in r, g, b, a order in memory, for a normalized gl::UNSIGNED_BYTE vertex attribute
+	pub fn to_packed(&self) -> u32 {
+		let [r, g, b, a] = self.to_rgba8();
+		(a as u32) << 24 | (b as u32) << 16 | (g as u32) << 8 | r as u32
+	}
+
+	// Accepts "#rgb", "#rgba", "#rrggbb" and "#rrggbbaa", with or without the '#'
+	pub fn from_hex(s: &str) -> Result<Color, String> {
+		let hex = if s.starts_with('#') { &s[1..] } else { s };
+		let digits: Option<Vec<u8>> = hex.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect();
+		let d = digits.ok_or_else(|| format!("invalid hex color '{}'", s))?;
+
+		match d.len() {
+			3 => Ok(Color::from_rgba8(d[0] * 17, d[1] * 17, d[2] * 17, 255)),
+			4 => Ok(Color::from_rgba8(d[0] * 17, d[1] * 17, d[2] * 17, d[3] * 17)),
+			6 => Ok(Color::from_rgba8(d[0] << 4 | d[1], d[2] << 4 | d[3], d[4] << 4 | d[5], 255)),
+			8 => Ok(Color::from_rgba8(d[0] << 4 | d[1], d[2] << 4 | d[3], d[4] << 4 | d[5], d[6] << 4 | d[7])),
+			_ => Err(format!("invalid hex color '{}'", s))
+		}
+	}
+
+	// "#rrggbbaa"
+	pub fn to_hex(&self) -> String {
+		format!("#{:08x}", self.to_u32())
+	}
+
+	pub fn to_linear(&self) -> Color {
+		Color::new(srgb_to_linear(self.r), srgb_to_linear(self.g), srgb_to_linear(self.b), self.a)
+	}
+
+	pub fn to_srgb(&self) -> Color {
+		Color::new(linear_to_srgb(self.r), linear_to_srgb(self.g), linear_to_srgb(self.b), self.a)
+	}
+
+	// Hue in degrees [0, 360), saturation and value in [0, 1]
+	pub fn from_hsv(h: f32, s: f32, v: f32) -> Color {
+		let c = v * s;
+		Color::from_hue_chroma(h, c, v - c)
+	}
+
+	pub fn to_hsv(&self) -> (f32, f32, f32) {
+		let (h, max, min) = self.hue();
+		let s = if max > 0.0 { (max - min) / max } else { 0.0 };
+		(h, s, max)
+	}
+
+	// Hue in degrees [0, 360), saturation and lightness in [0, 1]
+	pub fn from_hsl(h: f32, s: f32, l: f32) -> Color {
+		let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+		Color::from_hue_chroma(h, c, l - c * 0.5)
+	}
+
+	pub fn to_hsl(&self) -> (f32, f32, f32) {
+		let (h, max, min) = self.hue();
+		let l = (max + min) * 0.5;
+		let s = if max > min { (max - min) / (1.0 - (2.0 * l - 1.0).abs()) } else { 0.0 };
+		(h, s, l)
+	}
+
+	fn from_hue_chroma(h: f32, c: f32, m: f32) -> Color {
+		let h = (h % 360.0 + 360.0) % 360.0 / 60.0;
+		let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+
+		let (r, g, b) = match h as u32 {
+			0 => (c, x, 0.0),
+			1 => (x, c, 0.0),
+			2 => (0.0, c, x),
+			3 => (0.0, x, c),
+			4 => (x, 0.0, c),
+			_ => (c, 0.0, x),
+		};
+
+		Color::rgb(r + m, g + m, b + m)
+	}
+
+	// Hue in degrees with the largest and smallest components
+	fn hue(&self) -> (f32, f32, f32) {
+		let max = self.r.max(self.g).max(self.b);
+		let min = self.r.min(self.g).min(self.b);
+		let d = max - min;
+
+		let h = if d == 0.0 {
+			0.0
+		} else if max == self.r {
+			60.0 * (((self.g - self.b) / d) % 6.0)
+		} else if max == self.g {
+			60.0 * ((self.b - self.r) / d + 2.0)
+		} else {
+			60.0 * ((self.r - self.g) / d + 4.0)
+		};
+
+		(if h < 0.0 { h + 360.0 } else { h }, max, min)
+	}
+
+	pub fn with_alpha(&self, a: f32) -> Color {
+		Color::new(self.r, self.g, self.b, a)
+	}
+
+	pub fn premultiplied(&self) -> Color {
+		Color::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+	}
+
+	pub fn unpremultiplied(&self) -> Color {
+		if self.a <= 0.0 { return Color::TRANSPARENT }
+		Color::new(self.r / self.a, self.g / self.a, self.b / self.a, self.a)
+	}
+
+	pub fn clamped(&self) -> Color {
+		let c = |v: f32| v.max(0.0).min(1.0);
+		Color::new(c(self.r), c(self.g), c(self.b), c(self.a))
+	}
+
+	pub fn lerp(&self, o: Color, t: f32) -> Color {
+		*self + (o - *self) * t
+	}
+
+	// Porter-Duff source-over of `self` onto `dst`, both straight (not premultiplied) alpha
+	pub fn over(&self, dst: Color) -> Color {
+		let a = self.a + dst.a * (1.0 - self.a);
+		if a <= 0.0 { return Color::TRANSPARENT }
+
+		let blend = |s: f32, d: f32| (s * self.a + d * dst.a * (1.0 - self.a)) / a;
+		Color::new(blend(self.r, dst.r), blend(self.g, dst.g), blend(self.b, dst.b), a)
+	}
+
+	pub fn multiply(&self, o: Color) -> Color {
+		Color::new(self.r * o.r, self.g * o.g, self.b * o.b, self.a * o.a)
+	}
+
+	pub fn screen(&self, o: Color) -> Color {
+		let s = |a: f32, b: f32| 1.0 - (1.0 - a) * (1.0 - b);
+		Color::new(s(self.r, o.r), s(self.g, o.g), s(self.b, o.b), self.a)
+	}
+
+	pub fn to_vec4(&self) -> Vec4 { Vec4::new(self.r, self.g, self.b, self.a) }
+	pub fn to_vec3(&self) -> Vec3 { Vec3::new(self.r, self.g, self.b) }
+	pub fn to_array(&self) -> [f32; 4] { [self.r, self.g, self.b, self.a] }
+}
+
+impl From<Vec4> for Color {
+	fn from(v: Vec4) -> Color { Color::new(v.x, v.y, v.z, v.w) }
+}
+
+impl From<Vec3> for Color {
+	fn from(v: Vec3) -> Color { Color::rgb(v.x, v.y, v.z) }
+}
+
+impl From<(f32, f32, f32, f32)> for Color {
+	fn from(c: (f32, f32, f32, f32)) -> Color { Color::new(c.0, c.1, c.2, c.3) }
+}
+
+impl Add for Color {
+	type Output = Color;
+	fn add(self, o: Color) -> Color {
+		Color::new(self.r + o.r, self.g + o.g, self.b + o.b, self.a + o.a)
+	}
+}
+
+impl Sub for Color {
+	type Output = Color;
+	fn sub(self, o: Color) -> Color {
+		Color::new(self.r - o.r, self.g - o.g, self.b - o.b, self.a - o.a)
+	}
+}
+
+impl Mul<f32> for Color {
+	type Output = Color;
+	fn mul(self, o: f32) -> Color {
+		Color::new(self.r * o, self.g * o, self.b * o, self.a * o)
+	}
+}
+
+impl Mul<Color> for Color {
+	type Output = Color;
+	fn mul(self, o: Color) -> Color {
+		self.multiply(o)
+	}
+}
+
+// Color stops over [0, 1]. Interpolates in whatever space the stops are in,
+// so convert them to linear first for perceptually even blends
+#[derive(Clone, Debug)]
+pub struct Gradient {
+	stops: Vec<(f32, Color)>,
+}
+
+impl Gradient {
+	pub fn new() -> Gradient {
+		Gradient { stops: Vec::new() }
+	}
+
+	pub fn two(from: Color, to: Color) -> Gradient {
+		let mut g = Gradient::new();
+		g.add_stop(0.0, from);
+		g.add_stop(1.0, to);
+		g
+	}
+
+	pub fn add_stop(&mut self, t: f32, color: Color) {
+		let i = self.stops.iter().position(|s| s.0 > t).unwrap_or(self.stops.len());
+		self.stops.insert(i, (t, color));
+	}
+
+	pub fn stops(&self) -> &[(f32, Color)] { &self.stops }
+
+	// Clamped to the first and last stops. Transparent if there are none
+	pub fn eval(&self, t: f32) -> Color {
+		let first = match self.stops.first() {
+			Some(s) => s,
+			None => return Color::TRANSPARENT
+		};
+
+		if t <= first.0 { return first.1 }
+
+		for w in self.stops.windows(2) {
+			let (a, b) = (w[0], w[1]);
+			if t <= b.0 {
+				let f = if b.0 > a.0 { (t - a.0) / (b.0 - a.0) } else { 1.0 };
+				return a.1.lerp(b.1, f)
+			}
+		}
+
+		self.stops[self.stops.len() - 1].1
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn close(a: Color, b: Color) -> bool {
+		a.to_vec4().approx_eq(b.to_vec4(), 1e-4)
+	}
+
+	#[test]
+	fn from_hex() {
+		assert_eq!(Color::from_hex("#f80").unwrap().to_rgba8(), [0xff, 0x88, 0x00, 0xff]);
+		assert_eq!(Color::from_hex("f80c").unwrap().to_rgba8(), [0xff, 0x88, 0x00, 0xcc]);
+		assert_eq!(Color::from_hex("#12abEF").unwrap().to_rgba8(), [0x12, 0xab, 0xef, 0xff]);
+		assert_eq!(Color::from_hex("12abef80").unwrap().to_rgba8(), [0x12, 0xab, 0xef, 0x80]);
+
+		for bad in ["", "#", "##fff", "#ff", "#fffff", "#ggg", "#12 456", "#\u{e9}ab"].iter() {
+			assert!(Color::from_hex(bad).is_err(), "{:?}", bad);
+		}
+	}
+
+	#[test]
+	fn u32_and_hex_round_trip() {
+		for &v in [0x00000000u32, 0xffffffff, 0x12345678, 0xff800040].iter() {
+			let c = Color::from_u32(v);
+			assert_eq!(c.to_u32(), v);
+			assert_eq!(Color::from_hex(&c.to_hex()).unwrap().to_u32(), v);
+		}
+
+		assert_eq!(Color::RED.to_hex(), "#ff0000ff");
+		assert_eq!(Color::new(2.0, -1.0, 0.5, 1.0).to_rgba8(), [255, 0, 128, 255]);
+	}
+
+	#[test]
+	fn packed_byte_order() {
+		let p = Color::from_rgba8(1, 2, 3, 4).to_packed();
+		assert_eq!(p, 0x04030201);
+
+		// Red first in memory on the little endian targets GL runs on
+		let bytes: [u8; 4] = unsafe { ::std::mem::transmute(p.to_le()) };
+		assert_eq!(bytes, [1, 2, 3, 4]);
+	}
+
+	#[test]
+	fn srgb_round_trip() {
+		for i in 0..101 {
+			let v = i as f32 / 100.0;
+			assert!((linear_to_srgb(srgb_to_linear(v)) - v).abs() < 1e-5, "{}", v);
+		}
+
+		assert!((srgb_to_linear(0.5) - 0.21404).abs() < 1e-4);
+		let c = Color::new(0.2, 0.5, 0.9, 0.3);
+		assert!(close(c.to_linear().to_srgb(), c));
+		assert_eq!(c.to_linear().a, 0.3);
+	}
+
+	#[test]
+	fn hsv_and_hsl() {
+		let cases = [
+			(Color::RED, 0.0), (Color::rgb(1.0, 1.0, 0.0), 60.0), (Color::GREEN, 120.0),
+			(Color::rgb(0.0, 1.0, 1.0), 180.0), (Color::BLUE, 240.0), (Color::rgb(1.0, 0.0, 1.0), 300.0),
+		];
+
+		for &(c, hue) in cases.iter() {
+			assert_eq!(c.to_hsv(), (hue, 1.0, 1.0));
+			assert_eq!(c.to_hsl(), (hue, 1.0, 0.5));
+			assert!(close(Color::from_hsv(hue, 1.0, 1.0), c));
+			assert!(close(Color::from_hsl(hue, 1.0, 0.5), c));
+		}
+
+		// Grays have no hue or saturation
+		for &v in [0.0, 0.25, 1.0].iter() {
+			let g = Color::gray(v);
+			assert_eq!(g.to_hsv(), (0.0, 0.0, v));
+			assert_eq!(g.to_hsl(), (0.0, 0.0, v));
+			assert!(close(Color::from_hsv(123.0, 0.0, v), g));
+			assert!(close(Color::from_hsl(123.0, 0.0, v), g));
+		}
+
+		let c = Color::rgb(0.2, 0.6, 0.4);
+		let (h, s, v) = c.to_hsv();
+		assert!(close(Color::from_hsv(h, s, v), c));
+		let (h, s, l) = c.to_hsl();
+		assert!(close(Color::from_hsl(h, s, l), c));
+		assert!(close(Color::from_hsv(h - 360.0, 1.0, 1.0), Color::from_hsv(h, 1.0, 1.0)));
+	}
+
+	#[test]
+	fn over() {
+		assert_eq!(Color::RED.over(Color::BLUE), Color::RED);
+		assert_eq!(Color::TRANSPARENT.over(Color::BLUE), Color::BLUE);
+		assert_eq!(Color::TRANSPARENT.over(Color::TRANSPARENT), Color::TRANSPARENT);
+
+		let half = Color::RED.with_alpha(0.5);
+		assert!(close(half.over(Color::BLUE), Color::rgb(0.5, 0.0, 0.5)));
+		assert!(close(half.over(Color::BLUE.with_alpha(0.5)), Color::new(2.0 / 3.0, 0.0, 1.0 / 3.0, 0.75)));
+	}
+
+	#[test]
+	fn gradient() {
+		let mut g = Gradient::new();
+		assert_eq!(g.eval(0.5), Color::TRANSPARENT);
+
+		g.add_stop(1.0, Color::BLUE);
+		g.add_stop(0.0, Color::RED);
+		g.add_stop(0.5, Color::WHITE);
+		assert_eq!(g.stops().iter().map(|s| s.0).collect::<Vec<_>>(), vec![0.0, 0.5, 1.0]);
+
+		assert_eq!(g.eval(-1.0), Color::RED);
+		assert_eq!(g.eval(0.0), Color::RED);
+		assert_eq!(g.eval(0.5), Color::WHITE);
+		assert_eq!(g.eval(1.0), Color::BLUE);
+		assert_eq!(g.eval(2.0), Color::BLUE);
+		assert!(close(g.eval(0.25), Color::rgb(1.0, 0.5, 0.5)));
+		assert!(close(g.eval(0.75), Color::rgb(0.5, 0.5, 1.0)));
+
+		// A hard edge where two stops share a position
+		let mut g = Gradient::two(Color::BLACK, Color::WHITE);
+		g.add_stop(0.5, Color::RED);
+		g.add_stop(0.5, Color::GREEN);
+		assert!(close(g.eval(0.49), Color::RED.lerp(Color::BLACK, 0.02)));
+		assert!(close(g.eval(0.51), Color::GREEN.lerp(Color::WHITE, 0.02)));
+	}
+}
\ No newline at end of file
@@ -1,5 +1,4 @@
 use std::mem::size_of;
-use std::collections::HashMap;
 use std::convert::Into;
 use std::ptr::null;
 
@@ -11,34 +10,54 @@ use shader::*;
 
 struct VertexAttrib {
 	pub size: u32,
-	pub normalized: bool
+	pub normalized: bool,
+	pub ty: u32,
+	pub bytes: u32
 }
 
+// Attributes in the order they're laid out in the vertex
 pub struct VertexFormat {
-	attrs: HashMap<String, VertexAttrib>
+	attrs: Vec<(String, VertexAttrib)>
 }
 
 impl VertexFormat {
 	pub fn new() -> VertexFormat {
 		VertexFormat {
-			attrs: HashMap::new()
+			attrs: Vec::new()
 		}
 	}
 
 	pub fn add_attrib(&mut self, name: &str, size: u32, norm: bool) {
-		self.attrs.insert(
+		self.attrs.retain(|a| a.0 != name);
+		self.attrs.push((
 			name.to_owned(),
 			VertexAttrib {
 				size,
-				normalized: norm
+				normalized: norm,
+				ty: gl::FLOAT,
+				bytes: 4 * size
 			}
-		);
+		));
+	}
+
+	// Four normalized bytes in one u32, as produced by Color::to_packed
+	pub fn add_packed_color_attrib(&mut self, name: &str) {
+		self.attrs.retain(|a| a.0 != name);
+		self.attrs.push((
+			name.to_owned(),
+			VertexAttrib {
+				size: 4,
+				normalized: true,
+				ty: gl::UNSIGNED_BYTE,
+				bytes: 4
+			}
+		));
 	}
 
 	pub fn size(&self) -> i32 {
 		let mut offset = 0;
-		for (_, v) in self.attrs.iter() {
-			offset += 4 * v.size;
+		for &(_, ref v) in self.attrs.iter() {
+			offset += v.bytes;
 		}
 		offset as i32
 	}
@@ -46,7 +65,7 @@ impl VertexFormat {
 	pub fn bind_attribs(&self, shader: &mut Shader) {
 		let stride = self.size();
 		let mut offset = 0u32;
-		for (k, v) in self.attrs.iter() {
+		for &(ref k, ref v) in self.attrs.iter() {
 			let loc = shader.get_attrib_location(&k);
 			if loc != -1 {
 				unsafe {
@@ -54,19 +73,19 @@ impl VertexFormat {
 					gl::VertexAttribPointer(
 						loc as u32,
 						v.size as i32,
-						gl::FLOAT,
+						v.ty,
 						if v.normalized { gl::TRUE } else { gl::FALSE },
 						stride,
 						offset as *const _
 					);
 				}
 			}
-			offset += 4 * v.size;
+			offset += v.bytes;
 		}
 	}
 
 	pub fn unbind_attribs(&self, shader: &mut Shader) {
-		for (k, _) in self.attrs.iter() {
+		for &(ref k, _) in self.attrs.iter() {
 			let loc = shader.get_attrib_location(&k);
 			if loc != -1 {
 				unsafe {
@@ -123,6 +142,10 @@ impl<V> Mesh<V> where V: Vertex {
 	pub fn index_count(&self) -> usize { self.indices.len() }
 
 	pub fn flush(&mut self) {
+		if self.format.attrs.is_empty() && !self.vertices.is_empty() {
+			self.format = self.vertices[0].get_format();
+		}
+
 		unsafe {
 			let vsize = self.format.size() as u32 * self.vertices.len() as u32;
 
@@ -137,7 +160,7 @@ impl<V> Mesh<V> where V: Vertex {
 			if self.indexed {
 				let esize = size_of::<u16>() as u32 * self.indices.len() as u32;
 
-				gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+				gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo);
 				if esize > self.ibo_size {
 					gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, esize as _, self.indices.as_ptr() as _, gl::DYNAMIC_DRAW);
 					self.ibo_size = esize;
@@ -162,6 +185,21 @@ impl<V> Mesh<V> where V: Vertex {
 		self.indices.push(i2);
 	}
 
+	// Indexed meshes get a pair of indices per segment so several strips can share one mesh,
+	// drawn with gl::LINES. Otherwise draw with gl::LINE_STRIP, one strip per mesh
+	pub fn add_line_strip<I: IntoIterator<Item=V>>(&mut self, vertices: I) {
+		let start = self.vertices.len() as u16;
+		self.vertices.extend(vertices);
+
+		if self.indexed {
+			let end = self.vertices.len() as u16;
+			for i in start + 1..end {
+				self.indices.push(i - 1);
+				self.indices.push(i);
+			}
+		}
+	}
+
 	pub fn render(&self, mode: u32, shader: &mut Shader) {
 		unsafe {
 			if self.indexed {
@@ -188,3 +226,14 @@ impl<V> Mesh<V> where V: Vertex {
 		}
 	}
 }
+
+// Lets meshes with different vertex types share one draw list
+pub trait Drawable {
+	fn draw(&self, mode: u32, shader: &mut Shader);
+}
+
+impl<V: Vertex> Drawable for Mesh<V> {
+	fn draw(&self, mode: u32, shader: &mut Shader) {
+		self.render(mode, shader);
+	}
+}
\ No newline at end of file
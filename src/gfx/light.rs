@@ -0,0 +1,64 @@
+use math::vec::*;
+use math::geom::*;
+use color::*;
+use shadow::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+	// `direction` is the way the light travels, e.g. downwards for a noon sun
+	Directional { direction: Vec3 },
+
+	// Fades to nothing at `range`
+	Point { position: Vec3, range: f32 },
+
+	// Full strength inside `inner_angle` from the axis, fading out by `outer_angle`. Angles in radians
+	Spot { position: Vec3, direction: Vec3, range: f32, inner_angle: f32, outer_angle: f32 },
+}
+
+// `color` is sRGB, as with materials; the renderer linearises it and scales it by `intensity`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+	pub kind: LightKind,
+	pub color: Color,
+	pub intensity: f32,
+
+	// Directional and spot lights only
+	pub shadow: Option<ShadowSettings>,
+}
+
+impl Light {
+	pub fn directional(direction: Vec3, color: Color, intensity: f32) -> Light {
+		Light { kind: LightKind::Directional { direction: direction.normalized() }, color, intensity, shadow: None }
+	}
+
+	pub fn point(position: Vec3, range: f32, color: Color, intensity: f32) -> Light {
+		Light { kind: LightKind::Point { position, range }, color, intensity, shadow: None }
+	}
+
+	pub fn spot(position: Vec3, direction: Vec3, range: f32, inner_angle: f32, outer_angle: f32, color: Color, intensity: f32) -> Light {
+		Light {
+			kind: LightKind::Spot { position, direction: direction.normalized(), range, inner_angle, outer_angle },
+			color,
+			intensity,
+			shadow: None
+		}
+	}
+
+	pub fn with_shadow(mut self, settings: ShadowSettings) -> Light {
+		self.shadow = Some(settings);
+		self
+	}
+
+	// Linear color premultiplied by intensity, as the shaders want it
+	pub fn radiance(&self) -> Vec3 {
+		self.color.to_linear().to_vec3() * self.intensity
+	}
+
+	// The volume the light reaches. None for directional lights, which reach everywhere
+	pub fn bounds(&self) -> Option<Sphere> {
+		match self.kind {
+			LightKind::Directional { .. } => None,
+			LightKind::Point { position, range } | LightKind::Spot { position, range, .. } => Some(Sphere::new(position, range)),
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,170 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+use bindings::gl;
+use math::vec::*;
+use math::mat::*;
+use shader::*;
+use shaders::*;
+use color::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+	Opaque,
+	// Straight alpha
+	Alpha,
+	Premultiplied,
+	Additive,
+}
+
+impl BlendMode {
+	pub fn is_transparent(self) -> bool { self != BlendMode::Opaque }
+
+	pub fn apply(self) {
+		unsafe {
+			match self {
+				BlendMode::Opaque => gl::Disable(gl::BLEND),
+				BlendMode::Alpha => {
+					gl::Enable(gl::BLEND);
+					gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+				}
+				BlendMode::Premultiplied => {
+					gl::Enable(gl::BLEND);
+					gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+				}
+				BlendMode::Additive => {
+					gl::Enable(gl::BLEND);
+					gl::BlendFunc(gl::SRC_ALPHA, gl::ONE);
+				}
+			}
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum MaterialValue {
+	Int(i32),
+	Float(f32),
+	Vec2(Vec2),
+	Vec3(Vec3),
+	Vec4(Vec4),
+	Color(Color),
+	Mat3(Mat3),
+	Mat4(Mat4),
+}
+
+macro_rules! impl_material_value_from {
+	($($ty:ident => $variant:ident),+) => {
+		$(
+			impl From<$ty> for MaterialValue {
+				fn from(v: $ty) -> MaterialValue { MaterialValue::$variant(v) }
+			}
+		)+
+	}
+}
+
+impl_material_value_from!(i32 => Int, f32 => Float, Vec2 => Vec2, Vec3 => Vec3, Vec4 => Vec4, Color => Color, Mat3 => Mat3, Mat4 => Mat4);
+
+impl MaterialValue {
+	pub fn apply(&self, u: &Uniform) {
+		match *self {
+			MaterialValue::Int(v) => u.set(v),
+			MaterialValue::Float(v) => u.set(v),
+			MaterialValue::Vec2(v) => u.set(v),
+			MaterialValue::Vec3(v) => u.set(v),
+			MaterialValue::Vec4(v) => u.set(v),
+			MaterialValue::Color(v) => u.set(v),
+			MaterialValue::Mat3(v) => u.set(v),
+			MaterialValue::Mat4(v) => u.set(v),
+		}
+	}
+}
+
+static NEXT_MATERIAL_ID: AtomicUsize = AtomicUsize::new(0);
+
+// A shader with the uniform values and render state to draw with it
+pub struct Material {
+	id: u32,
+	pub shader: Rc<RefCell<Shader>>,
+	pub blend: BlendMode,
+	pub double_sided: bool,
+	params: Vec<(String, MaterialValue)>,
+}
+
+impl Material {
+	pub fn new(shader: Rc<RefCell<Shader>>) -> Material {
+		Material {
+			id: NEXT_MATERIAL_ID.fetch_add(1, Ordering::Relaxed) as u32,
+			shader,
+			blend: BlendMode::Opaque,
+			double_sided: false,
+			params: Vec::new(),
+		}
+	}
+
+	// Colors are sRGB; alpha below 1 switches to alpha blending
+	pub fn blinn_phong(lib: &ShaderLibrary, diffuse: Color, specular: Color, shininess: f32) -> Material {
+		let mut m = Material::new(lib.blinn_phong.clone());
+		m.set("u_diffuse", diffuse.to_linear());
+		m.set("u_specular", specular.to_linear().to_vec3());
+		m.set("u_shininess", shininess);
+		m.set("u_emissive", Vec3::zero());
+		if diffuse.a < 1.0 {
+			m.blend = BlendMode::Alpha;
+		}
+		m
+	}
+
+	// `base_color` is sRGB; alpha below 1 switches to alpha blending. Metallic and roughness in [0, 1]
+	pub fn pbr(lib: &ShaderLibrary, base_color: Color, metallic: f32, roughness: f32) -> Material {
+		let mut m = Material::new(lib.pbr.clone());
+		m.set("u_base_color", base_color.to_linear());
+		m.set("u_metallic", metallic);
+		m.set("u_roughness", roughness);
+		m.set("u_emissive", Vec3::zero());
+		if base_color.a < 1.0 {
+			m.blend = BlendMode::Alpha;
+		}
+		m
+	}
+
+	// Unique per material, for sorting draws by state
+	pub fn id(&self) -> u32 { self.id }
+
+	pub fn set<V: Into<MaterialValue>>(&mut self, name: &str, value: V) {
+		let value = value.into();
+		match self.params.iter().position(|p| p.0 == name) {
+			Some(i) => self.params[i].1 = value,
+			None => self.params.push((name.to_owned(), value)),
+		}
+	}
+
+	pub fn get(&self, name: &str) -> Option<MaterialValue> {
+		self.params.iter().find(|p| p.0 == name).map(|p| p.1)
+	}
+
+	pub fn is_transparent(&self) -> bool { self.blend.is_transparent() }
+
+	// Uploads the parameters to the shader, which must be bound
+	pub fn apply_params(&self, shader: &mut Shader) {
+		for &(ref name, ref value) in self.params.iter() {
+			if let Some(u) = shader.get(name) {
+				value.apply(&u);
+			}
+		}
+	}
+
+	// Blending and face culling
+	pub fn apply_state(&self) {
+		self.blend.apply();
+		unsafe {
+			if self.double_sided {
+				gl::Disable(gl::CULL_FACE);
+			} else {
+				gl::Enable(gl::CULL_FACE);
+			}
+			gl::DepthMask(if self.is_transparent() { gl::FALSE } else { gl::TRUE });
+		}
+	}
+}
\ No newline at end of file
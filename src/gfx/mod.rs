@@ -1,3 +1,9 @@
 #[macro_use]
 pub mod shader;
-pub mod geom;
\ No newline at end of file
+pub mod geom;
+pub mod color;
+pub mod shaders;
+pub mod material;
+pub mod light;
+pub mod shadow;
+pub mod renderer;
\ No newline at end of file
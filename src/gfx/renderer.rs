@@ -0,0 +1,318 @@
+use std::cmp::Ordering;
+
+use bindings::gl;
+use math::vec::*;
+use math::mat::*;
+use math::geom::*;
+use scene::camera::*;
+use gfx::geom::*;
+use shader::*;
+use material::*;
+use light::*;
+use shaders::*;
+use shadow::*;
+use color::*;
+
+pub struct DrawItem<'a> {
+	pub mesh: &'a dyn Drawable,
+	pub material: &'a Material,
+	pub transform: Mat4,
+
+	// Local space bounds to cull against. Items without are always drawn
+	pub bounds: Option<Aabb>,
+
+	// GL primitive, gl::TRIANGLES unless set
+	pub mode: u32,
+}
+
+impl<'a> DrawItem<'a> {
+	pub fn new(mesh: &'a dyn Drawable, material: &'a Material, transform: Mat4) -> DrawItem<'a> {
+		DrawItem { mesh, material, transform, bounds: None, mode: gl::TRIANGLES }
+	}
+
+	pub fn with_bounds(mut self, bounds: Aabb) -> DrawItem<'a> {
+		self.bounds = Some(bounds);
+		self
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RenderStats {
+	pub submitted: u32,
+	pub culled: u32,
+	pub drawn: u32,
+	pub shader_changes: u32,
+	pub material_changes: u32,
+	pub lights: u32,
+}
+
+// Draws lit items for one camera. Opaque items go first, grouped by shader and material and then
+// front to back, and transparent ones after them back to front
+pub struct Renderer {
+	pub lights: Vec<Light>,
+	// sRGB
+	pub ambient: Color,
+	// Shadow maps for lights with shadow settings; without them no light casts shadows
+	pub shadows: Option<Shadows>,
+
+	stats: RenderStats,
+	queue: Vec<(u64, usize)>,
+	visible_lights: Vec<Light>,
+	light_names: Vec<LightUniformNames>,
+}
+
+// The per-light uniform names, built once instead of formatted for every shader every frame
+struct LightUniformNames {
+	position: String,
+	direction: String,
+	color: String,
+	params: String,
+	shadow: String,
+}
+
+impl LightUniformNames {
+	fn new(i: usize) -> LightUniformNames {
+		LightUniformNames {
+			position: format!("u_light_position[{}]", i),
+			direction: format!("u_light_direction[{}]", i),
+			color: format!("u_light_color[{}]", i),
+			params: format!("u_light_params[{}]", i),
+			shadow: format!("u_light_shadow[{}]", i),
+		}
+	}
+}
+
+// Non-negative floats order the same as their bits
+fn depth_bits(depth: f32) -> u64 {
+	depth.max(0.0).to_bits() as u64
+}
+
+impl Renderer {
+	pub fn new() -> Renderer {
+		Renderer {
+			lights: Vec::new(),
+			ambient: Color::gray(0.1),
+			shadows: None,
+			stats: RenderStats::default(),
+			queue: Vec::new(),
+			visible_lights: Vec::new(),
+			light_names: (0..MAX_LIGHTS).map(LightUniformNames::new).collect(),
+		}
+	}
+
+	pub fn stats(&self) -> RenderStats { self.stats }
+
+	//	opaque:      0 | shader:15 | material:16 | depth:32
+	//	transparent: 1 | far to near depth:32 | shader:15 | material:16
+	fn sort_key(shader: u32, material: u32, transparent: bool, depth: f32) -> u64 {
+		let shader = shader as u64 & 0x7fff;
+		let material = material as u64 & 0xffff;
+		let depth = depth_bits(depth);
+
+		if transparent {
+			1 << 63 | (0xffff_ffff - depth) << 31 | shader << 16 | material
+		} else {
+			shader << 48 | material << 32 | depth
+		}
+	}
+
+	// Items whose bounds are outside the camera's frustum are skipped. At most MAX_LIGHTS lights
+	// are used: directional ones first, then those nearest the camera
+	pub fn render(&mut self, camera: &Camera, items: &[DrawItem]) {
+		self.stats = RenderStats::default();
+		self.stats.submitted = items.len() as u32;
+
+		let frustum = camera.frustum();
+		let eye = camera.position();
+		let forward = camera.forward();
+
+		self.queue.clear();
+		for (i, item) in items.iter().enumerate() {
+			let center = match item.bounds {
+				Some(ref b) => {
+					let world = b.transformed(&item.transform);
+					if !frustum.intersects_aabb(&world) {
+						self.stats.culled += 1;
+						continue
+					}
+					world.center()
+				}
+				None => item.transform.translation_part(),
+			};
+
+			let shader = item.material.shader.borrow().id();
+			let key = Renderer::sort_key(shader, item.material.id(), item.material.is_transparent(), (center - eye).dot(forward));
+			self.queue.push((key, i));
+		}
+		self.queue.sort_by_key(|q| q.0);
+
+		self.gather_lights(camera);
+		if let Some(ref mut shadows) = self.shadows {
+			shadows.render(camera, &self.visible_lights, items);
+		}
+
+		unsafe {
+			gl::Enable(gl::DEPTH_TEST);
+			gl::DepthFunc(gl::LEQUAL);
+			gl::CullFace(gl::BACK);
+		}
+
+		let mut shader_id = None;
+		let mut material_id = None;
+
+		for &(_, i) in self.queue.iter() {
+			let item = &items[i];
+			let mut shader = item.material.shader.borrow_mut();
+
+			if shader_id != Some(shader.id()) {
+				shader_id = Some(shader.id());
+				material_id = None;
+				self.stats.shader_changes += 1;
+
+				shader.bind();
+				self.set_frame_uniforms(&mut shader, camera);
+			}
+
+			if material_id != Some(item.material.id()) {
+				material_id = Some(item.material.id());
+				self.stats.material_changes += 1;
+
+				item.material.apply_state();
+				item.material.apply_params(&mut shader);
+			}
+
+			shader.set("u_model", item.transform);
+			shader.set("u_normal_matrix", item.transform.normal_matrix());
+			item.mesh.draw(item.mode, &mut shader);
+			self.stats.drawn += 1;
+		}
+
+		unsafe {
+			gl::DepthMask(gl::TRUE);
+			gl::Disable(gl::BLEND);
+		}
+	}
+
+	fn gather_lights(&mut self, camera: &Camera) {
+		let frustum = camera.frustum();
+		let eye = camera.position();
+
+		self.visible_lights.clear();
+		self.visible_lights.extend(self.lights.iter().filter(|l| {
+			l.bounds().map_or(true, |b| frustum.intersects_sphere(&b))
+		}));
+
+		let dist = |l: &Light| l.bounds().map_or(-1.0, |b| (b.center - eye).len_sq());
+		self.visible_lights.sort_by(|a, b| dist(a).partial_cmp(&dist(b)).unwrap_or(Ordering::Equal));
+		self.visible_lights.truncate(MAX_LIGHTS);
+
+		self.stats.lights = self.visible_lights.len() as u32;
+	}
+
+	fn set_frame_uniforms(&self, shader: &mut Shader, camera: &Camera) {
+		shader.set("u_view", camera.view());
+		shader.set("u_proj", camera.projection_matrix());
+		shader.set("u_view_proj", camera.view_projection());
+		shader.set("u_camera_position", camera.position());
+		shader.set("u_camera_forward", camera.forward());
+		shader.set("u_ambient", self.ambient.to_linear().to_vec3());
+
+		shader.set("u_light_count", self.visible_lights.len() as i32);
+		for (i, (light, names)) in self.visible_lights.iter().zip(self.light_names.iter()).enumerate() {
+			let (position, direction, params) = match light.kind {
+				LightKind::Directional { direction } =>
+					(direction.extend(0.0), direction, Vec3::zero()),
+
+				// A spot with a cone that includes everything
+				LightKind::Point { position, range } =>
+					(position.extend(1.0), Vec3::from_z(-1.0), Vec3::new(range, -1.0, -2.0)),
+
+				LightKind::Spot { position, direction, range, inner_angle, outer_angle } => {
+					// smoothstep needs distinct edges
+					let inner = inner_angle.cos();
+					(position.extend(1.0), direction, Vec3::new(range, inner, outer_angle.cos().min(inner - 1e-4)))
+				}
+			};
+
+			shader.set(&names.position, position);
+			shader.set(&names.direction, direction);
+			shader.set(&names.color, light.radiance());
+			shader.set(&names.params, params);
+
+			let shadow = self.shadows.as_ref().map_or(Vec4::new(-1.0, 0.0, 0.0, 0.0), |s| s.light_info(i));
+			shader.set(&names.shadow, shadow);
+		}
+
+		if let Some(ref shadows) = self.shadows {
+			shadows.set_uniforms(shader);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::PI;
+
+	#[test]
+	fn sort_key_order() {
+		let mut keys = vec![
+			(Renderer::sort_key(3, 1, true, 1.0), "glass near"),
+			(Renderer::sort_key(2, 7, false, 50.0), "b far"),
+			(Renderer::sort_key(1, 9, true, 30.0), "glass far"),
+			(Renderer::sort_key(1, 2, false, 5.0), "a2"),
+			(Renderer::sort_key(2, 7, false, 3.0), "b near"),
+			(Renderer::sort_key(1, 1, false, 90.0), "a1"),
+			(Renderer::sort_key(2, 6, false, 70.0), "b6"),
+		];
+		keys.sort_by_key(|k| k.0);
+
+		// Opaque by shader, then material, then near to far; transparent after, far to near
+		let order: Vec<&str> = keys.iter().map(|k| k.1).collect();
+		assert_eq!(order, vec!["a1", "a2", "b6", "b near", "b far", "glass far", "glass near"]);
+
+		// Even the largest opaque key comes before every transparent one
+		assert!(Renderer::sort_key(0xffff_ffff, 0xffff_ffff, false, ::std::f32::MAX) < Renderer::sort_key(0, 0, true, ::std::f32::MAX));
+
+		// Behind the camera counts as zero depth
+		assert_eq!(Renderer::sort_key(1, 1, false, -5.0), Renderer::sort_key(1, 1, false, 0.0));
+		assert_eq!(Renderer::sort_key(1, 1, true, -5.0), Renderer::sort_key(1, 1, true, 0.0));
+	}
+
+	fn point(z: f32) -> Light {
+		Light::point(Vec3::new(0.0, 0.0, z), 1.0, Color::WHITE, 1.0)
+	}
+
+	#[test]
+	fn gather_lights() {
+		let camera = Camera::perspective(PI / 2.0, 0.1, 100.0);
+		let sun = Light::directional(Vec3::new(0.0, -1.0, 0.0), Color::WHITE, 1.0);
+		let moon = Light::directional(Vec3::new(1.0, -1.0, 0.0), Color::WHITE, 0.1);
+		let spot = Light::spot(Vec3::new(3.0, 0.0, -10.0), Vec3::new(0.0, -1.0, 0.0), 2.0, 0.3, 0.5, Color::WHITE, 1.0);
+
+		let mut r = Renderer::new();
+		r.lights = vec![point(-50.0), point(-5.0), point(50.0), sun, spot, moon];
+		r.gather_lights(&camera);
+
+		// Directional first, then nearest first, with the light behind the camera culled
+		assert_eq!(r.visible_lights, vec![sun, moon, point(-5.0), spot, point(-50.0)]);
+		assert_eq!(r.stats().lights, 5);
+
+		r.lights = (0..12).map(|i| point(-2.0 - i as f32)).collect();
+		r.lights.push(sun);
+		r.gather_lights(&camera);
+
+		let mut expected = vec![sun];
+		expected.extend((0..MAX_LIGHTS - 1).map(|i| point(-2.0 - i as f32)));
+		assert_eq!(r.visible_lights, expected);
+		assert_eq!(r.stats().lights, MAX_LIGHTS as u32);
+	}
+
+	#[test]
+	fn light_uniform_names() {
+		let r = Renderer::new();
+		assert_eq!(r.light_names.len(), MAX_LIGHTS);
+		assert_eq!(r.light_names[0].position, "u_light_position[0]");
+		assert_eq!(r.light_names[MAX_LIGHTS - 1].shadow, format!("u_light_shadow[{}]", MAX_LIGHTS - 1));
+	}
+}
\ No newline at end of file
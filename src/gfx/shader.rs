@@ -1,6 +1,7 @@
 use bindings::gl;
 use math::vec::*;
 use math::mat::*;
+use color::*;
 use core::util::GLResource;
 
 use std::collections::HashMap;
@@ -53,6 +54,54 @@ impl Setter<Vec4> for Uniform {
 	}
 }
 
+impl Setter<Vec2i> for Uniform {
+	fn set(&self, val: Vec2i) {
+		unsafe {
+			gl::Uniform2i(self.loc, val.x, val.y);
+		}
+	}
+}
+
+impl Setter<Vec3i> for Uniform {
+	fn set(&self, val: Vec3i) {
+		unsafe {
+			gl::Uniform3i(self.loc, val.x, val.y, val.z);
+		}
+	}
+}
+
+impl Setter<Vec4i> for Uniform {
+	fn set(&self, val: Vec4i) {
+		unsafe {
+			gl::Uniform4i(self.loc, val.x, val.y, val.z, val.w);
+		}
+	}
+}
+
+impl Setter<Color> for Uniform {
+	fn set(&self, val: Color) {
+		unsafe {
+			gl::Uniform4f(self.loc, val.r, val.g, val.b, val.a);
+		}
+	}
+}
+
+impl Setter<Mat2> for Uniform {
+	fn set(&self, val: Mat2) {
+		unsafe {
+			gl::UniformMatrix2fv(self.loc, 1, gl::FALSE, val.as_ptr());
+		}
+	}
+}
+
+impl Setter<Mat3> for Uniform {
+	fn set(&self, val: Mat3) {
+		unsafe {
+			gl::UniformMatrix3fv(self.loc, 1, gl::FALSE, val.as_ptr());
+		}
+	}
+}
+
 impl Setter<Mat4> for Uniform {
 	fn set(&self, val: Mat4) {
 		unsafe {
@@ -140,6 +189,16 @@ impl Shader {
 		Some(Uniform { loc })
 	}
 
+	// Sets a uniform if the program uses it
+	pub fn set<T>(&mut self, uniform_name: &str, val: T) where Uniform: Setter<T> {
+		if let Some(u) = self.get(uniform_name) {
+			u.set(val);
+		}
+	}
+
+	// The GL program name, unique among live shaders
+	pub fn id(&self) -> u32 { self.program }
+
 	pub fn bind(&self) {
 		unsafe { gl::UseProgram(self.program); }
 	}
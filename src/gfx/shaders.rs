@@ -0,0 +1,324 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use shader::*;
+
+// Built-in lighting shaders for the forward renderer, in GLSL ES 1.00 for WebGL 1.
+//
+// Meshes provide `a_position` and `a_normal`. The renderer sets:
+//	u_model, u_normal_matrix, u_view, u_proj, u_view_proj, u_camera_position, u_camera_forward, u_ambient
+//	u_light_count and, per light i < MAX_LIGHTS:
+//		u_light_position[i]   xyz position, or direction of travel with w = 0 for directional lights
+//		u_light_direction[i]  spot axis
+//		u_light_color[i]      linear color * intensity
+//		u_light_params[i]     range, cos(inner angle), cos(outer angle)
+//		u_light_shadow[i]     first shadow tile or -1, cascade count (0 for spots), depth bias, normal bias
+// and the shadow atlas uniforms described in `shadow.rs`.
+// Shading is done in linear space and the output encoded to sRGB
+
+pub const MAX_LIGHTS: usize = 8;
+pub const MAX_SHADOW_TILES: usize = 8;
+
+macro_rules! lit_vertex_glsl { () => { "
+attribute vec3 a_position;
+attribute vec3 a_normal;
+
+uniform mat4 u_model;
+uniform mat4 u_view_proj;
+uniform mat3 u_normal_matrix;
+
+varying vec3 v_position;
+varying vec3 v_normal;
+
+void main() {
+	vec4 world = u_model * vec4(a_position, 1.0);
+	v_position = world.xyz;
+	v_normal = u_normal_matrix * a_normal;
+	gl_Position = u_view_proj * world;
+}
+" } }
+
+macro_rules! lit_fragment_header_glsl { () => { "
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+#define MAX_LIGHTS 8
+#define MAX_SHADOW_TILES 8
+
+uniform vec3 u_camera_position;
+uniform vec3 u_camera_forward;
+uniform vec3 u_ambient;
+
+uniform int u_light_count;
+uniform vec4 u_light_position[MAX_LIGHTS];
+uniform vec3 u_light_direction[MAX_LIGHTS];
+uniform vec3 u_light_color[MAX_LIGHTS];
+uniform vec3 u_light_params[MAX_LIGHTS];
+uniform vec4 u_light_shadow[MAX_LIGHTS];
+
+uniform sampler2D u_shadow_atlas;
+uniform mat4 u_shadow_matrix[MAX_SHADOW_TILES];
+uniform vec4 u_shadow_tile[MAX_SHADOW_TILES];
+uniform vec4 u_cascade_splits;
+uniform vec2 u_shadow_texel;
+uniform float u_shadow_pcf;
+uniform float u_shadow_packed;
+uniform float u_shadow_show_cascades;
+
+varying vec3 v_position;
+varying vec3 v_normal;
+
+float shadow_depth(vec2 uv) {
+	vec4 t = texture2D(u_shadow_atlas, uv);
+	if (u_shadow_packed > 0.5) {
+		return dot(t, vec4(1.0, 1.0 / 255.0, 1.0 / 65025.0, 1.0 / 16581375.0));
+	}
+	return t.r;
+}
+
+// Fraction of light reaching `p` past the casters in a shadow tile, averaged over the PCF kernel.
+// The loops select the tile because uniform arrays can't be indexed by a variable here
+float shadow_factor(int tile, vec3 p, float bias) {
+	vec4 c = vec4(0.0);
+	vec4 rect = vec4(0.0);
+	for (int j = 0; j < MAX_SHADOW_TILES; j++) {
+		if (j == tile) {
+			c = u_shadow_matrix[j] * vec4(p, 1.0);
+			rect = u_shadow_tile[j];
+		}
+	}
+
+	if (c.w <= 0.0) return 1.0;
+	vec3 s = c.xyz / c.w;
+	if (any(lessThan(s, vec3(0.0))) || any(greaterThan(s, vec3(1.0)))) return 1.0;
+
+	float depth = s.z - bias;
+	vec2 step_uv = u_shadow_texel / rect.zw;
+	float lit = 0.0;
+	float taps = 0.0;
+
+	for (int x = -2; x <= 2; x++) {
+		for (int y = -2; y <= 2; y++) {
+			if (abs(float(x)) > u_shadow_pcf || abs(float(y)) > u_shadow_pcf) continue;
+
+			vec2 uv = clamp(s.xy + vec2(float(x), float(y)) * step_uv, step_uv * 0.5, 1.0 - step_uv * 0.5);
+			lit += step(depth, shadow_depth(rect.xy + uv * rect.zw));
+			taps += 1.0;
+		}
+	}
+
+	return lit / taps;
+}
+" } }
+
+// Sums `shade(n, v, l, radiance)` over the lights into `color`. Inlined rather than a function
+// because GLSL ES 1.00 only allows indexing uniform arrays with loop indices
+macro_rules! light_loop_glsl { () => { "
+	float view_depth = dot(v_position - u_camera_position, u_camera_forward);
+	float shown_cascade = -1.0;
+
+	for (int i = 0; i < MAX_LIGHTS; i++) {
+		if (i >= u_light_count) break;
+
+		vec4 lp = u_light_position[i];
+		vec3 l;
+		float attenuation = 1.0;
+
+		if (lp.w == 0.0) {
+			l = -lp.xyz;
+		} else {
+			vec3 d = lp.xyz - v_position;
+			float dist = length(d);
+			l = d / dist;
+
+			// Inverse square, windowed to reach zero at the range
+			vec3 params = u_light_params[i];
+			float x = clamp(1.0 - pow(dist / params.x, 4.0), 0.0, 1.0);
+			attenuation = x * x / (dist * dist + 1.0);
+			attenuation *= smoothstep(params.z, params.y, dot(-l, u_light_direction[i]));
+		}
+
+		vec4 shadow = u_light_shadow[i];
+		if (shadow.x >= 0.0 && attenuation > 0.0) {
+			float tile = shadow.x;
+
+			// Directional lights pick a cascade by view depth, and are unshadowed past the last
+			if (shadow.y > 0.0) {
+				float cascade = dot(vec4(greaterThan(vec4(view_depth), u_cascade_splits)), vec4(1.0));
+				tile = cascade < shadow.y ? tile + cascade : -1.0;
+				shown_cascade = cascade < shadow.y ? cascade : shown_cascade;
+			}
+
+			if (tile >= 0.0) {
+				attenuation *= shadow_factor(int(tile + 0.5), v_position + n * shadow.w, shadow.z);
+			}
+		}
+
+		color += shade(n, v, l, u_light_color[i] * attenuation);
+	}
+
+	if (u_shadow_show_cascades > 0.5 && shown_cascade >= 0.0) {
+		vec3 tint = vec3(
+			float(shown_cascade == 0.0 || shown_cascade == 3.0),
+			float(shown_cascade == 1.0 || shown_cascade == 3.0),
+			float(shown_cascade == 2.0)
+		);
+		color = mix(color, tint, 0.3);
+	}
+" } }
+
+pub const LIT_VERTEX: &str = lit_vertex_glsl!();
+
+// Material uniforms: u_diffuse (vec4, alpha used for blending), u_specular, u_shininess, u_emissive
+pub const BLINN_PHONG_FRAGMENT: &str = concat!(lit_fragment_header_glsl!(), "
+uniform vec4 u_diffuse;
+uniform vec3 u_specular;
+uniform float u_shininess;
+uniform vec3 u_emissive;
+
+vec3 shade(vec3 n, vec3 v, vec3 l, vec3 radiance) {
+	float ndl = max(dot(n, l), 0.0);
+	if (ndl <= 0.0) return vec3(0.0);
+
+	vec3 h = normalize(l + v);
+	float spec = pow(max(dot(n, h), 0.0), u_shininess);
+	return radiance * (u_diffuse.rgb * ndl + u_specular * spec);
+}
+
+void main() {
+	vec3 n = normalize(v_normal);
+	if (!gl_FrontFacing) n = -n;
+	vec3 v = normalize(u_camera_position - v_position);
+
+	vec3 color = u_ambient * u_diffuse.rgb + u_emissive;
+", light_loop_glsl!(), "
+	gl_FragColor = vec4(pow(color, vec3(1.0 / 2.2)), u_diffuse.a);
+}
+");
+
+// Metallic-roughness with GGX distribution, Smith-Schlick geometry and Schlick Fresnel.
+// Material uniforms: u_base_color (vec4), u_metallic, u_roughness, u_emissive
+pub const PBR_FRAGMENT: &str = concat!(lit_fragment_header_glsl!(), "
+uniform vec4 u_base_color;
+uniform float u_metallic;
+uniform float u_roughness;
+uniform vec3 u_emissive;
+
+const float PI = 3.14159265;
+
+vec3 shade(vec3 n, vec3 v, vec3 l, vec3 radiance) {
+	float ndl = max(dot(n, l), 0.0);
+	if (ndl <= 0.0) return vec3(0.0);
+
+	vec3 h = normalize(l + v);
+	float ndv = max(dot(n, v), 1e-4);
+	float ndh = max(dot(n, h), 0.0);
+	float vdh = max(dot(v, h), 0.0);
+
+	float roughness = clamp(u_roughness, 0.04, 1.0);
+	float a = roughness * roughness;
+	float a2 = a * a;
+	float dd = ndh * ndh * (a2 - 1.0) + 1.0;
+	float distribution = a2 / (PI * dd * dd);
+
+	float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+	float geometry = (ndv / (ndv * (1.0 - k) + k)) * (ndl / (ndl * (1.0 - k) + k));
+
+	vec3 f0 = mix(vec3(0.04), u_base_color.rgb, u_metallic);
+	vec3 fresnel = f0 + (1.0 - f0) * pow(1.0 - vdh, 5.0);
+
+	vec3 specular = distribution * geometry * fresnel / max(4.0 * ndv * ndl, 1e-4);
+	vec3 diffuse = (1.0 - fresnel) * (1.0 - u_metallic) * u_base_color.rgb / PI;
+	return (diffuse + specular) * radiance * ndl;
+}
+
+void main() {
+	vec3 n = normalize(v_normal);
+	if (!gl_FrontFacing) n = -n;
+	vec3 v = normalize(u_camera_position - v_position);
+
+	vec3 color = u_ambient * u_base_color.rgb * (1.0 - u_metallic * 0.5) + u_emissive;
+", light_loop_glsl!(), "
+	gl_FragColor = vec4(pow(color, vec3(1.0 / 2.2)), u_base_color.a);
+}
+");
+
+// Writes depth for the shadow pass. With `u_shadow_packed` set the depth is packed into RGBA8,
+// for WebGL 1 without WEBGL_depth_texture
+pub const SHADOW_DEPTH_VERTEX: &str = "
+attribute vec3 a_position;
+
+uniform mat4 u_model;
+uniform mat4 u_light_view_proj;
+
+void main() {
+	gl_Position = u_light_view_proj * (u_model * vec4(a_position, 1.0));
+}
+";
+
+pub const SHADOW_DEPTH_FRAGMENT: &str = "
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+uniform float u_shadow_packed;
+
+void main() {
+	if (u_shadow_packed > 0.5) {
+		vec4 enc = fract(vec4(1.0, 255.0, 65025.0, 16581375.0) * gl_FragCoord.z);
+		gl_FragColor = enc - enc.yzww * vec4(1.0 / 255.0, 1.0 / 255.0, 1.0 / 255.0, 0.0);
+	} else {
+		gl_FragColor = vec4(1.0);
+	}
+}
+";
+
+// Shows the shadow atlas as grayscale, near casters dark. Drawn as a strip of 4 vertices
+pub const SHADOW_DEBUG_VERTEX: &str = "
+attribute vec2 a_position;
+
+varying vec2 v_uv;
+
+void main() {
+	v_uv = a_position;
+	gl_Position = vec4(a_position * 2.0 - 1.0, 0.0, 1.0);
+}
+";
+
+pub const SHADOW_DEBUG_FRAGMENT: &str = "
+precision mediump float;
+
+uniform sampler2D u_shadow_atlas;
+uniform float u_shadow_packed;
+
+varying vec2 v_uv;
+
+void main() {
+	vec4 t = texture2D(u_shadow_atlas, v_uv);
+	float d = u_shadow_packed > 0.5 ? dot(t, vec4(1.0, 1.0 / 255.0, 1.0 / 65025.0, 1.0 / 16581375.0)) : t.r;
+
+	// Stretch the top of the range, where most depths end up
+	gl_FragColor = vec4(vec3(pow(d, 16.0)), 1.0);
+}
+";
+
+// The built-in shaders, compiled once and shared between materials
+pub struct ShaderLibrary {
+	pub blinn_phong: Rc<RefCell<Shader>>,
+	pub pbr: Rc<RefCell<Shader>>,
+}
+
+impl ShaderLibrary {
+	// Needs a current GL context
+	pub fn new() -> ShaderLibrary {
+		ShaderLibrary {
+			blinn_phong: Rc::new(RefCell::new(Shader::new(LIT_VERTEX, BLINN_PHONG_FRAGMENT))),
+			pbr: Rc::new(RefCell::new(Shader::new(LIT_VERTEX, PBR_FRAGMENT))),
+		}
+	}
+}
\ No newline at end of file
@@ -0,0 +1,600 @@
+use std::ptr::null;
+use std::f32::consts::PI;
+
+use bindings::gl;
+use math::vec::*;
+use math::mat::*;
+use math::geom::*;
+use core::context::*;
+use core::util::GLResource;
+use scene::camera::*;
+use shader::*;
+use shaders::*;
+use light::*;
+use renderer::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShadowSettings {
+	// Subtracted from the receiver's depth, in the shadow map's [0, 1] range. Too little gives acne,
+	// too much detaches shadows from their casters
+	pub depth_bias: f32,
+
+	// Slope scaled offset applied while drawing casters, with glPolygonOffset
+	pub slope_bias: f32,
+
+	// World units the lookup moves along the surface normal, for acne at grazing angles
+	pub normal_bias: f32,
+
+	// Directional lights only: cascade count from 1 to 4, how logarithmic (1) rather than even (0)
+	// the splits are, how far from the camera shadows reach, and how far behind each
+	// cascade casters are still drawn
+	pub cascades: u32,
+	pub split_lambda: f32,
+	pub max_distance: f32,
+	pub caster_margin: f32,
+
+	// Spot lights only: near plane of the light's projection
+	pub near: f32,
+}
+
+impl Default for ShadowSettings {
+	fn default() -> ShadowSettings {
+		ShadowSettings {
+			depth_bias: 0.002,
+			slope_bias: 1.5,
+			normal_bias: 0.02,
+			cascades: 3,
+			split_lambda: 0.7,
+			max_distance: 60.0,
+			caster_margin: 50.0,
+			near: 0.1,
+		}
+	}
+}
+
+// How a shadow map stores depth. WebGL 1 only has depth textures with WEBGL_depth_texture;
+// without it depth is packed into the channels of an RGBA8 color texture
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DepthFormat {
+	Texture,
+	PackedRgba,
+}
+
+// A square render target whose depth can be sampled afterwards
+pub struct ShadowMap {
+	fbo: u32,
+	texture: u32,
+	depth_buffer: u32,
+	size: u32,
+	format: DepthFormat,
+}
+
+impl ShadowMap {
+	pub fn new(size: u32, format: DepthFormat) -> Result<ShadowMap, String> {
+		let mut map = ShadowMap { fbo: 0, texture: 0, depth_buffer: 0, size, format };
+
+		unsafe {
+			gl::GenTextures(1, &mut map.texture);
+			gl::BindTexture(gl::TEXTURE_2D, map.texture);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+
+			gl::GenFramebuffers(1, &mut map.fbo);
+			gl::BindFramebuffer(gl::FRAMEBUFFER, map.fbo);
+
+			match format {
+				DepthFormat::Texture => {
+					gl::TexImage2D(gl::TEXTURE_2D, 0, gl::DEPTH_COMPONENT as i32, size as i32, size as i32, 0,
+						gl::DEPTH_COMPONENT, gl::UNSIGNED_INT, null());
+					gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, map.texture, 0);
+				}
+
+				DepthFormat::PackedRgba => {
+					gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, size as i32, size as i32, 0,
+						gl::RGBA, gl::UNSIGNED_BYTE, null());
+					gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, map.texture, 0);
+
+					gl::GenRenderbuffers(1, &mut map.depth_buffer);
+					gl::BindRenderbuffer(gl::RENDERBUFFER, map.depth_buffer);
+					gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT16, size as i32, size as i32);
+					gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, map.depth_buffer);
+					gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+				}
+			}
+
+			let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+			gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+			gl::BindTexture(gl::TEXTURE_2D, 0);
+
+			if status != gl::FRAMEBUFFER_COMPLETE {
+				map.destroy();
+				return Err(format!("Shadow map framebuffer incomplete ({:#x}) with {:?}", status, format))
+			}
+		}
+
+		Ok(map)
+	}
+
+	pub fn size(&self) -> u32 { self.size }
+	pub fn format(&self) -> DepthFormat { self.format }
+	pub fn texture(&self) -> u32 { self.texture }
+
+	pub fn bind_target(&self) {
+		unsafe {
+			gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+		}
+	}
+
+	pub fn bind_texture(&self, unit: u32) {
+		unsafe {
+			gl::ActiveTexture(gl::TEXTURE0 + unit);
+			gl::BindTexture(gl::TEXTURE_2D, self.texture);
+			gl::ActiveTexture(gl::TEXTURE0);
+		}
+	}
+}
+
+impl GLResource for ShadowMap {
+	fn destroy(&self) {
+		unsafe {
+			gl::DeleteFramebuffers(1, &self.fbo);
+			gl::DeleteTextures(1, &self.texture);
+			if self.depth_buffer != 0 {
+				gl::DeleteRenderbuffers(1, &self.depth_buffer);
+			}
+		}
+	}
+}
+
+// Texture unit the lit shaders sample the atlas from, clear of the low units materials would use
+pub const SHADOW_TEXTURE_UNIT: u32 = 7;
+
+struct ShadowTile {
+	view_proj: Mat4,
+	// World to [0, 1] map coordinates and depth
+	matrix: Mat4,
+	// Offset and scale of the tile in the atlas
+	rect: Vec4,
+	frustum: Frustum,
+	slope_bias: f32,
+}
+
+// Clip space to [0, 1]
+fn clip_to_unit() -> Mat4 {
+	Mat4::new(&[
+		0.5, 0.0, 0.0, 0.0,
+		0.0, 0.5, 0.0, 0.0,
+		0.0, 0.0, 0.5, 0.0,
+		0.5, 0.5, 0.5, 1.0
+	])
+}
+
+fn light_up(dir: Vec3) -> Vec3 {
+	if dir.y.abs() > 0.99 { Vec3::from_z(1.0) } else { Vec3::from_y(1.0) }
+}
+
+// Shadow maps for the renderer's lights, packed as tiles into one atlas: a tile per cascade for
+// directional lights and one per spot light, up to MAX_SHADOW_TILES. Cascade splits are shared,
+// taken from the first shadowed directional light, and later ones get no more cascades than it
+pub struct Shadows {
+	map: ShadowMap,
+	depth_shader: Shader,
+	debug_shader: Shader,
+	debug_quad: u32,
+
+	tiles: Vec<ShadowTile>,
+	light_info: Vec<Vec4>,
+	splits: Vec4,
+
+	// Filter kernel radius in texels, 0 for hard shadows up to 2 for a 5x5 kernel
+	pub pcf_radius: u32,
+	// Tints lit surfaces by the cascade they're in
+	pub show_cascades: bool,
+}
+
+impl Shadows {
+	// Uses depth textures when WEBGL_depth_texture is available
+	pub fn new(ctx: &Context, size: u32) -> Result<Shadows, String> {
+		let format = if ctx.enable_extension("WEBGL_depth_texture") { DepthFormat::Texture } else { DepthFormat::PackedRgba };
+		Shadows::with_format(size, format)
+	}
+
+	pub fn with_format(size: u32, format: DepthFormat) -> Result<Shadows, String> {
+		let map = ShadowMap::new(size, format)?;
+
+		let quad: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+		let mut debug_quad = 0;
+		unsafe {
+			gl::GenBuffers(1, &mut debug_quad);
+			gl::BindBuffer(gl::ARRAY_BUFFER, debug_quad);
+			gl::BufferData(gl::ARRAY_BUFFER, (quad.len() * 4) as _, quad.as_ptr() as _, gl::STATIC_DRAW);
+			gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+		}
+
+		Ok(Shadows {
+			map,
+			depth_shader: Shader::new(SHADOW_DEPTH_VERTEX, SHADOW_DEPTH_FRAGMENT),
+			debug_shader: Shader::new(SHADOW_DEBUG_VERTEX, SHADOW_DEBUG_FRAGMENT),
+			debug_quad,
+			tiles: Vec::new(),
+			light_info: Vec::new(),
+			splits: Vec4::uniform(1e9),
+			pcf_radius: 1,
+			show_cascades: false,
+		})
+	}
+
+	pub fn map(&self) -> &ShadowMap { &self.map }
+	pub fn tile_count(&self) -> usize { self.tiles.len() }
+
+	// What the lit shaders get as `u_light_shadow[i]` for the i-th light of the last `render`
+	pub fn light_info(&self, i: usize) -> Vec4 {
+		self.light_info.get(i).cloned().unwrap_or(Vec4::new(-1.0, 0.0, 0.0, 0.0))
+	}
+
+	fn add_tile(&mut self, view_proj: Mat4, slope_bias: f32) {
+		self.tiles.push(ShadowTile {
+			view_proj,
+			matrix: view_proj * clip_to_unit(),
+			rect: Vec4::zero(),
+			frustum: Frustum::from_matrix(&view_proj),
+			slope_bias,
+		});
+	}
+
+	// View depths splitting [near, far] into `count` cascades
+	fn cascade_splits(near: f32, far: f32, count: u32, lambda: f32) -> Vec4 {
+		let mut splits = Vec4::uniform(1e9);
+		for k in 1..count + 1 {
+			let f = k as f32 / count as f32;
+			let even = near + (far - near) * f;
+			let log = if near > 0.0 { near * (far / near).powf(f) } else { even };
+			splits[k as usize - 1] = lambda * log + (1.0 - lambda) * even;
+		}
+		splits
+	}
+
+	// Tiles each of the first MAX_LIGHTS lights gets. Lights that would go over MAX_SHADOW_TILES get none
+	fn tile_counts(lights: &[Light]) -> Vec<usize> {
+		let mut counts = Vec::new();
+		let mut total = 0;
+		// The splits only cover the cascades of the first directional light that gets tiles
+		let mut cascades: Option<usize> = None;
+
+		for light in lights.iter().take(MAX_LIGHTS) {
+			let directional = match light.kind { LightKind::Directional { .. } => true, _ => false };
+			let count = match (light.kind, light.shadow) {
+				(LightKind::Directional { .. }, Some(s)) => {
+					let count = s.cascades.max(1).min(4) as usize;
+					cascades.map_or(count, |c| count.min(c))
+				}
+				(LightKind::Spot { .. }, Some(_)) => 1,
+				_ => 0,
+			};
+
+			let count = if total + count <= MAX_SHADOW_TILES { count } else { 0 };
+			if directional && count > 0 && cascades.is_none() {
+				cascades = Some(count);
+			}
+
+			counts.push(count);
+			total += count;
+		}
+
+		counts
+	}
+
+	// Side of the square grid `total` tiles are packed into
+	fn atlas_grid(total: usize) -> u32 {
+		(total as f32).sqrt().ceil().max(1.0) as u32
+	}
+
+	// Offset and scale of tile `t` in the atlas, filling rows from the bottom-left
+	fn tile_rect(t: usize, grid: u32) -> Vec4 {
+		let (col, row) = ((t as u32 % grid) as f32, (t as u32 / grid) as f32);
+		let scale = 1.0 / grid as f32;
+		Vec4::new(col * scale, row * scale, scale, scale)
+	}
+
+	fn add_cascades(&mut self, camera: &Camera, dir: Vec3, settings: &ShadowSettings, count: u32, tile_px: f32) {
+		let (near, far) = match camera.projection() {
+			Projection::Perspective { near, far, .. } | Projection::Orthographic { near, far, .. } => (near, far),
+		};
+		let shadow_far = far.min(settings.max_distance);
+
+		if self.splits.x >= 1e9 {
+			self.splits = Shadows::cascade_splits(near, shadow_far, count, settings.split_lambda);
+		}
+
+		// Frustum edges from the near to the far plane, which view depth moves along linearly
+		let inv = camera.inverse_view_projection();
+		let mut edges = [(Vec3::zero(), Vec3::zero()); 4];
+		for (i, &(x, y)) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].iter().enumerate() {
+			edges[i] = (inv.transform_point(Vec3::new(x, y, -1.0)), inv.transform_point(Vec3::new(x, y, 1.0)));
+		}
+
+		let view = Mat4::look_at(Vec3::zero(), dir, light_up(dir));
+		let mut from = near;
+
+		for k in 0..count as usize {
+			let to = self.splits[k];
+			let mut corners = Vec::with_capacity(8);
+			for &(n, f) in edges.iter() {
+				for &d in [from, to].iter() {
+					corners.push(n.lerp(f, (d - near) / (far - near)));
+				}
+			}
+			from = to;
+
+			// A bounding sphere keeps the cascade's size steady as the camera turns
+			let center = corners.iter().fold(Vec3::zero(), |a, &c| a + c) * (1.0 / 8.0);
+			let radius = corners.iter().fold(0.0f32, |r, &c| r.max(c.dist(center)));
+			let radius = (radius * 16.0).ceil() / 16.0;
+
+			// Snapping to whole texels stops edges shimmering as the camera moves
+			let c = view.transform_point(center);
+			let texel = 2.0 * radius / tile_px;
+			let (cx, cy) = ((c.x / texel).floor() * texel, (c.y / texel).floor() * texel);
+
+			let proj = Mat4::ortho(cx - radius, cx + radius, cy + radius, cy - radius,
+				-c.z - radius - settings.caster_margin, -c.z + radius);
+			self.add_tile(view * proj, settings.slope_bias);
+		}
+	}
+
+	// Lays out tiles for the shadowed lights and draws every opaque item into them
+	pub fn render(&mut self, camera: &Camera, lights: &[Light], items: &[DrawItem]) {
+		self.tiles.clear();
+		self.light_info.clear();
+		self.splits = Vec4::uniform(1e9);
+
+		let wanted = Shadows::tile_counts(lights);
+		let grid = Shadows::atlas_grid(wanted.iter().sum());
+		let tile_px = (self.map.size() / grid) as f32;
+
+		for (light, &count) in lights.iter().zip(wanted.iter()) {
+			let settings = match light.shadow {
+				Some(s) if count > 0 => s,
+				_ => {
+					self.light_info.push(Vec4::new(-1.0, 0.0, 0.0, 0.0));
+					continue
+				}
+			};
+
+			let first = self.tiles.len() as f32;
+			match light.kind {
+				LightKind::Directional { direction } => {
+					self.add_cascades(camera, direction, &settings, count as u32, tile_px);
+					self.light_info.push(Vec4::new(first, count as f32, settings.depth_bias, settings.normal_bias));
+				}
+
+				LightKind::Spot { position, direction, range, outer_angle, .. } => {
+					let view = Mat4::look_at(position, position + direction, light_up(direction));
+					let proj = Mat4::perspective((outer_angle * 2.0).min(PI * 0.95), 1.0, settings.near, range);
+					self.add_tile(view * proj, settings.slope_bias);
+					self.light_info.push(Vec4::new(first, 0.0, settings.depth_bias, settings.normal_bias));
+				}
+
+				LightKind::Point { .. } => unreachable!(),
+			}
+		}
+
+		for (t, tile) in self.tiles.iter_mut().enumerate() {
+			tile.rect = Shadows::tile_rect(t, grid);
+		}
+
+		self.draw_casters(items, grid, tile_px as i32);
+	}
+
+	fn draw_casters(&mut self, items: &[DrawItem], grid: u32, tile_px: i32) {
+		let mut fbo = 0;
+		let mut viewport = [0i32; 4];
+		let mut clear_color = [0.0f32; 4];
+		let packed = self.map.format() == DepthFormat::PackedRgba;
+
+		unsafe {
+			gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut fbo);
+			gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+			gl::GetFloatv(gl::COLOR_CLEAR_VALUE, clear_color.as_mut_ptr());
+
+			self.map.bind_target();
+			gl::Viewport(0, 0, self.map.size() as i32, self.map.size() as i32);
+			gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+			gl::DepthMask(gl::TRUE);
+			gl::ClearColor(1.0, 1.0, 1.0, 1.0);
+			gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+			if !packed {
+				gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+			}
+
+			gl::Enable(gl::DEPTH_TEST);
+			gl::DepthFunc(gl::LEQUAL);
+			gl::Disable(gl::BLEND);
+			gl::Disable(gl::CULL_FACE);
+			gl::Enable(gl::POLYGON_OFFSET_FILL);
+		}
+
+		self.depth_shader.bind();
+		self.depth_shader.set("u_shadow_packed", if packed { 1.0 } else { 0.0 });
+
+		for (t, tile) in self.tiles.iter().enumerate() {
+			let (col, row) = ((t as u32 % grid) as i32, (t as u32 / grid) as i32);
+			unsafe {
+				gl::Viewport(col * tile_px, row * tile_px, tile_px, tile_px);
+				gl::PolygonOffset(tile.slope_bias, 1.0);
+			}
+
+			self.depth_shader.set("u_light_view_proj", tile.view_proj);
+
+			for item in items.iter().filter(|i| !i.material.is_transparent()) {
+				if let Some(ref b) = item.bounds {
+					if !tile.frustum.intersects_aabb(&b.transformed(&item.transform)) {
+						continue
+					}
+				}
+
+				self.depth_shader.set("u_model", item.transform);
+				item.mesh.draw(item.mode, &mut self.depth_shader);
+			}
+		}
+
+		unsafe {
+			gl::Disable(gl::POLYGON_OFFSET_FILL);
+			gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+			gl::ClearColor(clear_color[0], clear_color[1], clear_color[2], clear_color[3]);
+			gl::BindFramebuffer(gl::FRAMEBUFFER, fbo as u32);
+			gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+		}
+	}
+
+	// For a bound lit shader, after `render`
+	pub fn set_uniforms(&self, shader: &mut Shader) {
+		self.map.bind_texture(SHADOW_TEXTURE_UNIT);
+		shader.set("u_shadow_atlas", SHADOW_TEXTURE_UNIT as i32);
+
+		for (j, tile) in self.tiles.iter().enumerate() {
+			shader.set(&format!("u_shadow_matrix[{}]", j), tile.matrix);
+			shader.set(&format!("u_shadow_tile[{}]", j), tile.rect);
+		}
+
+		shader.set("u_cascade_splits", self.splits);
+		shader.set("u_shadow_texel", Vec2::uniform(1.0 / self.map.size() as f32));
+		shader.set("u_shadow_pcf", self.pcf_radius.min(2) as f32);
+		shader.set("u_shadow_packed", if self.map.format() == DepthFormat::PackedRgba { 1.0 } else { 0.0 });
+		shader.set("u_shadow_show_cascades", if self.show_cascades { 1.0 } else { 0.0 });
+	}
+
+	// Draws the whole atlas into a square at (x, y) from the bottom-left of the current framebuffer, `size` pixels across
+	pub fn draw_debug(&mut self, x: i32, y: i32, size: i32) {
+		let mut viewport = [0i32; 4];
+		unsafe {
+			gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+			gl::Viewport(x, y, size, size);
+			gl::Disable(gl::DEPTH_TEST);
+			gl::Disable(gl::BLEND);
+		}
+
+		self.debug_shader.bind();
+		self.map.bind_texture(0);
+		self.debug_shader.set("u_shadow_atlas", 0);
+		self.debug_shader.set("u_shadow_packed", if self.map.format() == DepthFormat::PackedRgba { 1.0 } else { 0.0 });
+
+		let loc = self.debug_shader.get_attrib_location("a_position");
+		unsafe {
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.debug_quad);
+			if loc != -1 {
+				gl::EnableVertexAttribArray(loc as u32);
+				gl::VertexAttribPointer(loc as u32, 2, gl::FLOAT, gl::FALSE, 0, null());
+			}
+
+			gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+
+			if loc != -1 {
+				gl::DisableVertexAttribArray(loc as u32);
+			}
+			gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+			gl::BindTexture(gl::TEXTURE_2D, 0);
+
+			gl::Enable(gl::DEPTH_TEST);
+			gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+		}
+	}
+}
+
+impl GLResource for Shadows {
+	fn destroy(&self) {
+		self.map.destroy();
+		self.depth_shader.destroy();
+		self.debug_shader.destroy();
+		unsafe {
+			gl::DeleteBuffers(1, &self.debug_quad);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use color::*;
+
+	fn sun(cascades: u32) -> Light {
+		Light::directional(Vec3::new(0.0, -1.0, 0.0), Color::WHITE, 1.0)
+			.with_shadow(ShadowSettings { cascades, ..ShadowSettings::default() })
+	}
+
+	fn spot(shadowed: bool) -> Light {
+		let l = Light::spot(Vec3::zero(), Vec3::from_z(-1.0), 10.0, 0.3, 0.5, Color::WHITE, 1.0);
+		if shadowed { l.with_shadow(ShadowSettings::default()) } else { l }
+	}
+
+	#[test]
+	fn cascade_splits() {
+		let even = Shadows::cascade_splits(1.0, 100.0, 4, 0.0);
+		assert!(even.approx_eq(Vec4::new(25.75, 50.5, 75.25, 100.0), 1e-4));
+
+		let log = Shadows::cascade_splits(1.0, 100.0, 4, 1.0);
+		assert!(log.approx_eq(Vec4::new(10f32.sqrt(), 10.0, 1000f32.sqrt(), 100.0), 1e-3));
+
+		let mixed = Shadows::cascade_splits(1.0, 100.0, 4, 0.5);
+		assert!(mixed.approx_eq((even + log) * 0.5, 1e-3));
+
+		// Unused splits stay out of reach, so no view depth selects them
+		let two = Shadows::cascade_splits(0.5, 60.0, 2, 0.7);
+		assert!(two.x > 0.5 && two.x < 60.0);
+		assert!((two.y - 60.0).abs() < 1e-4);
+		assert_eq!((two.z, two.w), (1e9, 1e9));
+		assert_eq!(Shadows::cascade_splits(0.5, 60.0, 1, 0.7), Vec4::new(60.0, 1e9, 1e9, 1e9));
+
+		// Without a positive near plane the log distribution falls back to even
+		assert!(Shadows::cascade_splits(0.0, 80.0, 4, 1.0).approx_eq(Vec4::new(20.0, 40.0, 60.0, 80.0), 1e-4));
+	}
+
+	#[test]
+	fn tile_counts() {
+		let point = Light::point(Vec3::zero(), 5.0, Color::WHITE, 1.0).with_shadow(ShadowSettings::default());
+		assert_eq!(Shadows::tile_counts(&[sun(3), spot(true), point, spot(false)]), vec![3, 1, 0, 0]);
+		assert_eq!(Shadows::tile_counts(&[sun(0)]), vec![1]);
+		assert_eq!(Shadows::tile_counts(&[sun(9)]), vec![4]);
+
+		// Later directional lights can't have more cascades than the shared splits cover
+		assert_eq!(Shadows::tile_counts(&[sun(2), sun(4)]), vec![2, 2]);
+		assert_eq!(Shadows::tile_counts(&[sun(4), sun(1)]), vec![4, 1]);
+
+		// Lights that don't fit get nothing, and don't decide the cascade count
+		assert_eq!(Shadows::tile_counts(&[sun(4), sun(4), spot(true)]), vec![4, 4, 0]);
+		let mut lights: Vec<Light> = (0..6).map(|_| spot(true)).collect();
+		lights.push(sun(4));
+		lights.push(sun(2));
+		assert_eq!(Shadows::tile_counts(&lights), vec![1, 1, 1, 1, 1, 1, 0, 2]);
+
+		let many: Vec<Light> = (0..MAX_LIGHTS + 3).map(|_| spot(true)).collect();
+		assert_eq!(Shadows::tile_counts(&many).len(), MAX_LIGHTS);
+		assert!(Shadows::tile_counts(&many).iter().sum::<usize>() <= MAX_SHADOW_TILES);
+	}
+
+	#[test]
+	fn tile_layout() {
+		let grids: Vec<u32> = [0, 1, 2, 4, 5, 8, 9].iter().map(|&n| Shadows::atlas_grid(n)).collect();
+		assert_eq!(grids, vec![1, 1, 2, 2, 3, 3, 3]);
+
+		assert_eq!(Shadows::tile_rect(0, 1), Vec4::new(0.0, 0.0, 1.0, 1.0));
+		assert_eq!(Shadows::tile_rect(1, 2), Vec4::new(0.5, 0.0, 0.5, 0.5));
+		assert_eq!(Shadows::tile_rect(3, 2), Vec4::new(0.5, 0.5, 0.5, 0.5));
+
+		let third = 1.0 / 3.0;
+		assert!(Shadows::tile_rect(5, 3).approx_eq(Vec4::new(2.0 * third, third, third, third), 1e-6));
+
+		// Every tile of a full atlas is inside it and none overlap
+		let grid = Shadows::atlas_grid(MAX_SHADOW_TILES);
+		let rects: Vec<Vec4> = (0..MAX_SHADOW_TILES).map(|t| Shadows::tile_rect(t, grid)).collect();
+		for (i, a) in rects.iter().enumerate() {
+			assert!(a.x + a.z <= 1.0 + 1e-6 && a.y + a.w <= 1.0 + 1e-6);
+			for b in rects[i + 1..].iter() {
+				assert!(a.x + a.z <= b.x + 1e-6 || b.x + b.z <= a.x + 1e-6 || a.y + a.w <= b.y + 1e-6 || b.y + b.w <= a.y + 1e-6);
+			}
+		}
+	}
+}
\ No newline at end of file
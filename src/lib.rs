@@ -9,8 +9,13 @@
 pub mod math;
 pub mod core;
 pub mod gfx;
+pub mod scene;
 
 pub use bindings::emscripten::*;
 pub use math::*;
 pub use core::*;
-pub use gfx::*;
\ No newline at end of file
+pub use gfx::*;
+pub use scene::*;
+
+// Both math and gfx have a `geom`; the root one stays the mesh module it always was
+pub use gfx::geom;
\ No newline at end of file
@@ -0,0 +1,972 @@
+use std::f32;
+
+use vec::*;
+use mat::*;
+
+// Shapes that can be hit by a ray. Returns the distance along the ray to the first hit,
+// in units of the ray's direction, or zero if the origin is already inside
+pub trait Raycast {
+	fn raycast(&self, ray: &Ray) -> Option<f32>;
+}
+
+// Largest axis scale of an affine matrix, for radii
+fn max_scale(m: &Mat4) -> f32 {
+	m.rows[0].as_vec3().len()
+		.max(m.rows[1].as_vec3().len())
+		.max(m.rows[2].as_vec3().len())
+}
+
+pub fn closest_point_on_segment(a: Vec3, b: Vec3, p: Vec3) -> Vec3 {
+	let ab = b - a;
+	let l = ab.len_sq();
+	if l <= 0.0 { return a }
+
+	let t = ((p - a).dot(ab) / l).max(0.0).min(1.0);
+	a + ab * t
+}
+
+// Closest points between segments `p0 p1` and `q0 q1`
+pub fn closest_points_on_segments(p0: Vec3, p1: Vec3, q0: Vec3, q1: Vec3) -> (Vec3, Vec3) {
+	let d1 = p1 - p0;
+	let d2 = q1 - q0;
+	let r = p0 - q0;
+	let a = d1.len_sq();
+	let e = d2.len_sq();
+	let f = d2.dot(r);
+
+	if a <= f32::EPSILON && e <= f32::EPSILON {
+		return (p0, q0)
+	}
+
+	let (s, t);
+	if a <= f32::EPSILON {
+		s = 0.0;
+		t = (f / e).max(0.0).min(1.0);
+	} else {
+		let c = d1.dot(r);
+		if e <= f32::EPSILON {
+			t = 0.0;
+			s = (-c / a).max(0.0).min(1.0);
+		} else {
+			let b = d1.dot(d2);
+			let denom = a * e - b * b;
+
+			// Parallel segments pick an arbitrary s
+			let s0 = if denom != 0.0 { ((b * f - c * e) / denom).max(0.0).min(1.0) } else { 0.0 };
+			let t0 = (b * s0 + f) / e;
+
+			if t0 < 0.0 {
+				t = 0.0;
+				s = (-c / a).max(0.0).min(1.0);
+			} else if t0 > 1.0 {
+				t = 1.0;
+				s = ((b - c) / a).max(0.0).min(1.0);
+			} else {
+				t = t0;
+				s = s0;
+			}
+		}
+	}
+
+	(p0 + d1 * s, q0 + d2 * t)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+	pub origin: Vec3,
+	pub dir: Vec3,
+}
+
+impl Ray {
+	pub fn new(origin: Vec3, dir: Vec3) -> Ray {
+		Ray { origin, dir }
+	}
+
+	pub fn from_points(from: Vec3, to: Vec3) -> Ray {
+		Ray::new(from, (to - from).normalized())
+	}
+
+	pub fn at(&self, t: f32) -> Vec3 {
+		self.origin + self.dir * t
+	}
+
+	// The direction isn't renormalized, so distances along the result match the original
+	pub fn transformed(&self, m: &Mat4) -> Ray {
+		Ray::new(m.transform_point(self.origin), m.transform_vector(self.dir))
+	}
+
+	pub fn cast<T: Raycast>(&self, shape: &T) -> Option<f32> {
+		shape.raycast(self)
+	}
+}
+
+// Points where `normal.dot(p) == dist`. The normal side is the positive one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plane {
+	pub normal: Vec3,
+	pub dist: f32,
+}
+
+impl Plane {
+	pub fn new(normal: Vec3, dist: f32) -> Plane {
+		Plane { normal, dist }
+	}
+
+	pub fn from_point_normal(p: Vec3, normal: Vec3) -> Plane {
+		let normal = normal.normalized();
+		Plane::new(normal, normal.dot(p))
+	}
+
+	// Counter-clockwise winding faces the positive side
+	pub fn from_points(a: Vec3, b: Vec3, c: Vec3) -> Plane {
+		Plane::from_point_normal(a, (b - a).cross(c - a))
+	}
+
+	// From the (a, b, c, d) of ax + by + cz + d = 0
+	pub fn from_vec4(v: Vec4) -> Plane {
+		let l = v.as_vec3().len();
+		Plane::new(v.as_vec3() * (1.0 / l), -v.w / l)
+	}
+
+	pub fn to_vec4(&self) -> Vec4 {
+		self.normal.extend(-self.dist)
+	}
+
+	pub fn signed_distance(&self, p: Vec3) -> f32 {
+		self.normal.dot(p) - self.dist
+	}
+
+	pub fn project_point(&self, p: Vec3) -> Vec3 {
+		p - self.normal * self.signed_distance(p)
+	}
+
+	pub fn flipped(&self) -> Plane {
+		Plane::new(-self.normal, -self.dist)
+	}
+
+	pub fn transformed(&self, m: &Mat4) -> Plane {
+		let inv = m.inverted();
+		let v = self.to_vec4();
+		Plane::from_vec4(Vec4::new(inv.rows[0].dot(v), inv.rows[1].dot(v), inv.rows[2].dot(v), inv.rows[3].dot(v)))
+	}
+}
+
+impl Raycast for Plane {
+	fn raycast(&self, ray: &Ray) -> Option<f32> {
+		let denom = self.normal.dot(ray.dir);
+		if denom.abs() <= f32::EPSILON { return None }
+
+		let t = (self.dist - self.normal.dot(ray.origin)) / denom;
+		if t >= 0.0 { Some(t) } else { None }
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+	pub min: Vec3,
+	pub max: Vec3,
+}
+
+impl Aabb {
+	pub fn new(min: Vec3, max: Vec3) -> Aabb {
+		Aabb { min, max }
+	}
+
+	pub fn from_center(center: Vec3, half_extents: Vec3) -> Aabb {
+		Aabb::new(center - half_extents, center + half_extents)
+	}
+
+	// Contains nothing, and merging anything into it gives that thing
+	pub fn empty() -> Aabb {
+		Aabb::new(Vec3::uniform(f32::INFINITY), Vec3::uniform(f32::NEG_INFINITY))
+	}
+
+	pub fn from_points(points: &[Vec3]) -> Aabb {
+		points.iter().fold(Aabb::empty(), |b, &p| b.expanded_to(p))
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+	}
+
+	pub fn center(&self) -> Vec3 { (self.min + self.max) * 0.5 }
+	pub fn size(&self) -> Vec3 { self.max - self.min }
+	pub fn half_extents(&self) -> Vec3 { self.size() * 0.5 }
+
+	pub fn corners(&self) -> [Vec3; 8] {
+		let (a, b) = (self.min, self.max);
+		[
+			Vec3::new(a.x, a.y, a.z), Vec3::new(b.x, a.y, a.z),
+			Vec3::new(a.x, b.y, a.z), Vec3::new(b.x, b.y, a.z),
+			Vec3::new(a.x, a.y, b.z), Vec3::new(b.x, a.y, b.z),
+			Vec3::new(a.x, b.y, b.z), Vec3::new(b.x, b.y, b.z),
+		]
+	}
+
+	pub fn contains_point(&self, p: Vec3) -> bool {
+		p.x >= self.min.x && p.x <= self.max.x
+			&& p.y >= self.min.y && p.y <= self.max.y
+			&& p.z >= self.min.z && p.z <= self.max.z
+	}
+
+	pub fn contains(&self, o: &Aabb) -> bool {
+		self.contains_point(o.min) && self.contains_point(o.max)
+	}
+
+	pub fn intersects(&self, o: &Aabb) -> bool {
+		self.min.x <= o.max.x && self.max.x >= o.min.x
+			&& self.min.y <= o.max.y && self.max.y >= o.min.y
+			&& self.min.z <= o.max.z && self.max.z >= o.min.z
+	}
+
+	pub fn intersects_sphere(&self, s: &Sphere) -> bool {
+		(self.closest_point(s.center) - s.center).len_sq() <= s.radius * s.radius
+	}
+
+	pub fn closest_point(&self, p: Vec3) -> Vec3 {
+		p.clamp(self.min, self.max)
+	}
+
+	pub fn merged(&self, o: &Aabb) -> Aabb {
+		Aabb::new(self.min.min(o.min), self.max.max(o.max))
+	}
+
+	pub fn expanded_to(&self, p: Vec3) -> Aabb {
+		Aabb::new(self.min.min(p), self.max.max(p))
+	}
+
+	// Grown by `amount` on every side
+	pub fn expanded(&self, amount: f32) -> Aabb {
+		Aabb::new(self.min - amount, self.max + amount)
+	}
+
+	// Bounds of the transformed box, which are usually larger than the box itself
+	pub fn transformed(&self, m: &Mat4) -> Aabb {
+		let center = m.transform_point(self.center());
+		let e = self.half_extents();
+		let a = m.to_mat3();
+
+		let half = a[0].abs() * e.x + a[1].abs() * e.y + a[2].abs() * e.z;
+		Aabb::from_center(center, half)
+	}
+}
+
+// Ray against the box between `min` and `max`, one axis at a time. A zero direction component is
+// handled explicitly, since its slab would otherwise compute 0 * inf = NaN for a ray in a face plane
+fn slab_raycast(origin: &[f32], dir: &[f32], min: &[f32], max: &[f32]) -> Option<f32> {
+	let mut tmin = 0.0f32;
+	let mut tmax = f32::INFINITY;
+
+	for i in 0..origin.len() {
+		if dir[i] == 0.0 {
+			if origin[i] < min[i] || origin[i] > max[i] { return None }
+			continue
+		}
+
+		let inv = 1.0 / dir[i];
+		let t1 = (min[i] - origin[i]) * inv;
+		let t2 = (max[i] - origin[i]) * inv;
+		tmin = tmin.max(t1.min(t2));
+		tmax = tmax.min(t1.max(t2));
+	}
+
+	if tmax >= tmin { Some(tmin) } else { None }
+}
+
+impl Raycast for Aabb {
+	fn raycast(&self, ray: &Ray) -> Option<f32> {
+		let (o, d) = (ray.origin, ray.dir);
+		slab_raycast(&[o.x, o.y, o.z], &[d.x, d.y, d.z], &[self.min.x, self.min.y, self.min.z], &[self.max.x, self.max.y, self.max.z])
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sphere {
+	pub center: Vec3,
+	pub radius: f32,
+}
+
+impl Sphere {
+	pub fn new(center: Vec3, radius: f32) -> Sphere {
+		Sphere { center, radius }
+	}
+
+	// Not the tightest, but cheap
+	pub fn from_aabb(b: &Aabb) -> Sphere {
+		Sphere::new(b.center(), b.half_extents().len())
+	}
+
+	pub fn contains_point(&self, p: Vec3) -> bool {
+		(p - self.center).len_sq() <= self.radius * self.radius
+	}
+
+	pub fn intersects(&self, o: &Sphere) -> bool {
+		let r = self.radius + o.radius;
+		(o.center - self.center).len_sq() <= r * r
+	}
+
+	pub fn merged(&self, o: &Sphere) -> Sphere {
+		let d = o.center - self.center;
+		let l = d.len();
+
+		if l + o.radius <= self.radius { return *self }
+		if l + self.radius <= o.radius { return *o }
+
+		let radius = (l + self.radius + o.radius) * 0.5;
+		Sphere::new(self.center + d * ((radius - self.radius) / l), radius)
+	}
+
+	pub fn expanded(&self, amount: f32) -> Sphere {
+		Sphere::new(self.center, self.radius + amount)
+	}
+
+	pub fn aabb(&self) -> Aabb {
+		Aabb::from_center(self.center, Vec3::uniform(self.radius))
+	}
+
+	// Non-uniform scale grows the sphere to fit the largest axis
+	pub fn transformed(&self, m: &Mat4) -> Sphere {
+		Sphere::new(m.transform_point(self.center), self.radius * max_scale(m))
+	}
+}
+
+impl Raycast for Sphere {
+	fn raycast(&self, ray: &Ray) -> Option<f32> {
+		let m = ray.origin - self.center;
+		let a = ray.dir.len_sq();
+		let b = m.dot(ray.dir);
+		let c = m.len_sq() - self.radius * self.radius;
+
+		if c <= 0.0 { return Some(0.0) }
+		if b > 0.0 { return None }
+
+		let disc = b * b - a * c;
+		if disc < 0.0 { return None }
+
+		Some((-b - disc.sqrt()) / a)
+	}
+}
+
+// Oriented box; the rows of `axes` are its unit local axes
+#[derive(Copy, Clone, Debug)]
+pub struct Obb {
+	pub center: Vec3,
+	pub axes: Mat3,
+	pub half_extents: Vec3,
+}
+
+impl Obb {
+	pub fn new(center: Vec3, axes: Mat3, half_extents: Vec3) -> Obb {
+		Obb { center, axes, half_extents }
+	}
+
+	// `m` must be affine without shear
+	pub fn from_aabb(b: &Aabb, m: &Mat4) -> Obb {
+		let a = m.to_mat3();
+		let scale = Vec3::new(a[0].len(), a[1].len(), a[2].len());
+		let axes = Mat3::from_rows(a[0] * (1.0 / scale.x), a[1] * (1.0 / scale.y), a[2] * (1.0 / scale.z));
+
+		Obb::new(m.transform_point(b.center()), axes, b.half_extents().scale(scale))
+	}
+
+	// Position of `p` along each of the box's axes, relative to its center
+	pub fn to_local(&self, p: Vec3) -> Vec3 {
+		let d = p - self.center;
+		Vec3::new(d.dot(self.axes[0]), d.dot(self.axes[1]), d.dot(self.axes[2]))
+	}
+
+	pub fn from_local(&self, p: Vec3) -> Vec3 {
+		self.center + p * self.axes
+	}
+
+	pub fn contains_point(&self, p: Vec3) -> bool {
+		let l = self.to_local(p).abs();
+		l.x <= self.half_extents.x && l.y <= self.half_extents.y && l.z <= self.half_extents.z
+	}
+
+	pub fn closest_point(&self, p: Vec3) -> Vec3 {
+		self.from_local(self.to_local(p).clamp(-self.half_extents, self.half_extents))
+	}
+
+	pub fn intersects_sphere(&self, s: &Sphere) -> bool {
+		(self.closest_point(s.center) - s.center).len_sq() <= s.radius * s.radius
+	}
+
+	// Separating axis test over the 15 candidate axes
+	pub fn intersects(&self, o: &Obb) -> bool {
+		let a = &self.axes;
+		let b = &o.axes;
+		let ea = self.half_extents;
+		let eb = o.half_extents;
+		let d = o.center - self.center;
+
+		let separated = |axis: Vec3| {
+			if axis.len_sq() < 1e-6 { return false }
+			let ra = ea.x * a[0].dot(axis).abs() + ea.y * a[1].dot(axis).abs() + ea.z * a[2].dot(axis).abs();
+			let rb = eb.x * b[0].dot(axis).abs() + eb.y * b[1].dot(axis).abs() + eb.z * b[2].dot(axis).abs();
+			d.dot(axis).abs() > ra + rb
+		};
+
+		for i in 0..3 {
+			if separated(a[i]) || separated(b[i]) { return false }
+		}
+
+		for i in 0..3 {
+			for j in 0..3 {
+				if separated(a[i].cross(b[j])) { return false }
+			}
+		}
+
+		true
+	}
+
+	pub fn aabb(&self) -> Aabb {
+		let e = self.half_extents;
+		let a = &self.axes;
+		Aabb::from_center(self.center, a[0].abs() * e.x + a[1].abs() * e.y + a[2].abs() * e.z)
+	}
+
+	pub fn transformed(&self, m: &Mat4) -> Obb {
+		let local = Mat3::from_rows(
+			self.axes[0] * self.half_extents.x,
+			self.axes[1] * self.half_extents.y,
+			self.axes[2] * self.half_extents.z
+		).to_mat4();
+
+		let mut world = local * *m;
+		world.rows[3] = m.transform_point(self.center).extend(1.0);
+		Obb::from_aabb(&Aabb::from_center(Vec3::zero(), Vec3::uniform(1.0)), &world)
+	}
+}
+
+impl Raycast for Obb {
+	fn raycast(&self, ray: &Ray) -> Option<f32> {
+		let dir = Vec3::new(ray.dir.dot(self.axes[0]), ray.dir.dot(self.axes[1]), ray.dir.dot(self.axes[2]));
+		let local = Ray::new(self.to_local(ray.origin), dir);
+		Aabb::from_center(Vec3::zero(), self.half_extents).raycast(&local)
+	}
+}
+
+// Planes face inwards, in the order left, right, bottom, top, near, far
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+	pub planes: [Plane; 6],
+}
+
+impl Frustum {
+	// From a view-projection matrix, giving a world space frustum
+	pub fn from_matrix(m: &Mat4) -> Frustum {
+		let col = |j: usize| Vec4::new(m.rows[0][j], m.rows[1][j], m.rows[2][j], m.rows[3][j]);
+		let (x, y, z, w) = (col(0), col(1), col(2), col(3));
+
+		Frustum {
+			planes: [
+				Plane::from_vec4(w + x),
+				Plane::from_vec4(w - x),
+				Plane::from_vec4(w + y),
+				Plane::from_vec4(w - y),
+				Plane::from_vec4(w + z),
+				Plane::from_vec4(w - z),
+			]
+		}
+	}
+
+	pub fn contains_point(&self, p: Vec3) -> bool {
+		self.planes.iter().all(|pl| pl.signed_distance(p) >= 0.0)
+	}
+
+	// Conservative: some spheres just outside a corner still count
+	pub fn intersects_sphere(&self, s: &Sphere) -> bool {
+		self.planes.iter().all(|pl| pl.signed_distance(s.center) >= -s.radius)
+	}
+
+	// Conservative in the same way as `intersects_sphere`
+	pub fn intersects_aabb(&self, b: &Aabb) -> bool {
+		self.planes.iter().all(|pl| {
+			// The corner furthest along the plane normal
+			let n = pl.normal;
+			let p = Vec3::new(
+				if n.x >= 0.0 { b.max.x } else { b.min.x },
+				if n.y >= 0.0 { b.max.y } else { b.min.y },
+				if n.z >= 0.0 { b.max.z } else { b.min.z }
+			);
+			pl.signed_distance(p) >= 0.0
+		})
+	}
+
+	pub fn contains_aabb(&self, b: &Aabb) -> bool {
+		b.corners().iter().all(|&c| self.contains_point(c))
+	}
+
+	pub fn transformed(&self, m: &Mat4) -> Frustum {
+		let mut f = *self;
+		for p in f.planes.iter_mut() {
+			*p = p.transformed(m);
+		}
+		f
+	}
+}
+
+// Clips the ray against each inward facing plane in turn
+impl Raycast for Frustum {
+	fn raycast(&self, ray: &Ray) -> Option<f32> {
+		let mut tmin = 0.0f32;
+		let mut tmax = f32::INFINITY;
+
+		for pl in self.planes.iter() {
+			let dist = pl.signed_distance(ray.origin);
+			let speed = pl.normal.dot(ray.dir);
+
+			if speed == 0.0 {
+				if dist < 0.0 { return None }
+				continue
+			}
+
+			let t = -dist / speed;
+			if speed > 0.0 { tmin = tmin.max(t) } else { tmax = tmax.min(t) }
+		}
+
+		if tmax >= tmin { Some(tmin) } else { None }
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Triangle {
+	pub a: Vec3,
+	pub b: Vec3,
+	pub c: Vec3,
+}
+
+impl Triangle {
+	pub fn new(a: Vec3, b: Vec3, c: Vec3) -> Triangle {
+		Triangle { a, b, c }
+	}
+
+	// Faces towards the side the vertices wind counter-clockwise from
+	pub fn normal(&self) -> Vec3 {
+		(self.b - self.a).cross(self.c - self.a).normalized()
+	}
+
+	pub fn area(&self) -> f32 {
+		(self.b - self.a).cross(self.c - self.a).len() * 0.5
+	}
+
+	pub fn centroid(&self) -> Vec3 {
+		(self.a + self.b + self.c) * (1.0 / 3.0)
+	}
+
+	pub fn plane(&self) -> Plane {
+		Plane::from_points(self.a, self.b, self.c)
+	}
+
+	// Weights of a, b and c for `p` projected onto the triangle's plane
+	pub fn barycentric(&self, p: Vec3) -> Vec3 {
+		let v0 = self.b - self.a;
+		let v1 = self.c - self.a;
+		let v2 = p - self.a;
+
+		let d00 = v0.dot(v0);
+		let d01 = v0.dot(v1);
+		let d11 = v1.dot(v1);
+		let d20 = v2.dot(v0);
+		let d21 = v2.dot(v1);
+		let denom = d00 * d11 - d01 * d01;
+
+		let v = (d11 * d20 - d01 * d21) / denom;
+		let w = (d00 * d21 - d01 * d20) / denom;
+		Vec3::new(1.0 - v - w, v, w)
+	}
+
+	pub fn from_barycentric(&self, bary: Vec3) -> Vec3 {
+		self.a * bary.x + self.b * bary.y + self.c * bary.z
+	}
+
+	// Double sided. Also returns the barycentric coordinates of the hit
+	pub fn raycast_barycentric(&self, ray: &Ray) -> Option<(f32, Vec3)> {
+		let e1 = self.b - self.a;
+		let e2 = self.c - self.a;
+		let p = ray.dir.cross(e2);
+		let det = e1.dot(p);
+		if det.abs() <= f32::EPSILON { return None }
+
+		let inv = 1.0 / det;
+		let s = ray.origin - self.a;
+		let u = s.dot(p) * inv;
+		if u < 0.0 || u > 1.0 { return None }
+
+		let q = s.cross(e1);
+		let v = ray.dir.dot(q) * inv;
+		if v < 0.0 || u + v > 1.0 { return None }
+
+		let t = e2.dot(q) * inv;
+		if t < 0.0 { return None }
+
+		Some((t, Vec3::new(1.0 - u - v, u, v)))
+	}
+
+	pub fn aabb(&self) -> Aabb {
+		Aabb::from_points(&[self.a, self.b, self.c])
+	}
+
+	pub fn transformed(&self, m: &Mat4) -> Triangle {
+		Triangle::new(m.transform_point(self.a), m.transform_point(self.b), m.transform_point(self.c))
+	}
+}
+
+impl Raycast for Triangle {
+	fn raycast(&self, ray: &Ray) -> Option<f32> {
+		self.raycast_barycentric(ray).map(|(t, _)| t)
+	}
+}
+
+// Segment from `a` to `b` swept by `radius`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Capsule {
+	pub a: Vec3,
+	pub b: Vec3,
+	pub radius: f32,
+}
+
+impl Capsule {
+	pub fn new(a: Vec3, b: Vec3, radius: f32) -> Capsule {
+		Capsule { a, b, radius }
+	}
+
+	pub fn closest_point_on_axis(&self, p: Vec3) -> Vec3 {
+		closest_point_on_segment(self.a, self.b, p)
+	}
+
+	pub fn contains_point(&self, p: Vec3) -> bool {
+		(p - self.closest_point_on_axis(p)).len_sq() <= self.radius * self.radius
+	}
+
+	pub fn intersects_sphere(&self, s: &Sphere) -> bool {
+		let r = self.radius + s.radius;
+		(s.center - self.closest_point_on_axis(s.center)).len_sq() <= r * r
+	}
+
+	pub fn intersects(&self, o: &Capsule) -> bool {
+		let (p, q) = closest_points_on_segments(self.a, self.b, o.a, o.b);
+		let r = self.radius + o.radius;
+		(q - p).len_sq() <= r * r
+	}
+
+	pub fn aabb(&self) -> Aabb {
+		Aabb::new(self.a.min(self.b), self.a.max(self.b)).expanded(self.radius)
+	}
+
+	pub fn transformed(&self, m: &Mat4) -> Capsule {
+		Capsule::new(m.transform_point(self.a), m.transform_point(self.b), self.radius * max_scale(m))
+	}
+}
+
+impl Raycast for Capsule {
+	fn raycast(&self, ray: &Ray) -> Option<f32> {
+		if self.contains_point(ray.origin) { return Some(0.0) }
+
+		// The first hit on the capsule is the first hit on the body or either end cap
+		let caps = [Sphere::new(self.a, self.radius), Sphere::new(self.b, self.radius)];
+		let mut best = caps.iter()
+			.filter_map(|s| s.raycast(ray))
+			.fold(f32::INFINITY, f32::min);
+
+		let dir_len = ray.dir.len();
+		let rd = ray.dir * (1.0 / dir_len);
+		let ba = self.b - self.a;
+		let oa = ray.origin - self.a;
+
+		let baba = ba.len_sq();
+		let bard = ba.dot(rd);
+		let baoa = ba.dot(oa);
+
+		let a = baba - bard * bard;
+		let b = baba * rd.dot(oa) - baoa * bard;
+		let c = baba * oa.len_sq() - baoa * baoa - self.radius * self.radius * baba;
+		let h = b * b - a * c;
+
+		if a > f32::EPSILON && h >= 0.0 {
+			let t = (-b - h.sqrt()) / a;
+			let y = baoa + t * bard;
+			if t >= 0.0 && y > 0.0 && y < baba {
+				best = best.min(t / dir_len);
+			}
+		}
+
+		if best.is_finite() { Some(best) } else { None }
+	}
+}
+
+// Axis aligned 2D rectangle, for UI and screen space work
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+	pub min: Vec2,
+	pub max: Vec2,
+}
+
+impl Rect {
+	pub fn new(min: Vec2, max: Vec2) -> Rect {
+		Rect { min, max }
+	}
+
+	pub fn from_pos_size(pos: Vec2, size: Vec2) -> Rect {
+		Rect::new(pos, pos + size)
+	}
+
+	pub fn empty() -> Rect {
+		Rect::new(Vec2::uniform(f32::INFINITY), Vec2::uniform(f32::NEG_INFINITY))
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.min.x > self.max.x || self.min.y > self.max.y
+	}
+
+	pub fn width(&self) -> f32 { self.max.x - self.min.x }
+	pub fn height(&self) -> f32 { self.max.y - self.min.y }
+	pub fn size(&self) -> Vec2 { self.max - self.min }
+	pub fn center(&self) -> Vec2 { (self.min + self.max) * 0.5 }
+	pub fn area(&self) -> f32 { self.width() * self.height() }
+
+	pub fn contains_point(&self, p: Vec2) -> bool {
+		p.x >= self.min.x && p.x <= self.max.x && p.y >= self.min.y && p.y <= self.max.y
+	}
+
+	pub fn contains(&self, o: &Rect) -> bool {
+		self.contains_point(o.min) && self.contains_point(o.max)
+	}
+
+	pub fn intersects(&self, o: &Rect) -> bool {
+		self.min.x <= o.max.x && self.max.x >= o.min.x && self.min.y <= o.max.y && self.max.y >= o.min.y
+	}
+
+	pub fn intersection(&self, o: &Rect) -> Option<Rect> {
+		let r = Rect::new(self.min.max(o.min), self.max.min(o.max));
+		if r.is_empty() { None } else { Some(r) }
+	}
+
+	pub fn merged(&self, o: &Rect) -> Rect {
+		Rect::new(self.min.min(o.min), self.max.max(o.max))
+	}
+
+	pub fn expanded_to(&self, p: Vec2) -> Rect {
+		Rect::new(self.min.min(p), self.max.max(p))
+	}
+
+	pub fn expanded(&self, amount: f32) -> Rect {
+		Rect::new(self.min - amount, self.max + amount)
+	}
+
+	pub fn clamp_point(&self, p: Vec2) -> Vec2 {
+		p.clamp(self.min, self.max)
+	}
+
+	// Bounds of the transformed rectangle
+	pub fn transformed(&self, m: &Mat3) -> Rect {
+		let corners = [
+			self.min,
+			Vec2::new(self.max.x, self.min.y),
+			Vec2::new(self.min.x, self.max.y),
+			self.max,
+		];
+		corners.iter().fold(Rect::empty(), |r, &c| r.expanded_to(c * *m))
+	}
+}
+
+// In the xy plane: the ray's z is ignored, as if the rectangle extended infinitely along z
+impl Raycast for Rect {
+	fn raycast(&self, ray: &Ray) -> Option<f32> {
+		let (o, d) = (ray.origin, ray.dir);
+		slab_raycast(&[o.x, o.y], &[d.x, d.y], &[self.min.x, self.min.y], &[self.max.x, self.max.y])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use quat::*;
+	use std::f32::consts::FRAC_PI_4;
+
+	fn v(x: f32, y: f32, z: f32) -> Vec3 { Vec3::new(x, y, z) }
+
+	fn assert_hit<T: Raycast>(shape: &T, ray: Ray, t: f32) {
+		match shape.raycast(&ray) {
+			Some(hit) => assert!((hit - t).abs() < 1e-4, "hit at {}, expected {}", hit, t),
+			None => panic!("{:?} missed", ray)
+		}
+	}
+
+	fn assert_miss<T: Raycast>(shape: &T, ray: Ray) {
+		assert_eq!(shape.raycast(&ray), None, "{:?}", ray);
+	}
+
+	fn unit_box() -> Aabb { Aabb::new(v(-1.0, -1.0, -1.0), v(1.0, 1.0, 1.0)) }
+
+	fn camera_frustum() -> Frustum {
+		let view = Mat4::look_at(v(0.0, 0.0, 5.0), Vec3::zero(), Vec3::from_y(1.0));
+		Frustum::from_matrix(&(view * Mat4::perspective(FRAC_PI_4 * 2.0, 1.0, 1.0, 100.0)))
+	}
+
+	#[test]
+	fn plane() {
+		let p = Plane::new(Vec3::from_y(1.0), 2.0);
+		assert_hit(&p, Ray::new(v(0.0, 5.0, 0.0), v(0.0, -1.0, 0.0)), 3.0);
+		assert_hit(&p, Ray::new(v(0.0, 0.0, 0.0), v(0.0, 2.0, 0.0)), 1.0);
+		assert_miss(&p, Ray::new(v(0.0, 5.0, 0.0), v(0.0, 1.0, 0.0)));
+		assert_miss(&p, Ray::new(v(0.0, 5.0, 0.0), v(1.0, 0.0, 0.0)));
+		assert_hit(&p, Ray::new(v(0.0, 2.0, 0.0), v(0.0, 1.0, 0.0)), 0.0);
+
+		assert!((p.signed_distance(v(3.0, 5.0, 1.0)) - 3.0).abs() < 1e-6);
+		assert!(p.project_point(v(3.0, 5.0, 1.0)).approx_eq(v(3.0, 2.0, 1.0), 1e-6));
+		let moved = p.transformed(&Mat4::translation(v(0.0, 1.0, 0.0)));
+		assert!((moved.dist - 3.0).abs() < 1e-5);
+	}
+
+	#[test]
+	fn aabb_raycast() {
+		let b = unit_box();
+		assert_hit(&b, Ray::new(v(-5.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), 4.0);
+		assert_hit(&b, Ray::new(v(-5.0, -5.0, 0.0), v(1.0, 1.0, 0.0)), 4.0);
+		assert_hit(&b, Ray::new(v(0.5, 0.0, 0.0), v(0.0, 0.0, 1.0)), 0.0);
+		assert_miss(&b, Ray::new(v(-5.0, 2.0, 0.0), v(1.0, 0.0, 0.0)));
+		assert_miss(&b, Ray::new(v(5.0, 0.0, 0.0), v(1.0, 0.0, 0.0)));
+	}
+
+	#[test]
+	fn aabb_raycast_in_a_face_plane() {
+		let b = unit_box();
+		assert_hit(&b, Ray::new(v(-5.0, 1.0, 0.0), v(1.0, 0.0, 0.0)), 4.0);
+		assert_hit(&b, Ray::new(v(-5.0, 1.0, -1.0), v(1.0, 0.0, 0.0)), 4.0);
+		assert_miss(&b, Ray::new(v(-5.0, 1.001, 0.0), v(1.0, 0.0, 0.0)));
+	}
+
+	#[test]
+	fn aabb_overlap() {
+		let a = unit_box();
+		assert!(a.intersects(&Aabb::new(v(0.5, 0.5, 0.5), v(3.0, 3.0, 3.0))));
+		assert!(a.intersects(&Aabb::new(v(1.0, -1.0, -1.0), v(2.0, 1.0, 1.0))));
+		assert!(!a.intersects(&Aabb::new(v(1.5, 0.0, 0.0), v(2.0, 1.0, 1.0))));
+		assert!(!a.intersects(&Aabb::new(v(-0.5, 2.0, -0.5), v(0.5, 3.0, 0.5))));
+
+		assert!(a.contains(&Aabb::new(v(-0.5, -0.5, -0.5), v(0.5, 0.5, 0.5))));
+		assert!(!a.contains(&Aabb::new(v(-0.5, -0.5, -0.5), v(1.5, 0.5, 0.5))));
+		assert!(Aabb::empty().is_empty());
+		assert_eq!(Aabb::empty().merged(&a), a);
+	}
+
+	#[test]
+	fn aabb_transformed() {
+		let b = unit_box().transformed(&Mat4::trs(v(10.0, 0.0, 0.0), Quat::new(Vec3::from_z(1.0), FRAC_PI_4), Vec3::uniform(1.0)));
+		let r = 2.0f32.sqrt();
+		assert!(b.min.approx_eq(v(10.0 - r, -r, -1.0), 1e-5));
+		assert!(b.max.approx_eq(v(10.0 + r, r, 1.0), 1e-5));
+	}
+
+	#[test]
+	fn sphere_raycast() {
+		let s = Sphere::new(v(0.0, 0.0, -10.0), 2.0);
+		assert_hit(&s, Ray::new(Vec3::zero(), v(0.0, 0.0, -1.0)), 8.0);
+		assert_hit(&s, Ray::new(Vec3::zero(), v(0.0, 0.0, -2.0)), 4.0);
+		assert_hit(&s, Ray::new(v(0.0, 1.0, -10.0), v(1.0, 0.0, 0.0)), 0.0);
+		assert_miss(&s, Ray::new(Vec3::zero(), v(0.0, 0.0, 1.0)));
+		assert_miss(&s, Ray::new(v(0.0, 3.0, 0.0), v(0.0, 0.0, -1.0)));
+	}
+
+	#[test]
+	fn obb_raycast() {
+		// A 2x2x2 box turned 45 degrees about y, so its corner points along x
+		let o = Obb::from_aabb(&unit_box(), &Mat4::trs(v(0.0, 0.0, 0.0), Quat::new(Vec3::from_y(1.0), FRAC_PI_4), Vec3::uniform(1.0)));
+		assert_hit(&o, Ray::new(v(-5.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), 5.0 - 2.0f32.sqrt());
+		assert_hit(&o, Ray::new(v(0.5, 0.5, 0.0), v(0.0, 1.0, 0.0)), 0.0);
+		assert_miss(&o, Ray::new(v(-5.0, 0.0, 1.5), v(1.0, 0.0, 0.0)));
+		assert_miss(&o, Ray::new(v(-5.0, 1.5, 0.0), v(1.0, 0.0, 0.0)));
+
+		assert!(o.contains_point(v(1.3, 0.0, 0.0)));
+		assert!(!o.contains_point(v(1.0, 0.0, 1.0)));
+	}
+
+	#[test]
+	fn frustum_raycast() {
+		let f = camera_frustum();
+		assert_hit(&f, Ray::new(v(0.0, 0.0, 10.0), v(0.0, 0.0, -1.0)), 6.0);
+		assert_hit(&f, Ray::new(v(0.0, 0.0, 0.0), v(0.0, 0.0, -1.0)), 0.0);
+		assert_miss(&f, Ray::new(v(0.0, 0.0, 10.0), v(0.0, 0.0, 1.0)));
+		assert_miss(&f, Ray::new(v(0.0, 500.0, 10.0), v(0.0, 0.0, -1.0)));
+	}
+
+	#[test]
+	fn frustum_culling() {
+		let f = camera_frustum();
+		assert!(f.contains_point(Vec3::zero()));
+		assert!(!f.contains_point(v(0.0, 0.0, 6.0)));
+
+		assert!(f.intersects_sphere(&Sphere::new(Vec3::zero(), 1.0)));
+		assert!(f.intersects_sphere(&Sphere::new(v(0.0, 0.0, 4.5), 1.0)));
+		assert!(!f.intersects_sphere(&Sphere::new(v(0.0, 0.0, 10.0), 1.0)));
+		assert!(!f.intersects_sphere(&Sphere::new(v(20.0, 0.0, 0.0), 1.0)));
+		assert!(!f.intersects_sphere(&Sphere::new(v(0.0, 0.0, -200.0), 1.0)));
+
+		assert!(f.intersects_aabb(&unit_box()));
+		assert!(f.contains_aabb(&unit_box()));
+		assert!(!f.intersects_aabb(&Aabb::from_center(v(-20.0, 0.0, 0.0), Vec3::uniform(1.0))));
+	}
+
+	#[test]
+	fn triangle_raycast() {
+		let t = Triangle::new(v(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(0.0, 1.0, 0.0));
+		assert_hit(&t, Ray::new(v(0.25, 0.25, 5.0), v(0.0, 0.0, -1.0)), 5.0);
+		assert_hit(&t, Ray::new(v(0.25, 0.25, -5.0), v(0.0, 0.0, 1.0)), 5.0);
+		assert_hit(&t, Ray::new(v(0.25, 0.25, 0.0), v(0.0, 0.0, 1.0)), 0.0);
+		assert_miss(&t, Ray::new(v(0.75, 0.75, 5.0), v(0.0, 0.0, -1.0)));
+		assert_miss(&t, Ray::new(v(0.25, 0.25, 5.0), v(1.0, 0.0, 0.0)));
+
+		let (_, bary) = t.raycast_barycentric(&Ray::new(v(0.25, 0.5, 1.0), v(0.0, 0.0, -1.0))).unwrap();
+		assert!(bary.approx_eq(v(0.25, 0.25, 0.5), 1e-6));
+	}
+
+	#[test]
+	fn barycentrics() {
+		let t = Triangle::new(v(1.0, 0.0, 0.0), v(3.0, 0.0, 0.0), v(1.0, 4.0, 2.0));
+		assert!(t.barycentric(t.a).approx_eq(v(1.0, 0.0, 0.0), 1e-6));
+		assert!(t.barycentric(t.b).approx_eq(v(0.0, 1.0, 0.0), 1e-6));
+		assert!(t.barycentric(t.c).approx_eq(v(0.0, 0.0, 1.0), 1e-6));
+		assert!(t.barycentric(t.centroid()).approx_eq(Vec3::uniform(1.0 / 3.0), 1e-6));
+
+		let w = v(0.2, 0.3, 0.5);
+		assert!(t.barycentric(t.from_barycentric(w)).approx_eq(w, 1e-5));
+
+		// Outside the triangle a weight goes negative
+		assert!(t.barycentric(v(4.0, -1.0, 0.0)).min_element() < 0.0);
+	}
+
+	#[test]
+	fn capsule_raycast() {
+		let c = Capsule::new(v(0.0, -1.0, 0.0), v(0.0, 1.0, 0.0), 0.5);
+		assert_hit(&c, Ray::new(v(-5.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), 4.5);
+		assert_hit(&c, Ray::new(v(0.0, 5.0, 0.0), v(0.0, -1.0, 0.0)), 3.5);
+		assert_hit(&c, Ray::new(v(-5.0, 0.0, 0.0), v(2.0, 0.0, 0.0)), 2.25);
+		assert_hit(&c, Ray::new(v(0.0, 1.2, 0.0), v(1.0, 0.0, 0.0)), 0.0);
+		assert_miss(&c, Ray::new(v(-5.0, 1.6, 0.0), v(1.0, 0.0, 0.0)));
+		assert_miss(&c, Ray::new(v(-5.0, 0.0, 0.0), v(-1.0, 0.0, 0.0)));
+	}
+
+	#[test]
+	fn rect_raycast() {
+		let r = Rect::new(Vec2::new(0.0, 0.0), Vec2::new(4.0, 2.0));
+		assert_hit(&r, Ray::new(v(-2.0, 1.0, 7.0), v(1.0, 0.0, 0.0)), 2.0);
+		assert_hit(&r, Ray::new(v(-2.0, 2.0, 0.0), v(1.0, 0.0, 3.0)), 2.0);
+		assert_hit(&r, Ray::new(v(1.0, 1.0, 0.0), v(0.0, 0.0, 1.0)), 0.0);
+		assert_miss(&r, Ray::new(v(-2.0, 3.0, 0.0), v(1.0, 0.0, 0.0)));
+		assert_miss(&r, Ray::new(v(-2.0, 1.0, 0.0), v(0.0, 0.0, 1.0)));
+
+		assert_eq!(r.intersection(&Rect::new(Vec2::new(3.0, 1.0), Vec2::new(5.0, 5.0))), Some(Rect::new(Vec2::new(3.0, 1.0), Vec2::new(4.0, 2.0))));
+		assert_eq!(r.intersection(&Rect::new(Vec2::new(5.0, 0.0), Vec2::new(6.0, 1.0))), None);
+	}
+
+	#[test]
+	fn segments() {
+		let (p, q) = closest_points_on_segments(v(-1.0, 0.0, 0.0), v(1.0, 0.0, 0.0), v(0.0, -1.0, 1.0), v(0.0, 1.0, 1.0));
+		assert!(p.approx_eq(Vec3::zero(), 1e-6));
+		assert!(q.approx_eq(v(0.0, 0.0, 1.0), 1e-6));
+
+		assert!(closest_point_on_segment(v(0.0, 0.0, 0.0), v(2.0, 0.0, 0.0), v(5.0, 1.0, 0.0)).approx_eq(v(2.0, 0.0, 0.0), 1e-6));
+	}
+}
\ No newline at end of file
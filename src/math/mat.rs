@@ -1,10 +1,28 @@
-use std::ops::{ Mul, Index, IndexMut };
+use std::ops::{ Mul, MulAssign, Index, IndexMut };
 use vec::*;
-
+use quat::*;
+use simd;
+
+// Conventions for every matrix type here:
+//	- Vectors are rows and multiply on the left, so `v * a * b` applies `a` then `b`,
+//	  and a model-view-projection is `model * view * proj`. The old `a * v` is deprecated
+//	- `rows[3]` of an affine Mat4 holds the translation
+//	- Right handed: +x right, +y up, the camera looks down -z
+//	- Positive angles rotate counter-clockwise when looking down the axis towards the origin
+//	- Projections map depth to GL's [-1, 1] clip range
+// Uploaded untransposed, a matrix reads in GLSL as its column-major equivalent, so `mvp * pos` works there
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Mat4 { pub rows: [Vec4; 4] }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Mat3 { pub rows: [Vec3; 3] }
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Mat2 { pub rows: [Vec2; 2] }
+
 impl Index<usize> for Mat4 {
 	type Output = Vec4;
 	fn index(&self, i: usize) -> &Vec4 {
@@ -78,8 +96,8 @@ impl Mat4 {
 		let (s, c) = a.sin_cos();
 		Mat4::new(&[
 			1.0, 0.0, 0.0, 0.0,
-			0.0,   c,  -s, 0.0,
-			0.0,   s,   c, 0.0,
+			0.0,   c,   s, 0.0,
+			0.0,  -s,   c, 0.0,
 			0.0, 0.0, 0.0, 1.0
 		])
 	}
@@ -97,8 +115,8 @@ impl Mat4 {
 	pub fn rotation_z(a: f32) -> Mat4 {
 		let (s, c) = a.sin_cos();
 		Mat4::new(&[
-			  c,  -s, 0.0, 0.0,
-			  s,   c, 0.0, 0.0,
+			  c,   s, 0.0, 0.0,
+			 -s,   c, 0.0, 0.0,
 			0.0, 0.0, 1.0, 0.0,
 			0.0, 0.0, 0.0, 1.0
 		])
@@ -112,9 +130,9 @@ impl Mat4 {
 		let y = ax.y;
 		let z = ax.z;
 		Mat4::new(&[
-			t * x * x + c, t * x * y - z * s, t * x * z + y * s, 0.0,
-			t * x * y + z * s, t * y * y + c, t * y * z - x * s, 0.0,
-			t * x * z - y * s, t * y * z + x * s, t * z * z + c, 0.0,
+			t * x * x + c, t * x * y + z * s, t * x * z - y * s, 0.0,
+			t * x * y - z * s, t * y * y + c, t * y * z + x * s, 0.0,
+			t * x * z + y * s, t * y * z - x * s, t * z * z + c, 0.0,
 			0.0, 0.0, 0.0, 1.0
 		])
 	}
@@ -124,10 +142,10 @@ impl Mat4 {
 		let h = t - b;
 		let d = f - n;
 		Mat4::new(&[
-			2.0 / w,	 0.0,	   0.0, -(r + l) / w,
-				0.0, 2.0 / h,	   0.0, -(t + b) / h,
-				0.0,	 0.0, -2.0 / d, -(f + n) / d,
-				0.0,	 0.0,	   0.0,			 1.0,
+			 2.0 / w,	   0.0,	      0.0, 0.0,
+				 0.0,  2.0 / h,	      0.0, 0.0,
+				 0.0,	   0.0,  -2.0 / d, 0.0,
+			-(r + l) / w, -(t + b) / h, -(f + n) / d, 1.0,
 		])
 	}
 
@@ -149,27 +167,102 @@ impl Mat4 {
 		])
 	}
 
+	// `fov` is the full vertical field of view in radians, `asp` is width / height
 	pub fn perspective(fov: f32, asp: f32, n: f32, f: f32) -> Mat4 {
-		let ymax = n * fov.tan();
+		let ymax = n * (fov / 2.0).tan();
 		let xmax = ymax * asp;
-		Mat4::frustum(-xmax, xmax, -ymax, ymax, n, f)
+		Mat4::frustum(-xmax, xmax, ymax, -ymax, n, f)
 	}
 
+	// View matrix for a camera at `eye` looking towards `at`
 	pub fn look_at(eye: Vec3, at: Vec3, up: Vec3) -> Mat4 {
 		let z = (eye - at).normalized();
 		let x = up.cross(z).normalized();
 		let y = z.cross(x);
 
 		let r = Mat4::new(&[
-			x.x, x.y, -x.z, 0.0,
-			y.x, y.y, -y.z, 0.0,
-			z.x, z.y, -z.z, 0.0,
+			x.x, y.x, z.x, 0.0,
+			x.y, y.y, z.y, 0.0,
+			x.z, y.z, z.z, 0.0,
 			0.0, 0.0, 0.0, 1.0
 		]);
 
 		Mat4::translation(-eye) * r
 	}
 
+	// Scale, then rotate, then translate
+	pub fn trs(translation: Vec3, rotation: Quat, scale: Vec3) -> Mat4 {
+		let r = rotation.to_mat3();
+		Mat4::from_rows(
+			(r[0] * scale.x).extend(0.0),
+			(r[1] * scale.y).extend(0.0),
+			(r[2] * scale.z).extend(0.0),
+			translation.extend(1.0)
+		)
+	}
+
+	// Inverse of `trs`, for affine matrices without shear. A mirroring is reported as a negative x scale
+	pub fn decompose(self) -> (Vec3, Quat, Vec3) {
+		let m = self.to_mat3();
+		let mut scale = Vec3::new(m[0].len(), m[1].len(), m[2].len());
+		if m.determinant() < 0.0 {
+			scale.x = -scale.x;
+		}
+
+		let r = Mat3::from_rows(m[0] * (1.0 / scale.x), m[1] * (1.0 / scale.y), m[2] * (1.0 / scale.z));
+		(self.rows[3].as_vec3(), Quat::from_mat3(&r), scale)
+	}
+
+	pub fn translation_part(self) -> Vec3 { self.rows[3].as_vec3() }
+
+	pub fn is_affine(self) -> bool {
+		self.rows[0].w == 0.0 && self.rows[1].w == 0.0 && self.rows[2].w == 0.0 && self.rows[3].w == 1.0
+	}
+
+	pub fn determinant(self) -> f32 {
+		let [a, b, c, d] = self.rows;
+
+		// Expansion by 2x2 minors of the top and bottom halves
+		let s0 = a.x * b.y - b.x * a.y;
+		let s1 = a.x * b.z - b.x * a.z;
+		let s2 = a.x * b.w - b.x * a.w;
+		let s3 = a.y * b.z - b.y * a.z;
+		let s4 = a.y * b.w - b.y * a.w;
+		let s5 = a.z * b.w - b.z * a.w;
+
+		let c5 = c.z * d.w - d.z * c.w;
+		let c4 = c.y * d.w - d.y * c.w;
+		let c3 = c.y * d.z - d.y * c.z;
+		let c2 = c.x * d.w - d.x * c.w;
+		let c1 = c.x * d.z - d.x * c.z;
+		let c0 = c.x * d.y - d.x * c.y;
+
+		s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
+	}
+
+	// Cheaper than `inverted` when the matrix is known to be affine
+	pub fn affine_inverted(self) -> Mat4 {
+		let r = self.to_mat3().inverted();
+		let t = -(self.translation_part() * r);
+		Mat4::from_rows(
+			r[0].extend(0.0),
+			r[1].extend(0.0),
+			r[2].extend(0.0),
+			t.extend(1.0)
+		)
+	}
+
+	// Transforms a position, dividing by w afterwards
+	pub fn transform_point(self, p: Vec3) -> Vec3 {
+		let v = p.extend(1.0) * self;
+		v.as_vec3() * (1.0 / v.w)
+	}
+
+	// Transforms a direction, ignoring translation
+	pub fn transform_vector(self, v: Vec3) -> Vec3 {
+		(v.extend(0.0) * self).as_vec3()
+	}
+
 	pub fn transposed(self) -> Mat4 {
 		let [a, b, c, d] = self.rows;
 		Mat4::new(&[
@@ -181,6 +274,34 @@ impl Mat4 {
 	}
 
 	pub fn inverted(self) -> Mat4 {
+		simd::inverted(&self)
+	}
+
+	// Transforms every vector in place, as `v * self`
+	pub fn transform_vec4s(&self, vs: &mut [Vec4]) {
+		simd::transform_vec4s(self, vs)
+	}
+
+	// The portable implementations, also used when the `simd` feature is off
+
+	pub fn mul_scalar(&self, rhs: &Mat4) -> Mat4 {
+		let mut d = [0.0f32; 16];
+		let ot = rhs.transposed();
+
+		for j in 0..4 {
+			for i in 0..4 {
+				d[i + j * 4] = self.rows[j].dot(ot.rows[i]);
+			}
+		}
+
+		Mat4::new(&d)
+	}
+
+	pub fn mul_vec4_scalar(&self, v: Vec4) -> Vec4 {
+		self.rows[0] * v.x + self.rows[1] * v.y + self.rows[2] * v.z + self.rows[3] * v.w
+	}
+
+	pub fn inverted_scalar(&self) -> Mat4 {
 		//
 		// Inversion by Cramer's rule.  Code taken from an Intel publication
 		//
@@ -270,6 +391,16 @@ impl Mat4 {
 		mat
 	}
 
+	// Upper-left 3x3, dropping translation
+	pub fn to_mat3(self) -> Mat3 {
+		Mat3::from_rows(self.rows[0].as_vec3(), self.rows[1].as_vec3(), self.rows[2].as_vec3())
+	}
+
+	// For transforming normals by a model matrix with non-uniform scale
+	pub fn normal_matrix(self) -> Mat3 {
+		self.to_mat3().inverted().transposed()
+	}
+
 	pub fn clone(self) -> Mat4 {
 		let [a, b, c, d] = self.rows;
 		Mat4::new(&[
@@ -284,40 +415,52 @@ impl Mat4 {
 impl Mul<Mat4> for Mat4 {
 	type Output = Mat4;
 	fn mul(self, rhs: Mat4) -> Mat4 {
-		let mut d = [0.0f32; 16];
-		let ot = rhs.transposed();
+		simd::mul_mat4(&self, &rhs)
+	}
+}
 
-		for j in 0..4 {
-			for i in 0..4 {
-				d[i + j * 4] = self.rows[j].dot(ot.rows[i]);
-			}
-		}
+impl<'a> Mul<&'a Mat4> for &'a Mat4 {
+	type Output = Mat4;
+	fn mul(self, rhs: &Mat4) -> Mat4 {
+		*self * *rhs
+	}
+}
 
-		Mat4::new(&d)
+impl MulAssign<Mat4> for Mat4 {
+	fn mul_assign(&mut self, rhs: Mat4) {
+		*self = *self * rhs;
+	}
+}
+
+impl Mul<Mat4> for Vec4 {
+	type Output = Vec4;
+	fn mul(self, m: Mat4) -> Vec4 {
+		simd::mul_vec4(self, &m)
 	}
 }
 
+// Treats the Vec3 as a point with w = 1, without the perspective divide
+impl Mul<Mat4> for Vec3 {
+	type Output = Vec3;
+	fn mul(self, m: Mat4) -> Vec3 {
+		(self.extend(1.0) * m).as_vec3()
+	}
+}
+
+// Deprecated: `m * v` is kept only so code written against the old column-vector operators still builds,
+// and gives the same result as before for the rotation and ortho constructors. Write `v * m` instead.
+// (`#[deprecated]` can't be put on a trait impl, so this comment is all there is)
 impl Mul<Vec4> for Mat4 {
 	type Output = Vec4;
-	fn mul(self, rhs: Vec4) -> Vec4 {
-		Vec4::new(
-			self.rows[0].dot(rhs),
-			self.rows[1].dot(rhs),
-			self.rows[2].dot(rhs),
-			self.rows[3].dot(rhs),
-		)
+	fn mul(self, v: Vec4) -> Vec4 {
+		v * self
 	}
 }
 
 impl Mul<Vec3> for Mat4 {
 	type Output = Vec3;
-	fn mul(self, rhs: Vec3) -> Vec3 {
-		let v = rhs.extend(1.0);
-		Vec3::new(
-			self.rows[0].dot(v),
-			self.rows[1].dot(v),
-			self.rows[2].dot(v)
-		)
+	fn mul(self, v: Vec3) -> Vec3 {
+		v * self
 	}
 }
 
@@ -331,4 +474,435 @@ impl Mul<f32> for Mat4 {
 			self.rows[3] * rhs
 		)
 	}
+}
+
+impl Index<usize> for Mat3 {
+	type Output = Vec3;
+	fn index(&self, i: usize) -> &Vec3 {
+		&self.rows[i]
+	}
+}
+
+impl IndexMut<usize> for Mat3 {
+	fn index_mut(&mut self, i: usize) -> &mut Vec3 {
+		&mut self.rows[i]
+	}
+}
+
+// 2D transforms match the upper-left of their Mat4 counterparts, with translation in the last row
+impl Mat3 {
+	pub fn as_ptr(&self) -> *const f32 {
+		&self.rows[0].x
+	}
+
+	pub fn new(m: &[f32; 9]) -> Mat3 {
+		Mat3 {
+			rows: [
+				Vec3::new(m[0], m[1], m[2]),
+				Vec3::new(m[3], m[4], m[5]),
+				Vec3::new(m[6], m[7], m[8]),
+			]
+		}
+	}
+
+	pub fn from_rows(r0: Vec3, r1: Vec3, r2: Vec3) -> Mat3 {
+		Mat3 { rows: [ r0, r1, r2 ] }
+	}
+
+	pub fn from_mat2(m: Mat2) -> Mat3 {
+		Mat3::from_rows(m.rows[0].extend(0.0), m.rows[1].extend(0.0), Vec3::new(0.0, 0.0, 1.0))
+	}
+
+	pub fn ident() -> Mat3 {
+		Mat3::new(&[
+			1.0, 0.0, 0.0,
+			0.0, 1.0, 0.0,
+			0.0, 0.0, 1.0
+		])
+	}
+
+	pub fn translation_2d(v: Vec2) -> Mat3 {
+		Mat3::new(&[
+			1.0, 0.0, 0.0,
+			0.0, 1.0, 0.0,
+			v.x, v.y, 1.0
+		])
+	}
+
+	pub fn rotation_2d(a: f32) -> Mat3 {
+		let (s, c) = a.sin_cos();
+		Mat3::new(&[
+			  c,   s, 0.0,
+			 -s,   c, 0.0,
+			0.0, 0.0, 1.0
+		])
+	}
+
+	pub fn scaling_2d(v: Vec2) -> Mat3 {
+		Mat3::new(&[
+			v.x, 0.0, 0.0,
+			0.0, v.y, 0.0,
+			0.0, 0.0, 1.0
+		])
+	}
+
+	pub fn scaling(v: Vec3) -> Mat3 {
+		Mat3::new(&[
+			v.x, 0.0, 0.0,
+			0.0, v.y, 0.0,
+			0.0, 0.0, v.z
+		])
+	}
+
+	// Embeds into a Mat4 with no translation
+	pub fn to_mat4(self) -> Mat4 {
+		Mat4::from_rows(
+			self.rows[0].extend(0.0),
+			self.rows[1].extend(0.0),
+			self.rows[2].extend(0.0),
+			Vec4::new(0.0, 0.0, 0.0, 1.0)
+		)
+	}
+
+	pub fn to_mat2(self) -> Mat2 {
+		Mat2::from_rows(self.rows[0].as_vec2(), self.rows[1].as_vec2())
+	}
+
+	pub fn transposed(self) -> Mat3 {
+		let [a, b, c] = self.rows;
+		Mat3::new(&[
+			a.x, b.x, c.x,
+			a.y, b.y, c.y,
+			a.z, b.z, c.z
+		])
+	}
+
+	pub fn determinant(self) -> f32 {
+		let [a, b, c] = self.rows;
+		a.dot(b.cross(c))
+	}
+
+	pub fn inverted(self) -> Mat3 {
+		let [a, b, c] = self.rows;
+		let det = self.determinant();
+
+		// Columns of the adjugate are the cross products of the rows
+		Mat3::from_rows(b.cross(c), c.cross(a), a.cross(b)).transposed() * (1.0 / det)
+	}
+}
+
+impl Mul<Mat3> for Mat3 {
+	type Output = Mat3;
+	fn mul(self, rhs: Mat3) -> Mat3 {
+		let ot = rhs.transposed();
+		let mut d = [0.0f32; 9];
+
+		for j in 0..3 {
+			for i in 0..3 {
+				d[i + j * 3] = self.rows[j].dot(ot.rows[i]);
+			}
+		}
+
+		Mat3::new(&d)
+	}
+}
+
+impl Mul<Mat3> for Vec3 {
+	type Output = Vec3;
+	fn mul(self, m: Mat3) -> Vec3 {
+		m.rows[0] * self.x + m.rows[1] * self.y + m.rows[2] * self.z
+	}
+}
+
+// Treats the Vec2 as a point, like Vec3 * Mat4
+impl Mul<Mat3> for Vec2 {
+	type Output = Vec2;
+	fn mul(self, m: Mat3) -> Vec2 {
+		(self.extend(1.0) * m).as_vec2()
+	}
+}
+
+// Deprecated, see Mat4's `m * v`
+impl Mul<Vec3> for Mat3 {
+	type Output = Vec3;
+	fn mul(self, v: Vec3) -> Vec3 {
+		v * self
+	}
+}
+
+impl Mul<Vec2> for Mat3 {
+	type Output = Vec2;
+	fn mul(self, v: Vec2) -> Vec2 {
+		v * self
+	}
+}
+
+impl Mul<f32> for Mat3 {
+	type Output = Mat3;
+	fn mul(self, rhs: f32) -> Mat3 {
+		Mat3::from_rows(
+			self.rows[0] * rhs,
+			self.rows[1] * rhs,
+			self.rows[2] * rhs
+		)
+	}
+}
+
+impl Index<usize> for Mat2 {
+	type Output = Vec2;
+	fn index(&self, i: usize) -> &Vec2 {
+		&self.rows[i]
+	}
+}
+
+impl IndexMut<usize> for Mat2 {
+	fn index_mut(&mut self, i: usize) -> &mut Vec2 {
+		&mut self.rows[i]
+	}
+}
+
+impl Mat2 {
+	pub fn as_ptr(&self) -> *const f32 {
+		&self.rows[0].x
+	}
+
+	pub fn new(m: &[f32; 4]) -> Mat2 {
+		Mat2 {
+			rows: [
+				Vec2::new(m[0], m[1]),
+				Vec2::new(m[2], m[3]),
+			]
+		}
+	}
+
+	pub fn from_rows(r0: Vec2, r1: Vec2) -> Mat2 {
+		Mat2 { rows: [ r0, r1 ] }
+	}
+
+	pub fn ident() -> Mat2 {
+		Mat2::new(&[
+			1.0, 0.0,
+			0.0, 1.0
+		])
+	}
+
+	pub fn rotation(a: f32) -> Mat2 {
+		let (s, c) = a.sin_cos();
+		Mat2::new(&[
+			 c, s,
+			-s, c
+		])
+	}
+
+	pub fn scaling(v: Vec2) -> Mat2 {
+		Mat2::new(&[
+			v.x, 0.0,
+			0.0, v.y
+		])
+	}
+
+	pub fn transposed(self) -> Mat2 {
+		let [a, b] = self.rows;
+		Mat2::new(&[
+			a.x, b.x,
+			a.y, b.y
+		])
+	}
+
+	pub fn determinant(self) -> f32 {
+		self.rows[0].perp_dot(self.rows[1])
+	}
+
+	pub fn inverted(self) -> Mat2 {
+		let [a, b] = self.rows;
+		Mat2::new(&[
+			 b.y, -a.y,
+			-b.x,  a.x
+		]) * (1.0 / self.determinant())
+	}
+}
+
+impl Mul<Mat2> for Mat2 {
+	type Output = Mat2;
+	fn mul(self, rhs: Mat2) -> Mat2 {
+		let ot = rhs.transposed();
+		Mat2::from_rows(
+			Vec2::new(self.rows[0].dot(ot.rows[0]), self.rows[0].dot(ot.rows[1])),
+			Vec2::new(self.rows[1].dot(ot.rows[0]), self.rows[1].dot(ot.rows[1]))
+		)
+	}
+}
+
+impl Mul<Mat2> for Vec2 {
+	type Output = Vec2;
+	fn mul(self, m: Mat2) -> Vec2 {
+		m.rows[0] * self.x + m.rows[1] * self.y
+	}
+}
+
+// Deprecated, see Mat4's `m * v`
+impl Mul<Vec2> for Mat2 {
+	type Output = Vec2;
+	fn mul(self, v: Vec2) -> Vec2 {
+		v * self
+	}
+}
+
+impl Mul<f32> for Mat2 {
+	type Output = Mat2;
+	fn mul(self, rhs: f32) -> Mat2 {
+		Mat2::from_rows(
+			self.rows[0] * rhs,
+			self.rows[1] * rhs
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::FRAC_PI_2;
+
+	fn assert_mat4_eq(a: Mat4, b: Mat4, eps: f32) {
+		for i in 0..4 {
+			assert!(a[i].approx_eq(b[i], eps), "{:?} != {:?}", a, b);
+		}
+	}
+
+	fn some_trs() -> Mat4 {
+		Mat4::trs(Vec3::new(1.0, -2.0, 3.0), Quat::new(Vec3::new(1.0, 2.0, 2.0).normalized(), 0.8), Vec3::new(2.0, 0.5, 1.5))
+	}
+
+	#[test]
+	fn row_vector_order() {
+		let p = Vec3::new(1.0, 0.0, 0.0);
+		let m = Mat4::rotation_z(FRAC_PI_2) * Mat4::translation(Vec3::new(0.0, 0.0, 5.0));
+
+		// Rotated first, then translated
+		assert!(m.transform_point(p).approx_eq(Vec3::new(0.0, 1.0, 5.0), 1e-6));
+		assert!((p * m).approx_eq(Vec3::new(0.0, 1.0, 5.0), 1e-6));
+		assert!(m.transform_vector(p).approx_eq(Vec3::new(0.0, 1.0, 0.0), 1e-6));
+	}
+
+	#[test]
+	fn old_operators_are_row_vector_products() {
+		let m = some_trs();
+		let v = Vec4::new(1.0, 2.0, 3.0, 1.0);
+		assert!((m * v).approx_eq(v * m, 0.0));
+		assert!((m * v.as_vec3()).approx_eq(v.as_vec3() * m, 0.0));
+
+		// Matches what the column-vector operator gave with the old, transposed rotation matrices
+		let p = Mat4::rotation_z(FRAC_PI_2) * Vec3::from_x(1.0);
+		assert!(p.approx_eq(Vec3::from_y(1.0), 1e-6));
+	}
+
+	#[test]
+	fn inverses() {
+		let affine = some_trs();
+		assert!(affine.is_affine());
+		assert_mat4_eq(affine * affine.inverted(), Mat4::ident(), 1e-5);
+		assert_mat4_eq(affine.inverted() * affine, Mat4::ident(), 1e-5);
+		assert_mat4_eq(affine * affine.affine_inverted(), Mat4::ident(), 1e-5);
+		assert_mat4_eq(affine.affine_inverted(), affine.inverted(), 1e-5);
+
+		let proj = Mat4::perspective(1.2, 1.5, 0.1, 100.0);
+		assert!(!proj.is_affine());
+		assert_mat4_eq(proj * proj.inverted(), Mat4::ident(), 1e-4);
+		assert_mat4_eq(proj.inverted(), proj.inverted_scalar(), 1e-4);
+
+		let m3 = affine.to_mat3();
+		let i3 = m3 * m3.inverted();
+		for i in 0..3 {
+			let mut row = Vec3::zero();
+			row[i] = 1.0;
+			assert!(i3[i].approx_eq(row, 1e-5));
+		}
+	}
+
+	#[test]
+	fn determinant() {
+		assert_eq!(Mat4::ident().determinant(), 1.0);
+		assert!((Mat4::scaling(Vec3::new(2.0, 3.0, 4.0)).determinant() - 24.0).abs() < 1e-5);
+		assert!((some_trs().determinant() - 1.5).abs() < 1e-4);
+		assert!(Mat4::scaling(Vec3::new(-1.0, 1.0, 1.0)).determinant() < 0.0);
+	}
+
+	#[test]
+	fn trs_decompose_round_trip() {
+		let t = Vec3::new(1.0, -2.0, 3.0);
+		let r = Quat::new(Vec3::new(1.0, 2.0, 2.0).normalized(), 0.8);
+		let s = Vec3::new(2.0, 0.5, 1.5);
+
+		let (t2, r2, s2) = Mat4::trs(t, r, s).decompose();
+		assert!(t2.approx_eq(t, 1e-6));
+		assert!(r2.approx_eq(r, 1e-5));
+		assert!(s2.approx_eq(s, 1e-5));
+
+		// A mirror comes back as a negative x scale, and still rebuilds the same matrix
+		let m = Mat4::trs(t, r, Vec3::new(1.0, -2.0, 1.0));
+		let (t3, r3, s3) = m.decompose();
+		assert!(s3.x < 0.0);
+		assert_mat4_eq(Mat4::trs(t3, r3, s3), m, 1e-5);
+	}
+
+	#[test]
+	fn perspective_depth_range() {
+		let (n, f) = (0.5, 50.0);
+		let m = Mat4::perspective(1.0, 1.5, n, f);
+
+		let near = m.transform_point(Vec3::new(0.0, 0.0, -n));
+		let far = m.transform_point(Vec3::new(0.0, 0.0, -f));
+		assert!((near.z + 1.0).abs() < 1e-5, "{:?}", near);
+		assert!((far.z - 1.0).abs() < 1e-4, "{:?}", far);
+
+		// The top edge of the field of view maps to y = 1
+		let top = m.transform_point(Vec3::new(0.0, (0.5f32).tan() * 10.0, -10.0));
+		assert!((top.y - 1.0).abs() < 1e-5);
+	}
+
+	#[test]
+	fn ortho_depth_range() {
+		let m = Mat4::ortho(-2.0, 6.0, 3.0, -1.0, 1.0, 9.0);
+
+		let near = Vec3::new(-2.0, -1.0, -1.0) * m;
+		let far = Vec3::new(6.0, 3.0, -9.0) * m;
+		assert!(near.approx_eq(Vec3::new(-1.0, -1.0, -1.0), 1e-6), "{:?}", near);
+		assert!(far.approx_eq(Vec3::new(1.0, 1.0, 1.0), 1e-6), "{:?}", far);
+
+		// Bottom left of the screen is the origin
+		let m = Mat4::ortho_2d(800.0, 600.0);
+		assert!((Vec3::zero() * m).approx_eq(Vec3::new(-1.0, -1.0, 0.0), 1e-6));
+		assert!((Vec3::new(800.0, 600.0, 0.0) * m).approx_eq(Vec3::new(1.0, 1.0, 0.0), 1e-6));
+	}
+
+	#[test]
+	fn look_at_faces_down_negative_z() {
+		let eye = Vec3::new(0.0, 0.0, 5.0);
+		let view = Mat4::look_at(eye, Vec3::new(0.0, 0.0, -1.0), Vec3::from_y(1.0));
+		assert!((eye * view).approx_eq(Vec3::zero(), 1e-6));
+		assert!((Vec3::zero() * view).approx_eq(Vec3::new(0.0, 0.0, -5.0), 1e-6));
+
+		let view = Mat4::look_at(Vec3::zero(), Vec3::new(1.0, 0.0, 0.0), Vec3::from_y(1.0));
+		assert!((Vec3::new(3.0, 0.0, 0.0) * view).approx_eq(Vec3::new(0.0, 0.0, -3.0), 1e-6));
+	}
+
+	#[test]
+	fn rotations_match_axis_angle() {
+		let v = Vec3::new(0.3, 0.7, -1.1);
+		assert_mat4_eq(Mat4::axis_angle(Vec3::from_x(1.0), 0.6), Mat4::rotation_x(0.6), 1e-6);
+		assert_mat4_eq(Mat4::axis_angle(Vec3::from_y(1.0), 0.6), Mat4::rotation_y(0.6), 1e-6);
+		assert_mat4_eq(Mat4::axis_angle(Vec3::from_z(1.0), 0.6), Mat4::rotation_z(0.6), 1e-6);
+		assert!((v * Mat4::rotation_x(FRAC_PI_2)).approx_eq(Vec3::new(0.3, 1.1, 0.7), 1e-6));
+	}
+
+	#[test]
+	fn normal_matrix_keeps_normals_perpendicular() {
+		let m = Mat4::scaling(Vec3::new(4.0, 1.0, 1.0));
+		let tangent = Vec3::new(1.0, -1.0, 0.0);
+		let normal = Vec3::new(1.0, 1.0, 0.0);
+
+		let t = m.transform_vector(tangent);
+		let n = normal * m.normal_matrix();
+		assert!(t.dot(n).abs() < 1e-5);
+	}
 }
\ No newline at end of file
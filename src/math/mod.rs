@@ -1,3 +1,9 @@
 #[macro_use]
 pub mod mat;
-pub mod vec;
\ No newline at end of file
+pub mod vec;
+pub mod quat;
+pub mod geom;
+pub mod simd;
+pub mod spline;
+pub mod random;
+pub mod noise;
\ No newline at end of file
@@ -0,0 +1,371 @@
+use std::cmp::Ordering;
+use std::ops::Mul;
+
+use vec::*;
+use random::*;
+
+// Seeded gradient, value and cellular noise. Only uses +, *, floor and sqrt,
+// so a given seed produces identical values on every platform.
+// Gradient and value noise return roughly [-1, 1]
+#[derive(Clone)]
+pub struct Noise {
+	perm: [u8; 256],
+}
+
+// Scales bringing each dimension's output to roughly [-1, 1]
+const PERLIN_SCALE: [f32; 4] = [2.0, 1.0, 1.0, 0.9];
+const SIMPLEX_SCALE: [f32; 4] = [3.2, 70.0, 32.0, 27.0];
+const SIMPLEX_RADIUS_SQ: [f32; 4] = [1.0, 0.5, 0.6, 0.6];
+
+impl Noise {
+	pub fn new(seed: u64) -> Noise {
+		let mut perm = [0u8; 256];
+		for (i, p) in perm.iter_mut().enumerate() {
+			*p = i as u8;
+		}
+		Rng::new(seed).shuffle(&mut perm);
+
+		Noise { perm }
+	}
+
+	pub fn perlin1(&self, x: f32) -> f32 { self.perlin(&[x]) }
+	pub fn perlin2(&self, p: Vec2) -> f32 { self.perlin(&[p.x, p.y]) }
+	pub fn perlin3(&self, p: Vec3) -> f32 { self.perlin(&[p.x, p.y, p.z]) }
+	pub fn perlin4(&self, p: Vec4) -> f32 { self.perlin(&[p.x, p.y, p.z, p.w]) }
+
+	pub fn simplex1(&self, x: f32) -> f32 { self.simplex(&[x]) }
+	pub fn simplex2(&self, p: Vec2) -> f32 { self.simplex(&[p.x, p.y]) }
+	pub fn simplex3(&self, p: Vec3) -> f32 { self.simplex(&[p.x, p.y, p.z]) }
+	pub fn simplex4(&self, p: Vec4) -> f32 { self.simplex(&[p.x, p.y, p.z, p.w]) }
+
+	pub fn value1(&self, x: f32) -> f32 { self.value(&[x]) }
+	pub fn value2(&self, p: Vec2) -> f32 { self.value(&[p.x, p.y]) }
+	pub fn value3(&self, p: Vec3) -> f32 { self.value(&[p.x, p.y, p.z]) }
+	pub fn value4(&self, p: Vec4) -> f32 { self.value(&[p.x, p.y, p.z, p.w]) }
+
+	// Distances to the nearest and second nearest feature points, one point per unit cell
+	pub fn worley1(&self, x: f32) -> (f32, f32) { self.worley(&[x]) }
+	pub fn worley2(&self, p: Vec2) -> (f32, f32) { self.worley(&[p.x, p.y]) }
+	pub fn worley3(&self, p: Vec3) -> (f32, f32) { self.worley(&[p.x, p.y, p.z]) }
+	pub fn worley4(&self, p: Vec4) -> (f32, f32) { self.worley(&[p.x, p.y, p.z, p.w]) }
+
+	fn hash(&self, cell: &[i32]) -> usize {
+		cell.iter().fold(0, |h, &c| self.perm[(h + (c & 255) as usize) & 255] as usize)
+	}
+
+	// Dot product of `d` with the pseudo-random gradient picked by `h`
+	fn gradient(h: usize, d: &[f32]) -> f32 {
+		match d.len() {
+			1 => d[0] * ((h & 15) as f32 - 7.5) * (1.0 / 7.5),
+
+			// The 8 directions to a square's edges and corners
+			2 => match h & 7 {
+				0 => d[0] + d[1],
+				1 => d[0] - d[1],
+				2 => -d[0] + d[1],
+				3 => -d[0] - d[1],
+				4 => d[0],
+				5 => -d[0],
+				6 => d[1],
+				_ => -d[1],
+			},
+
+			// Directions to the cube or tesseract's edge midpoints: one axis zero, the rest +-1
+			n => {
+				let skip = h % n;
+				let mut signs = h / n;
+				let mut sum = 0.0;
+				for i in 0..n {
+					if i == skip { continue }
+					sum += if signs & 1 == 0 { d[i] } else { -d[i] };
+					signs >>= 1;
+				}
+				sum
+			}
+		}
+	}
+
+	fn perlin(&self, p: &[f32]) -> f32 {
+		let n = p.len();
+		let mut cell = [0i32; 4];
+		let mut frac = [0.0f32; 4];
+		let mut fade = [0.0f32; 4];
+
+		for i in 0..n {
+			let f = p[i].floor();
+			cell[i] = f as i32;
+			frac[i] = p[i] - f;
+			fade[i] = smootherstep(frac[i]);
+		}
+
+		let mut sum = 0.0;
+		for corner in 0..1 << n {
+			let mut c = [0i32; 4];
+			let mut d = [0.0f32; 4];
+			let mut w = 1.0;
+
+			for i in 0..n {
+				let bit = (corner >> i) & 1;
+				c[i] = cell[i] + bit;
+				d[i] = frac[i] - bit as f32;
+				w *= if bit == 1 { fade[i] } else { 1.0 - fade[i] };
+			}
+
+			sum += w * Noise::gradient(self.hash(&c[..n]), &d[..n]);
+		}
+
+		sum * PERLIN_SCALE[n - 1]
+	}
+
+	fn simplex(&self, p: &[f32]) -> f32 {
+		let n = p.len();
+
+		// Skew to the lattice where simplices are axis aligned, and back again
+		let nf = n as f32;
+		let (skew, unskew) = if n == 1 {
+			(0.0, 0.0)
+		} else {
+			(((nf + 1.0).sqrt() - 1.0) / nf, (1.0 - 1.0 / (nf + 1.0).sqrt()) / nf)
+		};
+
+		let s = p.iter().sum::<f32>() * skew;
+		let mut cell = [0i32; 4];
+		let mut d0 = [0.0f32; 4];
+		for i in 0..n {
+			cell[i] = (p[i] + s).floor() as i32;
+		}
+
+		let t = cell[..n].iter().sum::<i32>() as f32 * unskew;
+		for i in 0..n {
+			d0[i] = p[i] - (cell[i] as f32 - t);
+		}
+
+		// Walk the simplex's corners, stepping along the axes in order of decreasing offset
+		let mut order = [0usize, 1, 2, 3];
+		order[..n].sort_by(|&a, &b| d0[b].partial_cmp(&d0[a]).unwrap_or(Ordering::Equal));
+
+		let mut offset = [0i32; 4];
+		let mut sum = 0.0;
+
+		for k in 0..n + 1 {
+			if k > 0 {
+				offset[order[k - 1]] = 1;
+			}
+
+			let mut c = [0i32; 4];
+			let mut d = [0.0f32; 4];
+			let mut dist_sq = 0.0;
+			for i in 0..n {
+				c[i] = cell[i] + offset[i];
+				d[i] = d0[i] - offset[i] as f32 + k as f32 * unskew;
+				dist_sq += d[i] * d[i];
+			}
+
+			let falloff = SIMPLEX_RADIUS_SQ[n - 1] - dist_sq;
+			if falloff > 0.0 {
+				let f2 = falloff * falloff;
+				sum += f2 * f2 * Noise::gradient(self.hash(&c[..n]), &d[..n]);
+			}
+		}
+
+		sum * SIMPLEX_SCALE[n - 1]
+	}
+
+	fn value(&self, p: &[f32]) -> f32 {
+		let n = p.len();
+		let mut cell = [0i32; 4];
+		let mut fade = [0.0f32; 4];
+
+		for i in 0..n {
+			let f = p[i].floor();
+			cell[i] = f as i32;
+			fade[i] = smootherstep(p[i] - f);
+		}
+
+		let mut sum = 0.0;
+		for corner in 0..1 << n {
+			let mut c = [0i32; 4];
+			let mut w = 1.0;
+
+			for i in 0..n {
+				let bit = (corner >> i) & 1;
+				c[i] = cell[i] + bit;
+				w *= if bit == 1 { fade[i] } else { 1.0 - fade[i] };
+			}
+
+			sum += w * (self.hash(&c[..n]) as f32 * (2.0 / 255.0) - 1.0);
+		}
+
+		sum
+	}
+
+	fn worley(&self, p: &[f32]) -> (f32, f32) {
+		let n = p.len();
+		let mut cell = [0i32; 4];
+		for i in 0..n {
+			cell[i] = p[i].floor() as i32;
+		}
+
+		let mut f1 = ::std::f32::INFINITY;
+		let mut f2 = ::std::f32::INFINITY;
+
+		// The 3^n neighbouring cells
+		let mut neighbour = 0;
+		while neighbour < 3usize.pow(n as u32) {
+			let mut c = [0i32; 5];
+			let mut rest = neighbour;
+			for i in 0..n {
+				c[i] = cell[i] + (rest % 3) as i32 - 1;
+				rest /= 3;
+			}
+
+			let mut dist_sq = 0.0;
+			for i in 0..n {
+				// Salt the hash with the axis to get independent coordinates
+				c[n] = i as i32;
+				let point = c[i] as f32 + self.hash(&c[..n + 1]) as f32 * (1.0 / 256.0);
+				let d = point - p[i];
+				dist_sq += d * d;
+			}
+
+			let dist = dist_sq.sqrt();
+			if dist < f1 {
+				f2 = f1;
+				f1 = dist;
+			} else if dist < f2 {
+				f2 = dist;
+			}
+
+			neighbour += 1;
+		}
+
+		(f1, f2)
+	}
+}
+
+fn smootherstep(t: f32) -> f32 {
+	t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+// Sums octaves of a noise function at rising frequency and falling amplitude
+#[derive(Copy, Clone, Debug)]
+pub struct Fractal {
+	pub octaves: u32,
+	// Frequency multiplier per octave
+	pub lacunarity: f32,
+	// Amplitude multiplier per octave
+	pub gain: f32,
+}
+
+impl Default for Fractal {
+	fn default() -> Fractal {
+		Fractal { octaves: 5, lacunarity: 2.0, gain: 0.5 }
+	}
+}
+
+impl Fractal {
+	pub fn new(octaves: u32) -> Fractal {
+		Fractal { octaves, .. Fractal::default() }
+	}
+
+	fn sum<P, F>(&self, p: P, noise: F, shape: &dyn Fn(f32) -> f32) -> f32
+		where P: Mul<f32, Output=P> + Copy, F: Fn(P) -> f32
+	{
+		let mut sum = 0.0;
+		let mut amplitude = 1.0;
+		let mut frequency = 1.0;
+		let mut norm = 0.0;
+
+		for _ in 0..self.octaves {
+			sum += shape(noise(p * frequency)) * amplitude;
+			norm += amplitude;
+			amplitude *= self.gain;
+			frequency *= self.lacunarity;
+		}
+
+		if norm > 0.0 { sum / norm } else { 0.0 }
+	}
+
+	// Fractional Brownian motion, in the same range as `noise`
+	//	fractal.fbm(p, |p| noise.simplex3(p))
+	pub fn fbm<P, F>(&self, p: P, noise: F) -> f32
+		where P: Mul<f32, Output=P> + Copy, F: Fn(P) -> f32
+	{
+		self.sum(p, noise, &|n| n)
+	}
+
+	// Sum of absolute values, in [0, 1]. Billowy, like clouds
+	pub fn turbulence<P, F>(&self, p: P, noise: F) -> f32
+		where P: Mul<f32, Output=P> + Copy, F: Fn(P) -> f32
+	{
+		self.sum(p, noise, &|n| n.abs())
+	}
+
+	// Inverted and squared turbulence, in [0, 1]. Sharp crests, like mountain ranges
+	pub fn ridged<P, F>(&self, p: P, noise: F) -> f32
+		where P: Mul<f32, Output=P> + Copy, F: Fn(P) -> f32
+	{
+		self.sum(p, noise, &|n| {
+			let r = 1.0 - n.abs();
+			r * r
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::NAN;
+
+	#[test]
+	fn nan_input_doesnt_panic() {
+		let noise = Noise::new(7);
+		noise.simplex2(Vec2::new(NAN, 0.5));
+		noise.simplex3(Vec3::new(0.5, NAN, 1.5));
+		noise.simplex4(Vec4::new(NAN, NAN, 0.25, 2.0));
+		Fractal::new(4).fbm(Vec2::new(NAN, 1.0), |p| noise.simplex2(p));
+	}
+
+	// Only +, *, floor and sqrt go into these, so they must match exactly on every platform
+	#[test]
+	fn golden_values() {
+		let n = Noise::new(1234);
+		assert_eq!(n.perlin2(Vec2::new(0.3, 1.7)), 0.22831193);
+		assert_eq!(n.simplex3(Vec3::new(0.3, 1.7, -2.2)), -0.4534737);
+		assert_eq!(n.value2(Vec2::new(5.5, 0.25)), 0.38782945);
+		assert_eq!(n.worley2(Vec2::new(0.3, 1.7)), (0.55233717, 0.61537117));
+	}
+
+	#[test]
+	fn output_range() {
+		let n = Noise::new(1234);
+		let mut rng = Rng::new(9);
+		let mut max = [0.0f32; 12];
+
+		for _ in 0..20000 {
+			let p = Vec4::new(rng.range_f32(-50.0, 50.0), rng.range_f32(-50.0, 50.0), rng.range_f32(-50.0, 50.0), rng.range_f32(-50.0, 50.0));
+			let (p2, p3) = (Vec2::new(p.x, p.y), p.as_vec3());
+			let values = [
+				n.perlin1(p.x), n.perlin2(p2), n.perlin3(p3), n.perlin4(p),
+				n.simplex1(p.x), n.simplex2(p2), n.simplex3(p3), n.simplex4(p),
+				n.value1(p.x), n.value2(p2), n.value3(p3), n.value4(p),
+			];
+
+			for (m, v) in max.iter_mut().zip(values.iter()) {
+				*m = m.max(v.abs());
+			}
+		}
+
+		// Within [-1, 1], and the scales don't leave most of that range unused
+		for (i, &m) in max.iter().enumerate() {
+			assert!(m <= 1.0 && m > 0.8, "{}: {}", i, m);
+		}
+	}
+
+	#[test]
+	fn seeded() {
+		let p = Vec3::new(1.3, -0.7, 4.2);
+		assert_eq!(Noise::new(7).simplex3(p), Noise::new(7).simplex3(p));
+		assert!(Noise::new(7).simplex3(p) != Noise::new(8).simplex3(p));
+	}
+}
\ No newline at end of file
@@ -1,16 +1,38 @@
 // Original code from <https://github.com/manpat/common-rs>
-use std::ops::{ Add, Mul };
+use std::ops::{ Add, Sub, Mul, Neg };
 use mat::*;
 use vec::*;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Quat{ pub x: f32, pub y: f32, pub z: f32, pub w: f32 }
 
+// Order the rotations are applied in; `XYZ` rotates about x first, then y, then z.
+// Angles are always passed as (x, y, z) whatever the order
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EulerOrder { XYZ, XZY, YXZ, YZX, ZXY, ZYX }
+
+impl EulerOrder {
+	// Axis indices in application order, and whether that's an even permutation of xyz
+	fn axes(self) -> (usize, usize, usize, bool) {
+		match self {
+			EulerOrder::XYZ => (0, 1, 2, true),
+			EulerOrder::YZX => (1, 2, 0, true),
+			EulerOrder::ZXY => (2, 0, 1, true),
+			EulerOrder::XZY => (0, 2, 1, false),
+			EulerOrder::YXZ => (1, 0, 2, false),
+			EulerOrder::ZYX => (2, 1, 0, false),
+		}
+	}
+}
+
 impl Quat {
 	pub const fn from_raw(x: f32, y: f32, z: f32, w: f32) -> Quat {
 		Quat { x, y, z, w }
 	}
 
+	pub fn ident() -> Quat { Quat::from_raw(0.0, 0.0, 0.0, 1.0) }
+
+	// `axis` must be normalized
 	pub fn new(axis: Vec3, angle: f32) -> Quat {
 		let angle = angle / 2.0;
 		let s = angle.sin();
@@ -23,6 +45,89 @@ impl Quat {
 		)
 	}
 
+	pub fn from_euler(angles: Vec3, order: EulerOrder) -> Quat {
+		let axis = [Vec3::from_x(1.0), Vec3::from_y(1.0), Vec3::from_z(1.0)];
+		let (i, j, k, _) = order.axes();
+
+		Quat::new(axis[k], angles[k]) * Quat::new(axis[j], angles[j]) * Quat::new(axis[i], angles[i])
+	}
+
+	// Inverse of `from_euler`. At gimbal lock the last rotation is reported as zero
+	pub fn to_euler(&self, order: EulerOrder) -> Vec3 {
+		// Extracted from the column-vector form, where `m[k][i]` is the sine of the middle angle
+		let m = self.to_mat3().transposed();
+		let (i, j, k, even) = order.axes();
+		let sign = if even { 1.0 } else { -1.0 };
+
+		let mut angles = Vec3::zero();
+		let sb = -sign * m[k][i];
+
+		if sb.abs() < 0.99999 {
+			angles[i] = (sign * m[k][j]).atan2(m[k][k]);
+			angles[j] = sb.asin();
+			angles[k] = (sign * m[j][i]).atan2(m[i][i]);
+		} else {
+			angles[i] = (-sign * m[j][k]).atan2(m[j][j]);
+			angles[j] = sb.signum() * ::std::f32::consts::FRAC_PI_2;
+			angles[k] = 0.0;
+		}
+
+		angles
+	}
+
+	// `m` must be a pure rotation
+	pub fn from_mat3(m: &Mat3) -> Quat {
+		let m = m.transposed();
+		let trace = m[0][0] + m[1][1] + m[2][2];
+
+		if trace > 0.0 {
+			let s = (trace + 1.0).sqrt() * 2.0;
+			Quat::from_raw((m[2][1] - m[1][2]) / s, (m[0][2] - m[2][0]) / s, (m[1][0] - m[0][1]) / s, 0.25 * s)
+		} else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+			let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+			Quat::from_raw(0.25 * s, (m[0][1] + m[1][0]) / s, (m[0][2] + m[2][0]) / s, (m[2][1] - m[1][2]) / s)
+		} else if m[1][1] > m[2][2] {
+			let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+			Quat::from_raw((m[0][1] + m[1][0]) / s, 0.25 * s, (m[1][2] + m[2][1]) / s, (m[0][2] - m[2][0]) / s)
+		} else {
+			let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+			Quat::from_raw((m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s, 0.25 * s, (m[1][0] - m[0][1]) / s)
+		}
+	}
+
+	// Rotation part only; scale must be removed first
+	pub fn from_mat4(m: &Mat4) -> Quat {
+		Quat::from_mat3(&m.to_mat3())
+	}
+
+	// Shortest rotation taking direction `from` onto `to`
+	pub fn from_to(from: Vec3, to: Vec3) -> Quat {
+		let from = from.normalized();
+		let to = to.normalized();
+		let d = from.dot(to);
+
+		if d < -0.999999 {
+			// Opposite directions; any perpendicular axis will do
+			let mut axis = Vec3::from_x(1.0).cross(from);
+			if axis.len_sq() < 0.000001 {
+				axis = Vec3::from_y(1.0).cross(from);
+			}
+			return Quat::new(axis.normalized(), ::std::f32::consts::PI)
+		}
+
+		let c = from.cross(to);
+		Quat::from_raw(c.x, c.y, c.z, 1.0 + d).normalize()
+	}
+
+	// Orientation whose `forward()` points along `forward`, with `up()` as close to `up` as possible
+	pub fn look_rotation(forward: Vec3, up: Vec3) -> Quat {
+		let z = -forward.normalized();
+		let x = up.cross(z).normalized();
+		let y = z.cross(x);
+
+		Quat::from_mat3(&Mat3::from_rows(x, y, z))
+	}
+
 	pub fn forward(&self) -> Vec3 { *self * Vec3::from_z(-1.0) }
 	pub fn right(&self) -> Vec3 { *self * Vec3::from_x(1.0) }
 	pub fn up(&self) -> Vec3 { *self * Vec3::from_y(1.0) }
@@ -31,8 +136,12 @@ impl Quat {
 		Vec3::new(self.x, self.y, self.z)
 	}
 
+	pub fn dot(&self, o: Quat) -> f32 {
+		self.x * o.x + self.y * o.y + self.z * o.z + self.w * o.w
+	}
+
 	pub fn magnitude(&self) -> f32 {
-		(self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt()
+		self.dot(*self).sqrt()
 	}
 
 	pub fn normalize(&self) -> Quat {
@@ -44,13 +153,74 @@ impl Quat {
 		Quat::from_raw(-self.x, -self.y, -self.z, self.w)
 	}
 
+	// Same as `conjugate` for unit quaternions
+	pub fn inverse(&self) -> Quat {
+		self.conjugate() * (1.0 / self.dot(*self))
+	}
+
+	// Rotation angle in [0, 2pi]
+	pub fn angle(&self) -> f32 {
+		2.0 * self.w.max(-1.0).min(1.0).acos()
+	}
+
+	// The axis is arbitrary for the identity
+	pub fn to_axis_angle(&self) -> (Vec3, f32) {
+		let q = self.normalize();
+		let s = (1.0 - q.w * q.w).max(0.0).sqrt();
+
+		if s < 0.0001 {
+			(Vec3::from_x(1.0), 0.0)
+		} else {
+			(q.imaginary() * (1.0 / s), q.angle())
+		}
+	}
+
+	// Angle of the rotation taking `self` to `o`. atan2 rather than acos of the dot product,
+	// which loses most of its precision for small angles
+	pub fn angle_between(&self, o: Quat) -> f32 {
+		let d = self.conjugate() * o;
+		2.0 * Vec3::new(d.x, d.y, d.z).len().atan2(d.w.abs())
+	}
+
+	// Normalized lerp. Cheaper than slerp and fine for small steps, but not constant speed
+	pub fn nlerp(&self, o: Quat, t: f32) -> Quat {
+		let o = if self.dot(o) < 0.0 { -o } else { o };
+		(*self * (1.0 - t) + o * t).normalize()
+	}
+
+	// Constant speed along the shortest path
+	pub fn slerp(&self, o: Quat, t: f32) -> Quat {
+		let mut d = self.dot(o);
+		let o = if d < 0.0 { d = -d; -o } else { o };
+
+		if d > 0.9995 {
+			return self.nlerp(o, t)
+		}
+
+		let theta = d.acos();
+		let s = theta.sin();
+		let a = ((1.0 - t) * theta).sin() / s;
+		let b = (t * theta).sin() / s;
+
+		*self * a + o * b
+	}
+
+	// q and -q are the same rotation, so both count as equal
+	pub fn approx_eq(&self, o: Quat, epsilon: f32) -> bool {
+		1.0 - self.dot(o).abs() <= epsilon
+	}
+
+	// Rows are the rotated basis vectors
+	pub fn to_mat3(&self) -> Mat3 {
+		Mat3::from_rows(
+			*self * Vec3::from_x(1.0),
+			*self * Vec3::from_y(1.0),
+			*self * Vec3::from_z(1.0)
+		)
+	}
+
 	pub fn to_mat4(&self) -> Mat4 {
-		Mat4::from_rows([
-			(*self * Vec3::new(1.0, 0.0, 0.0)).extend(0.0),
-			(*self * Vec3::new(0.0, 1.0, 0.0)).extend(0.0),
-			(*self * Vec3::new(0.0, 0.0, 1.0)).extend(0.0),
-			Vec4::new(0.0, 0.0, 0.0, 1.0)
-		]).transpose()
+		self.to_mat3().to_mat4()
 	}
 }
 
@@ -61,6 +231,20 @@ impl Add<Quat> for Quat {
 	}
 }
 
+impl Sub<Quat> for Quat {
+	type Output = Quat;
+	fn sub(self, o: Quat) -> Quat {
+		Quat::from_raw(self.x-o.x, self.y-o.y, self.z-o.z, self.w-o.w)
+	}
+}
+
+impl Neg for Quat {
+	type Output = Quat;
+	fn neg(self) -> Quat {
+		Quat::from_raw(-self.x, -self.y, -self.z, -self.w)
+	}
+}
+
 impl Mul<Quat> for Quat {
 	type Output = Quat;
 	fn mul(self, o: Quat) -> Quat {
@@ -86,4 +270,183 @@ impl Mul<Vec3> for Quat {
 		let q = Quat::from_raw(o.x, o.y, o.z, 0.0);
 		(self * q * self.conjugate()).imaginary()
 	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::{ PI, FRAC_PI_2, FRAC_PI_4 };
+
+	const ORDERS: [EulerOrder; 6] = [EulerOrder::XYZ, EulerOrder::XZY, EulerOrder::YXZ, EulerOrder::YZX, EulerOrder::ZXY, EulerOrder::ZYX];
+
+	fn x() -> Vec3 { Vec3::from_x(1.0) }
+	fn y() -> Vec3 { Vec3::from_y(1.0) }
+	fn z() -> Vec3 { Vec3::from_z(1.0) }
+
+	fn close(a: Vec3, b: Vec3) -> bool { a.approx_eq(b, 1e-5) }
+
+	#[test]
+	fn quarter_turns() {
+		// Counter-clockwise looking down each axis towards the origin
+		assert!(close(Quat::new(z(), FRAC_PI_2) * x(), y()));
+		assert!(close(Quat::new(x(), FRAC_PI_2) * y(), z()));
+		assert!(close(Quat::new(y(), FRAC_PI_2) * z(), x()));
+		assert!(close(Quat::new(y(), -FRAC_PI_2) * x(), z()));
+	}
+
+	#[test]
+	fn half_turns() {
+		assert!(close(Quat::new(y(), PI) * x(), -x()));
+		assert!(close(Quat::new(y(), PI) * y(), y()));
+		assert!(close(Quat::new(z(), PI) * Vec3::new(1.0, 2.0, 3.0), Vec3::new(-1.0, -2.0, 3.0)));
+
+		let q = Quat::new(x(), PI);
+		assert!((q.angle() - PI).abs() < 1e-5);
+		assert!(q.w.abs() < 1e-6);
+	}
+
+	#[test]
+	fn axis_angle_round_trip() {
+		let axis = Vec3::new(1.0, -2.0, 0.5).normalized();
+		let (a, angle) = Quat::new(axis, 1.2).to_axis_angle();
+		assert!(close(a, axis));
+		assert!((angle - 1.2).abs() < 1e-5);
+
+		let (_, angle) = Quat::ident().to_axis_angle();
+		assert_eq!(angle, 0.0);
+	}
+
+	#[test]
+	fn composition_applies_the_right_side_first() {
+		let a = Quat::new(z(), FRAC_PI_2);
+		let b = Quat::new(x(), FRAC_PI_2);
+
+		// b takes y to z, which a leaves alone
+		assert!(close((a * b) * y(), z()));
+		// a takes y to -x, which b leaves alone
+		assert!(close((b * a) * y(), -x()));
+	}
+
+	#[test]
+	fn to_mat_matches_the_rotation_matrices() {
+		let cases = [
+			(Quat::new(x(), FRAC_PI_2), Mat4::rotation_x(FRAC_PI_2)),
+			(Quat::new(y(), FRAC_PI_2), Mat4::rotation_y(FRAC_PI_2)),
+			(Quat::new(z(), PI), Mat4::rotation_z(PI)),
+			(Quat::new(y(), PI), Mat4::rotation_y(PI)),
+		];
+
+		for &(q, m) in cases.iter() {
+			let qm = q.to_mat4();
+			for i in 0..4 {
+				assert!(qm[i].approx_eq(m[i], 1e-6), "{:?} vs {:?}", qm, m);
+			}
+
+			let p = Vec3::new(0.3, -1.0, 2.0);
+			assert!((p.extend(1.0) * qm).approx_eq((q * p).extend(1.0), 1e-5));
+			assert!(Quat::from_mat4(&qm).approx_eq(q, 1e-6));
+		}
+	}
+
+	#[test]
+	fn euler_known_rotations() {
+		let q = Quat::from_euler(Vec3::new(FRAC_PI_2, 0.0, 0.0), EulerOrder::XYZ);
+		assert!(q.approx_eq(Quat::new(x(), FRAC_PI_2), 1e-6));
+
+		// XYZ turns about x first: y goes to z, which z leaves alone, and x is left for z to turn to y
+		let q = Quat::from_euler(Vec3::new(FRAC_PI_2, 0.0, FRAC_PI_2), EulerOrder::XYZ);
+		assert!(close(q * y(), z()));
+		assert!(close(q * x(), y()));
+
+		// ZYX turns about z first: x goes to y, then x turns that to z
+		let q = Quat::from_euler(Vec3::new(FRAC_PI_2, 0.0, FRAC_PI_2), EulerOrder::ZYX);
+		assert!(close(q * x(), z()));
+	}
+
+	#[test]
+	fn euler_round_trip() {
+		let angles = Vec3::new(0.4, -0.7, 1.1);
+		for &order in ORDERS.iter() {
+			let q = Quat::from_euler(angles, order);
+			let back = q.to_euler(order);
+			assert!(close(back, angles), "{:?}: {:?}", order, back);
+			assert!(Quat::from_euler(back, order).approx_eq(q, 1e-6));
+		}
+	}
+
+	#[test]
+	fn euler_gimbal_lock() {
+		for &order in ORDERS.iter() {
+			let mut angles = Vec3::zero();
+			let (i, j, _, _) = order.axes();
+			angles[i] = 0.3;
+			angles[j] = FRAC_PI_2;
+
+			// The split between the outer angles is lost, but the rotation survives
+			let q = Quat::from_euler(angles, order);
+			let back = q.to_euler(order);
+			assert!(Quat::from_euler(back, order).approx_eq(q, 1e-5), "{:?}: {:?}", order, back);
+		}
+	}
+
+	#[test]
+	fn slerp_endpoints_and_midpoint() {
+		let a = Quat::new(z(), 0.2);
+		let b = Quat::new(Vec3::new(1.0, 1.0, 0.0).normalized(), 2.0);
+		assert!(a.slerp(b, 0.0).approx_eq(a, 1e-6));
+		assert!(a.slerp(b, 1.0).approx_eq(b, 1e-6));
+
+		let half = Quat::ident().slerp(Quat::new(z(), FRAC_PI_2), 0.5);
+		assert!(half.approx_eq(Quat::new(z(), FRAC_PI_4), 1e-6));
+		assert!((half.magnitude() - 1.0).abs() < 1e-6);
+	}
+
+	#[test]
+	fn slerp_takes_the_short_way_round_antipodes() {
+		// -q is the same rotation as q, so halfway to it is still 45 degrees, not 135
+		let target = -Quat::new(z(), FRAC_PI_2);
+		assert!(target.w < 0.0);
+
+		let half = Quat::ident().slerp(target, 0.5);
+		assert!(close(half * x(), Vec3::new(FRAC_PI_4.cos(), FRAC_PI_4.sin(), 0.0)));
+		assert!(Quat::ident().slerp(target, 1.0).approx_eq(target, 1e-6));
+
+		let n = Quat::ident().nlerp(target, 0.5);
+		assert!(n.approx_eq(Quat::new(z(), FRAC_PI_4), 1e-6));
+
+		// Between q and -q there's nothing to turn through
+		let q = Quat::new(y(), 1.0);
+		assert!(q.slerp(-q, 0.5).approx_eq(q, 1e-6));
+		assert!(q.angle_between(-q) < 1e-6);
+	}
+
+	#[test]
+	fn from_to_and_look_rotation() {
+		assert!(close(Quat::from_to(x(), y()) * x(), y()));
+		assert!(close(Quat::from_to(x(), -x()) * x(), -x()));
+		assert!(close(Quat::from_to(z(), Vec3::new(0.0, 3.0, 3.0)) * z(), Vec3::new(0.0, 1.0, 1.0).normalized()));
+
+		let q = Quat::look_rotation(x(), y());
+		assert!(close(q.forward(), x()));
+		assert!(close(q.up(), y()));
+		assert!(close(q.right(), z()));
+	}
+
+	#[test]
+	fn angle_between_small_angles() {
+		let a = Quat::new(y(), 0.3);
+		for &angle in [1e-3f32, 0.01, 0.5, 3.0].iter() {
+			let b = Quat::new(y(), 0.3 + angle);
+			assert!((a.angle_between(b) / angle - 1.0).abs() < 1e-3, "{}: {}", angle, a.angle_between(b));
+			assert!((b.angle_between(a) / angle - 1.0).abs() < 1e-3);
+		}
+	}
+
+	#[test]
+	fn inverse_undoes() {
+		let q = Quat::new(Vec3::new(0.0, 0.6, 0.8), 0.9);
+		let p = Vec3::new(1.0, 2.0, 3.0);
+		assert!(close(q.inverse() * (q * p), p));
+		assert!((q * q.conjugate()).approx_eq(Quat::ident(), 1e-6));
+	}
 }
\ No newline at end of file
@@ -0,0 +1,301 @@
+use vec::*;
+use quat::*;
+use math::geom::*;
+
+// PCG32 (XSH RR). Small, fast and reproducible: the same seed gives the same sequence on
+// every platform. Apart from `gaussian`, helpers avoid libm calls so their results are just as portable
+#[derive(Clone, Debug)]
+pub struct Rng {
+	state: u64,
+	inc: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const DEFAULT_STREAM: u64 = 0xda3e39cb94b95bdb;
+
+impl Rng {
+	pub fn new(seed: u64) -> Rng {
+		Rng::with_stream(seed, DEFAULT_STREAM)
+	}
+
+	// Different streams with the same seed give independent sequences
+	pub fn with_stream(seed: u64, stream: u64) -> Rng {
+		let mut rng = Rng { state: 0, inc: (stream << 1) | 1 };
+		rng.next_u32();
+		rng.state = rng.state.wrapping_add(seed);
+		rng.next_u32();
+		rng
+	}
+
+	pub fn next_u32(&mut self) -> u32 {
+		let old = self.state;
+		self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+
+		let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+		let rot = (old >> 59) as u32;
+		xorshifted.rotate_right(rot)
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		(self.next_u32() as u64) << 32 | self.next_u32() as u64
+	}
+
+	// In [0, 1)
+	pub fn next_f32(&mut self) -> f32 {
+		(self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+	}
+
+	// In [0, 1)
+	pub fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+	}
+
+	// Uniform in [0, n), without modulo bias
+	pub fn below(&mut self, n: u32) -> u32 {
+		assert!(n > 0);
+		let threshold = n.wrapping_neg() % n;
+		loop {
+			let r = self.next_u32();
+			if r >= threshold {
+				return r % n
+			}
+		}
+	}
+
+	// In [lo, hi)
+	pub fn range_i32(&mut self, lo: i32, hi: i32) -> i32 {
+		assert!(lo < hi);
+		lo.wrapping_add(self.below(hi.wrapping_sub(lo) as u32) as i32)
+	}
+
+	// In [lo, hi)
+	pub fn range_f32(&mut self, lo: f32, hi: f32) -> f32 {
+		lo + (hi - lo) * self.next_f32()
+	}
+
+	// True with probability `p`
+	pub fn chance(&mut self, p: f32) -> bool {
+		self.next_f32() < p
+	}
+
+	pub fn bool(&mut self) -> bool {
+		self.next_u32() & 1 == 1
+	}
+
+	// In [-1, 1)
+	pub fn signed_f32(&mut self) -> f32 {
+		self.next_f32() * 2.0 - 1.0
+	}
+
+	pub fn point_in_circle(&mut self) -> Vec2 {
+		loop {
+			let p = Vec2::new(self.signed_f32(), self.signed_f32());
+			if p.len_sq() <= 1.0 {
+				return p
+			}
+		}
+	}
+
+	pub fn point_in_sphere(&mut self) -> Vec3 {
+		loop {
+			let p = Vec3::new(self.signed_f32(), self.signed_f32(), self.signed_f32());
+			if p.len_sq() <= 1.0 {
+				return p
+			}
+		}
+	}
+
+	pub fn unit_vec2(&mut self) -> Vec2 {
+		loop {
+			let p = self.point_in_circle();
+			let l = p.len_sq();
+			if l > 1e-4 {
+				return p * (1.0 / l.sqrt())
+			}
+		}
+	}
+
+	pub fn unit_vec3(&mut self) -> Vec3 {
+		loop {
+			let p = self.point_in_sphere();
+			let l = p.len_sq();
+			if l > 1e-4 {
+				return p * (1.0 / l.sqrt())
+			}
+		}
+	}
+
+	// Uniformly distributed orientation
+	pub fn rotation(&mut self) -> Quat {
+		loop {
+			let q = Quat::from_raw(self.signed_f32(), self.signed_f32(), self.signed_f32(), self.signed_f32());
+			let l = q.dot(q);
+			if l > 1e-4 && l <= 1.0 {
+				return q * (1.0 / l.sqrt())
+			}
+		}
+	}
+
+	pub fn point_in_rect(&mut self, r: &Rect) -> Vec2 {
+		Vec2::new(self.range_f32(r.min.x, r.max.x), self.range_f32(r.min.y, r.max.y))
+	}
+
+	pub fn point_in_aabb(&mut self, b: &Aabb) -> Vec3 {
+		Vec3::new(
+			self.range_f32(b.min.x, b.max.x),
+			self.range_f32(b.min.y, b.max.y),
+			self.range_f32(b.min.z, b.max.z)
+		)
+	}
+
+	pub fn point_in_triangle(&mut self, t: &Triangle) -> Vec3 {
+		let (mut u, mut v) = (self.next_f32(), self.next_f32());
+		if u + v > 1.0 {
+			u = 1.0 - u;
+			v = 1.0 - v;
+		}
+		t.from_barycentric(Vec3::new(1.0 - u - v, u, v))
+	}
+
+	// Standard normal distribution, via the Marsaglia polar method
+	pub fn gaussian(&mut self) -> f32 {
+		loop {
+			let p = Vec2::new(self.signed_f32(), self.signed_f32());
+			let s = p.len_sq();
+			if s > 0.0 && s < 1.0 {
+				return p.x * (-2.0 * s.ln() / s).sqrt()
+			}
+		}
+	}
+
+	pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+		if items.is_empty() {
+			None
+		} else {
+			Some(&items[self.below(items.len() as u32) as usize])
+		}
+	}
+
+	// Index picked with probability proportional to its weight. None if every weight is zero
+	pub fn weighted_index(&mut self, weights: &[f32]) -> Option<usize> {
+		let total: f32 = weights.iter().map(|w| w.max(0.0)).sum();
+		if total <= 0.0 { return None }
+
+		let mut r = self.next_f32() * total;
+		for (i, w) in weights.iter().enumerate() {
+			let w = w.max(0.0);
+			if r < w {
+				return Some(i)
+			}
+			r -= w;
+		}
+
+		// Rounding can leave r just past the last non-zero weight
+		weights.iter().rposition(|&w| w > 0.0)
+	}
+
+	pub fn weighted_choice<'a, T>(&mut self, items: &'a [(T, f32)]) -> Option<&'a T> {
+		let weights: Vec<f32> = items.iter().map(|i| i.1).collect();
+		self.weighted_index(&weights).map(|i| &items[i].0)
+	}
+
+	// Fisher-Yates
+	pub fn shuffle<T>(&mut self, items: &mut [T]) {
+		for i in (1..items.len()).rev() {
+			let j = self.below(i as u32 + 1) as usize;
+			items.swap(i, j);
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn pcg32_reference_sequence() {
+		// From the PCG reference implementation's pcg32-demo, seeded with (42, 54)
+		let mut rng = Rng::with_stream(42, 54);
+		let expected = [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e];
+		for &e in expected.iter() {
+			assert_eq!(rng.next_u32(), e);
+		}
+	}
+
+	#[test]
+	fn seeds_and_streams() {
+		let a: Vec<u32> = { let mut r = Rng::new(1); (0..8).map(|_| r.next_u32()).collect() };
+		let b: Vec<u32> = { let mut r = Rng::new(1); (0..8).map(|_| r.next_u32()).collect() };
+		let c: Vec<u32> = { let mut r = Rng::new(2); (0..8).map(|_| r.next_u32()).collect() };
+		let d: Vec<u32> = { let mut r = Rng::with_stream(1, 7); (0..8).map(|_| r.next_u32()).collect() };
+		assert_eq!(a, b);
+		assert!(a != c && a != d);
+	}
+
+	#[test]
+	fn floats_in_range() {
+		let mut rng = Rng::new(3);
+		for _ in 0..10000 {
+			let f = rng.next_f32();
+			assert!(f >= 0.0 && f < 1.0);
+			let d = rng.next_f64();
+			assert!(d >= 0.0 && d < 1.0);
+			let r = rng.range_f32(-2.0, 5.0);
+			assert!(r >= -2.0 && r < 5.0);
+			assert!(rng.unit_vec3().len() - 1.0 < 1e-5);
+		}
+	}
+
+	#[test]
+	fn integers_in_range() {
+		let mut rng = Rng::new(4);
+		let mut seen = [false; 7];
+		for _ in 0..10000 {
+			let n = rng.below(7);
+			assert!(n < 7);
+			seen[n as usize] = true;
+
+			let i = rng.range_i32(-3, 4);
+			assert!(i >= -3 && i < 4);
+		}
+		assert!(seen.iter().all(|&s| s));
+
+		assert_eq!(rng.below(1), 0);
+		let i = rng.range_i32(i32::min_value(), i32::max_value());
+		assert!(i < i32::max_value());
+	}
+
+	#[test]
+	fn weighted_index() {
+		let mut rng = Rng::new(5);
+		let weights = [0.0, 1.0, -4.0, 3.0];
+		let mut counts = [0; 4];
+		for _ in 0..10000 {
+			counts[rng.weighted_index(&weights).unwrap()] += 1;
+		}
+
+		// Zero and negative weights are never picked, the rest in proportion
+		assert_eq!(counts[0], 0);
+		assert_eq!(counts[2], 0);
+		assert!(counts[3] > counts[1] * 2 && counts[3] < counts[1] * 4, "{:?}", counts);
+
+		assert_eq!(rng.weighted_index(&[0.0, 0.0]), None);
+		assert_eq!(rng.weighted_index(&[]), None);
+	}
+
+	#[test]
+	fn shuffle_is_a_permutation() {
+		let mut rng = Rng::new(6);
+		let mut items: Vec<u32> = (0..50).collect();
+		rng.shuffle(&mut items);
+		assert!(items != (0..50).collect::<Vec<_>>());
+
+		let mut sorted = items.clone();
+		sorted.sort();
+		assert_eq!(sorted, (0..50).collect::<Vec<_>>());
+
+		let mut empty: [u32; 0] = [];
+		rng.shuffle(&mut empty);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,355 @@
+// Vectorised Mat4 kernels. Built with the `simd` feature these use SSE on x86 and simd128 on
+// wasm when the target has it; everywhere else they fall back to the scalar code in `mat`.
+// Results match the scalar path to within float rounding, not bit for bit
+use mat::*;
+use vec::*;
+
+pub use self::imp::*;
+
+#[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64"), target_feature = "sse"))]
+mod imp {
+	#[cfg(target_arch = "x86")]
+	use std::arch::x86::*;
+	#[cfg(target_arch = "x86_64")]
+	use std::arch::x86_64::*;
+
+	use mat::*;
+	use vec::*;
+
+	pub const ENABLED: bool = true;
+
+	macro_rules! shuffle {
+		($a:expr, $b:expr, $x:expr, $y:expr, $z:expr, $w:expr) => {
+			_mm_shuffle_ps($a, $b, ($x | $y << 2 | $z << 4 | $w << 6))
+		};
+	}
+
+	macro_rules! swizzle {
+		($v:expr, $x:expr, $y:expr, $z:expr, $w:expr) => { shuffle!($v, $v, $x, $y, $z, $w) };
+	}
+
+	unsafe fn load(v: &Vec4) -> __m128 { _mm_loadu_ps(&v.x) }
+	unsafe fn store(v: __m128) -> Vec4 {
+		let mut out = Vec4::zero();
+		_mm_storeu_ps(&mut out.x, v);
+		out
+	}
+
+	// Row `v` times the matrix whose rows are `m`
+	#[inline]
+	unsafe fn row_mul(v: __m128, m: &[__m128; 4]) -> __m128 {
+		let x = _mm_mul_ps(swizzle!(v, 0, 0, 0, 0), m[0]);
+		let y = _mm_mul_ps(swizzle!(v, 1, 1, 1, 1), m[1]);
+		let z = _mm_mul_ps(swizzle!(v, 2, 2, 2, 2), m[2]);
+		let w = _mm_mul_ps(swizzle!(v, 3, 3, 3, 3), m[3]);
+		_mm_add_ps(_mm_add_ps(x, y), _mm_add_ps(z, w))
+	}
+
+	unsafe fn load_mat(m: &Mat4) -> [__m128; 4] {
+		[load(&m.rows[0]), load(&m.rows[1]), load(&m.rows[2]), load(&m.rows[3])]
+	}
+
+	pub fn mul_mat4(a: &Mat4, b: &Mat4) -> Mat4 {
+		unsafe {
+			let b = load_mat(b);
+			Mat4::from_rows(
+				store(row_mul(load(&a.rows[0]), &b)),
+				store(row_mul(load(&a.rows[1]), &b)),
+				store(row_mul(load(&a.rows[2]), &b)),
+				store(row_mul(load(&a.rows[3]), &b))
+			)
+		}
+	}
+
+	pub fn mul_vec4(v: Vec4, m: &Mat4) -> Vec4 {
+		unsafe { store(row_mul(load(&v), &load_mat(m))) }
+	}
+
+	pub fn transform_vec4s(m: &Mat4, vs: &mut [Vec4]) {
+		unsafe {
+			let m = load_mat(m);
+			for v in vs.iter_mut() {
+				*v = store(row_mul(load(v), &m));
+			}
+		}
+	}
+
+	// 2x2 matrices packed as (m00, m01, m10, m11)
+	#[inline]
+	unsafe fn mat2_mul(a: __m128, b: __m128) -> __m128 {
+		_mm_add_ps(
+			_mm_mul_ps(a, swizzle!(b, 0, 3, 0, 3)),
+			_mm_mul_ps(swizzle!(a, 1, 0, 3, 2), swizzle!(b, 2, 1, 2, 1))
+		)
+	}
+
+	// adj(a) * b
+	#[inline]
+	unsafe fn mat2_adj_mul(a: __m128, b: __m128) -> __m128 {
+		_mm_sub_ps(
+			_mm_mul_ps(swizzle!(a, 3, 3, 0, 0), b),
+			_mm_mul_ps(swizzle!(a, 1, 1, 2, 2), swizzle!(b, 2, 3, 0, 1))
+		)
+	}
+
+	// a * adj(b)
+	#[inline]
+	unsafe fn mat2_mul_adj(a: __m128, b: __m128) -> __m128 {
+		_mm_sub_ps(
+			_mm_mul_ps(a, swizzle!(b, 3, 0, 3, 0)),
+			_mm_mul_ps(swizzle!(a, 1, 0, 3, 2), swizzle!(b, 2, 1, 2, 1))
+		)
+	}
+
+	// Block-wise inverse over the four 2x2 quadrants
+	pub fn inverted(m: &Mat4) -> Mat4 {
+		unsafe {
+			let r = load_mat(m);
+
+			let a = _mm_movelh_ps(r[0], r[1]);
+			let b = _mm_movehl_ps(r[1], r[0]);
+			let c = _mm_movelh_ps(r[2], r[3]);
+			let d = _mm_movehl_ps(r[3], r[2]);
+
+			// (|A|, |B|, |C|, |D|)
+			let det_sub = _mm_sub_ps(
+				_mm_mul_ps(shuffle!(r[0], r[2], 0, 2, 0, 2), shuffle!(r[1], r[3], 1, 3, 1, 3)),
+				_mm_mul_ps(shuffle!(r[0], r[2], 1, 3, 1, 3), shuffle!(r[1], r[3], 0, 2, 0, 2))
+			);
+			let det_a = swizzle!(det_sub, 0, 0, 0, 0);
+			let det_b = swizzle!(det_sub, 1, 1, 1, 1);
+			let det_c = swizzle!(det_sub, 2, 2, 2, 2);
+			let det_d = swizzle!(det_sub, 3, 3, 3, 3);
+
+			let d_c = mat2_adj_mul(d, c);
+			let a_b = mat2_adj_mul(a, b);
+
+			let x = _mm_sub_ps(_mm_mul_ps(det_d, a), mat2_mul(b, d_c));
+			let w = _mm_sub_ps(_mm_mul_ps(det_a, d), mat2_mul(c, a_b));
+			let y = _mm_sub_ps(_mm_mul_ps(det_b, c), mat2_mul_adj(d, a_b));
+			let z = _mm_sub_ps(_mm_mul_ps(det_c, b), mat2_mul_adj(a, d_c));
+
+			// |M| = |A||D| + |B||C| - tr(adj(A)B adj(D)C)
+			let mut tr = _mm_mul_ps(a_b, swizzle!(d_c, 0, 2, 1, 3));
+			tr = _mm_add_ps(tr, swizzle!(tr, 1, 0, 3, 2));
+			tr = _mm_add_ps(tr, swizzle!(tr, 2, 3, 0, 1));
+			let det = _mm_sub_ps(_mm_add_ps(_mm_mul_ps(det_a, det_d), _mm_mul_ps(det_b, det_c)), tr);
+
+			let r_det = _mm_div_ps(_mm_setr_ps(1.0, -1.0, -1.0, 1.0), det);
+			let x = _mm_mul_ps(x, r_det);
+			let y = _mm_mul_ps(y, r_det);
+			let z = _mm_mul_ps(z, r_det);
+			let w = _mm_mul_ps(w, r_det);
+
+			Mat4::from_rows(
+				store(shuffle!(x, y, 3, 1, 3, 1)),
+				store(shuffle!(x, y, 2, 0, 2, 0)),
+				store(shuffle!(z, w, 3, 1, 3, 1)),
+				store(shuffle!(z, w, 2, 0, 2, 0))
+			)
+		}
+	}
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+mod imp {
+	use std::arch::wasm32::*;
+
+	use mat::*;
+	use vec::*;
+
+	pub const ENABLED: bool = true;
+
+	unsafe fn load(v: &Vec4) -> v128 { v128_load(&v.x as *const f32 as *const v128) }
+	unsafe fn store(v: v128) -> Vec4 {
+		let mut out = Vec4::zero();
+		v128_store(&mut out.x as *mut f32 as *mut v128, v);
+		out
+	}
+
+	#[inline]
+	unsafe fn row_mul(v: &Vec4, m: &[v128; 4]) -> v128 {
+		let x = f32x4_mul(f32x4_splat(v.x), m[0]);
+		let y = f32x4_mul(f32x4_splat(v.y), m[1]);
+		let z = f32x4_mul(f32x4_splat(v.z), m[2]);
+		let w = f32x4_mul(f32x4_splat(v.w), m[3]);
+		f32x4_add(f32x4_add(x, y), f32x4_add(z, w))
+	}
+
+	unsafe fn load_mat(m: &Mat4) -> [v128; 4] {
+		[load(&m.rows[0]), load(&m.rows[1]), load(&m.rows[2]), load(&m.rows[3])]
+	}
+
+	pub fn mul_mat4(a: &Mat4, b: &Mat4) -> Mat4 {
+		unsafe {
+			let b = load_mat(b);
+			Mat4::from_rows(
+				store(row_mul(&a.rows[0], &b)),
+				store(row_mul(&a.rows[1], &b)),
+				store(row_mul(&a.rows[2], &b)),
+				store(row_mul(&a.rows[3], &b))
+			)
+		}
+	}
+
+	pub fn mul_vec4(v: Vec4, m: &Mat4) -> Vec4 {
+		unsafe { store(row_mul(&v, &load_mat(m))) }
+	}
+
+	pub fn transform_vec4s(m: &Mat4, vs: &mut [Vec4]) {
+		unsafe {
+			let m = load_mat(m);
+			for v in vs.iter_mut() {
+				*v = store(row_mul(v, &m));
+			}
+		}
+	}
+
+	// Shuffles are comparatively slow on wasm, so the inverse stays scalar
+	pub fn inverted(m: &Mat4) -> Mat4 {
+		m.inverted_scalar()
+	}
+}
+
+#[cfg(not(any(
+	all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64"), target_feature = "sse"),
+	all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")
+)))]
+mod imp {
+	use mat::*;
+	use vec::*;
+
+	pub const ENABLED: bool = false;
+
+	pub fn mul_mat4(a: &Mat4, b: &Mat4) -> Mat4 { a.mul_scalar(b) }
+	pub fn mul_vec4(v: Vec4, m: &Mat4) -> Vec4 { m.mul_vec4_scalar(v) }
+	pub fn inverted(m: &Mat4) -> Mat4 { m.inverted_scalar() }
+
+	pub fn transform_vec4s(m: &Mat4, vs: &mut [Vec4]) {
+		for v in vs.iter_mut() {
+			*v = m.mul_vec4_scalar(*v);
+		}
+	}
+}
+
+// Transforms positions in place, without the perspective divide
+pub fn transform_points(m: &Mat4, ps: &mut [Vec3]) {
+	for p in ps.iter_mut() {
+		*p = mul_vec4(p.extend(1.0), m).as_vec3();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use quat::*;
+
+	// Entries here stay within a few hundred, where this is a few dozen ulps
+	const EPS: f32 = 1e-3;
+
+	fn assert_vec4_eq(a: Vec4, b: Vec4) {
+		assert!(a.approx_eq(b, EPS), "{:?} != {:?}", a, b);
+	}
+
+	fn assert_mat4_eq(a: &Mat4, b: &Mat4) {
+		for i in 0..4 {
+			assert_vec4_eq(a[i], b[i]);
+		}
+	}
+
+	// Deterministic values in [-10, 10)
+	struct Rng(u32);
+
+	impl Rng {
+		fn next(&mut self) -> f32 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 17;
+			self.0 ^= self.0 << 5;
+			(self.0 >> 8) as f32 / (1 << 24) as f32 * 20.0 - 10.0
+		}
+
+		fn vec4(&mut self) -> Vec4 { Vec4::new(self.next(), self.next(), self.next(), self.next()) }
+		fn mat4(&mut self) -> Mat4 { Mat4::from_rows(self.vec4(), self.vec4(), self.vec4(), self.vec4()) }
+	}
+
+	fn matrices() -> Vec<Mat4> {
+		let mut rng = Rng(0x9e37_79b9);
+		let mut ms = vec![
+			Mat4::ident(),
+			Mat4::translation(Vec3::new(1.0, -2.0, 3.0)),
+			Mat4::trs(Vec3::new(4.0, 5.0, -6.0), Quat::new(Vec3::new(1.0, 2.0, 3.0).normalized(), 0.7), Vec3::new(2.0, 0.5, 1.5)),
+			Mat4::perspective(1.2, 1.5, 0.1, 100.0),
+			Mat4::ortho(-4.0, 4.0, 3.0, -3.0, 0.5, 20.0),
+			Mat4::look_at(Vec3::new(3.0, 4.0, 5.0), Vec3::zero(), Vec3::from_y(1.0)),
+		];
+		for _ in 0..20 {
+			ms.push(rng.mat4());
+		}
+		ms
+	}
+
+	#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+	#[test]
+	fn enabled() {
+		// Otherwise everything below compares the scalar path with itself
+		assert!(ENABLED);
+	}
+
+	#[test]
+	fn mat4_mul_matches_scalar() {
+		let ms = matrices();
+		for a in ms.iter() {
+			for b in ms.iter() {
+				assert_mat4_eq(&mul_mat4(a, b), &a.mul_scalar(b));
+			}
+		}
+	}
+
+	#[test]
+	fn vec4_mul_matches_scalar() {
+		let mut rng = Rng(12345);
+		for m in matrices().iter() {
+			for _ in 0..10 {
+				let v = rng.vec4();
+				assert_vec4_eq(mul_vec4(v, m), m.mul_vec4_scalar(v));
+			}
+		}
+	}
+
+	#[test]
+	fn inverted_matches_scalar() {
+		for m in matrices().iter() {
+			// Random matrices can be badly conditioned, so compare the products as well as the inverses
+			let (a, b) = (inverted(m), m.inverted_scalar());
+			if m.determinant().abs() > 1.0 {
+				assert_mat4_eq(&a, &b);
+			}
+			assert_mat4_eq(&m.mul_scalar(&a), &Mat4::ident());
+		}
+	}
+
+	#[test]
+	fn batch_transforms_match_scalar() {
+		let mut rng = Rng(777);
+		let vs: Vec<Vec4> = (0..37).map(|_| rng.vec4()).collect();
+		let ps: Vec<Vec3> = vs.iter().map(|v| v.as_vec3()).collect();
+
+		for m in matrices().iter() {
+			let mut out = vs.clone();
+			transform_vec4s(m, &mut out);
+			for (o, v) in out.iter().zip(vs.iter()) {
+				assert_vec4_eq(*o, m.mul_vec4_scalar(*v));
+			}
+
+			let mut out = ps.clone();
+			transform_points(m, &mut out);
+			for (o, p) in out.iter().zip(ps.iter()) {
+				assert_vec4_eq(o.extend(0.0), m.mul_vec4_scalar(p.extend(1.0)).as_vec3().extend(0.0));
+			}
+		}
+
+		// Empty batches are fine
+		transform_vec4s(&Mat4::ident(), &mut []);
+		transform_points(&Mat4::ident(), &mut []);
+	}
+}
\ No newline at end of file
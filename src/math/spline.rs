@@ -0,0 +1,492 @@
+use std::cmp::Ordering;
+use std::ops::{ Add, Sub, Mul };
+
+use vec::*;
+use quat::*;
+use gfx::geom::*;
+
+// Anything curves can be built from. Every curve here is evaluated with repeated `lerp`s,
+// so quaternion curves get slerp for free
+pub trait SplinePoint: Copy {
+	fn lerp(self, o: Self, t: f32) -> Self;
+	fn distance(self, o: Self) -> f32;
+}
+
+// Points that also form a vector space, for derivatives and Hermite tangents
+pub trait SplineVector: SplinePoint + Add<Output=Self> + Sub<Output=Self> + Mul<f32, Output=Self> {
+	fn len(self) -> f32;
+}
+
+macro_rules! impl_spline_vector {
+	($($ty:ident),+) => {
+		$(
+			impl SplinePoint for $ty {
+				fn lerp(self, o: $ty, t: f32) -> $ty { $ty::lerp(self, o, t) }
+				fn distance(self, o: $ty) -> f32 { self.dist(o) }
+			}
+
+			impl SplineVector for $ty {
+				fn len(self) -> f32 { $ty::len(self) }
+			}
+		)+
+	};
+}
+
+impl_spline_vector!(Vec2, Vec3, Vec4);
+
+impl SplinePoint for Quat {
+	fn lerp(self, o: Quat, t: f32) -> Quat { self.slerp(o, t) }
+	fn distance(self, o: Quat) -> f32 { self.angle_between(o) }
+}
+
+// Segments used to seed tessellation, so S-bends aren't mistaken for straight lines
+const TESSELLATION_SEED: usize = 8;
+const TESSELLATION_MAX_DEPTH: u32 = 10;
+
+// A curve over `t` in [0, 1]
+pub trait Curve<P: SplinePoint> {
+	fn eval(&self, t: f32) -> P;
+
+	fn derivative(&self, t: f32) -> P where P: SplineVector {
+		// Central difference, one sided at the ends
+		let h = 0.0005;
+		let t0 = (t - h).max(0.0);
+		let t1 = (t + h).min(1.0);
+		(self.eval(t1) - self.eval(t0)) * (1.0 / (t1 - t0))
+	}
+
+	// Zero where the curve stands still, like at a cusp or between coincident points
+	fn tangent(&self, t: f32) -> P where P: SplineVector {
+		let d = self.derivative(t);
+		let l = d.len();
+		if l > 0.0 { d * (1.0 / l) } else { d * 0.0 }
+	}
+
+	fn arc_length_table(&self, segments: usize) -> ArcLengthTable {
+		let mut lengths = Vec::with_capacity(segments + 1);
+		let mut prev = self.eval(0.0);
+		let mut total = 0.0;
+		lengths.push(0.0);
+
+		for i in 1..segments + 1 {
+			let p = self.eval(i as f32 / segments as f32);
+			total += prev.distance(p);
+			lengths.push(total);
+			prev = p;
+		}
+
+		ArcLengthTable { lengths }
+	}
+
+	fn length(&self) -> f32 {
+		self.arc_length_table(256).total()
+	}
+
+	// Parameter and position of the point on the curve nearest `p`
+	fn closest_point(&self, p: P) -> (f32, P) {
+		let samples = 64;
+		let dist = |t: f32| self.eval(t).distance(p);
+
+		let mut best = 0;
+		let mut best_dist = dist(0.0);
+		for i in 1..samples + 1 {
+			let d = dist(i as f32 / samples as f32);
+			if d < best_dist {
+				best = i;
+				best_dist = d;
+			}
+		}
+
+		// Golden section search around the best sample
+		let ratio = 0.618034;
+		let mut lo = (best as f32 - 1.0).max(0.0) / samples as f32;
+		let mut hi = (best as f32 + 1.0).min(samples as f32) / samples as f32;
+
+		for _ in 0..24 {
+			let a = hi - (hi - lo) * ratio;
+			let b = lo + (hi - lo) * ratio;
+			if dist(a) < dist(b) { hi = b } else { lo = a }
+		}
+
+		let t = (lo + hi) * 0.5;
+		(t, self.eval(t))
+	}
+
+	// Points along the curve, split until each segment's midpoint is within `tolerance` of the curve
+	fn tessellate(&self, tolerance: f32) -> Vec<P> {
+		let mut out = vec![self.eval(0.0)];
+
+		for i in 0..TESSELLATION_SEED {
+			let t0 = i as f32 / TESSELLATION_SEED as f32;
+			let t1 = (i + 1) as f32 / TESSELLATION_SEED as f32;
+			tessellate_segment(self, t0, t1, out[out.len() - 1], self.eval(t1), tolerance, 0, &mut out);
+		}
+
+		out
+	}
+
+	// Appends the tessellated curve to `mesh` as a line strip, see `Mesh::add_line_strip`
+	fn tessellate_into<V, F>(&self, mesh: &mut Mesh<V>, tolerance: f32, vertex: F)
+		where V: Vertex, F: Fn(P) -> V, Self: Sized
+	{
+		mesh.add_line_strip(self.tessellate(tolerance).into_iter().map(vertex));
+	}
+}
+
+fn tessellate_segment<P, C>(curve: &C, t0: f32, t1: f32, p0: P, p1: P, tolerance: f32, depth: u32, out: &mut Vec<P>)
+	where P: SplinePoint, C: Curve<P> + ?Sized
+{
+	let tm = (t0 + t1) * 0.5;
+	let pm = curve.eval(tm);
+
+	if depth < TESSELLATION_MAX_DEPTH && pm.distance(p0.lerp(p1, 0.5)) > tolerance {
+		tessellate_segment(curve, t0, tm, p0, pm, tolerance, depth + 1, out);
+		tessellate_segment(curve, tm, t1, pm, p1, tolerance, depth + 1, out);
+	} else {
+		out.push(p1);
+	}
+}
+
+// Cumulative length at evenly spaced parameters, for moving along a curve at constant speed
+#[derive(Clone, Debug)]
+pub struct ArcLengthTable {
+	lengths: Vec<f32>,
+}
+
+impl ArcLengthTable {
+	pub fn total(&self) -> f32 {
+		self.lengths[self.lengths.len() - 1]
+	}
+
+	// Parameter at `distance` along the curve, clamped to the ends
+	pub fn param_at(&self, distance: f32) -> f32 {
+		let segments = self.lengths.len() - 1;
+		if distance <= 0.0 { return 0.0 }
+		if distance >= self.total() { return 1.0 }
+
+		let i = match self.lengths.binary_search_by(|l| l.partial_cmp(&distance).unwrap_or(Ordering::Equal)) {
+			Ok(i) => return i as f32 / segments as f32,
+			Err(i) => i - 1,
+		};
+
+		let (a, b) = (self.lengths[i], self.lengths[i + 1]);
+		let f = if b > a { (distance - a) / (b - a) } else { 0.0 };
+		(i as f32 + f) / segments as f32
+	}
+
+	// `distance` as a fraction of the total length
+	pub fn param_at_fraction(&self, f: f32) -> f32 {
+		self.param_at(f * self.total())
+	}
+}
+
+// Single Bezier curve of any degree
+#[derive(Clone, Debug)]
+pub struct Bezier<P: SplinePoint> {
+	pub points: Vec<P>,
+}
+
+impl<P: SplinePoint> Bezier<P> {
+	pub fn new(points: Vec<P>) -> Bezier<P> {
+		assert!(points.len() >= 2, "a Bezier curve needs at least two points");
+		Bezier { points }
+	}
+
+	pub fn cubic(p0: P, p1: P, p2: P, p3: P) -> Bezier<P> {
+		Bezier::new(vec![p0, p1, p2, p3])
+	}
+
+	pub fn degree(&self) -> usize { self.points.len() - 1 }
+}
+
+impl<P: SplinePoint> Curve<P> for Bezier<P> {
+	// de Casteljau
+	fn eval(&self, t: f32) -> P {
+		let mut pts = self.points.clone();
+		for n in (1..pts.len()).rev() {
+			for i in 0..n {
+				pts[i] = pts[i].lerp(pts[i + 1], t);
+			}
+		}
+		pts[0]
+	}
+}
+
+// Piecewise cubic through `points` with explicit `tangents` at each
+#[derive(Clone, Debug)]
+pub struct Hermite<P: SplineVector> {
+	pub points: Vec<P>,
+	pub tangents: Vec<P>,
+}
+
+impl<P: SplineVector> Hermite<P> {
+	pub fn new(points: Vec<P>, tangents: Vec<P>) -> Hermite<P> {
+		assert!(points.len() >= 2 && points.len() == tangents.len(), "a Hermite spline needs a tangent for each of at least two points");
+		Hermite { points, tangents }
+	}
+
+	pub fn segments(&self) -> usize { self.points.len() - 1 }
+}
+
+impl<P: SplineVector> Curve<P> for Hermite<P> {
+	fn eval(&self, t: f32) -> P {
+		let (i, u) = segment_at(t, self.segments());
+		let (u2, u3) = (u * u, u * u * u);
+
+		let h00 = 2.0 * u3 - 3.0 * u2 + 1.0;
+		let h10 = u3 - 2.0 * u2 + u;
+		let h01 = -2.0 * u3 + 3.0 * u2;
+		let h11 = u3 - u2;
+
+		self.points[i] * h00 + self.tangents[i] * h10 + self.points[i + 1] * h01 + self.tangents[i + 1] * h11
+	}
+}
+
+// Passes through every point. `alpha` picks the parameterisation: 0 uniform,
+// 0.5 centripetal (no cusps or self intersections within a segment), 1 chordal
+#[derive(Clone, Debug)]
+pub struct CatmullRom<P: SplinePoint> {
+	pub points: Vec<P>,
+	pub alpha: f32,
+	pub closed: bool,
+}
+
+impl<P: SplinePoint> CatmullRom<P> {
+	pub fn new(points: Vec<P>) -> CatmullRom<P> {
+		assert!(points.len() >= 2, "a Catmull-Rom spline needs at least two points");
+		CatmullRom { points, alpha: 0.5, closed: false }
+	}
+
+	pub fn closed(points: Vec<P>) -> CatmullRom<P> {
+		CatmullRom { closed: true, .. CatmullRom::new(points) }
+	}
+
+	pub fn segments(&self) -> usize {
+		if self.closed { self.points.len() } else { self.points.len() - 1 }
+	}
+
+	fn point(&self, i: isize) -> P {
+		let n = self.points.len() as isize;
+		if self.closed {
+			return self.points[((i % n + n) % n) as usize]
+		}
+
+		// Mirror the neighbour past each end
+		if i < 0 {
+			self.points[1].lerp(self.points[0], 2.0)
+		} else if i >= n {
+			self.points[n as usize - 2].lerp(self.points[n as usize - 1], 2.0)
+		} else {
+			self.points[i as usize]
+		}
+	}
+}
+
+impl<P: SplinePoint> Curve<P> for CatmullRom<P> {
+	// Barry and Goldman's pyramid, which only needs lerps
+	fn eval(&self, t: f32) -> P {
+		let (i, u) = segment_at(t, self.segments());
+		let i = i as isize;
+		let (p0, p1, p2, p3) = (self.point(i - 1), self.point(i), self.point(i + 1), self.point(i + 2));
+
+		let knot = |a: P, b: P| a.distance(b).powf(self.alpha).max(1e-4);
+		let t0 = 0.0;
+		let t1 = t0 + knot(p0, p1);
+		let t2 = t1 + knot(p1, p2);
+		let t3 = t2 + knot(p2, p3);
+		let t = t1 + (t2 - t1) * u;
+
+		let a1 = p0.lerp(p1, (t - t0) / (t1 - t0));
+		let a2 = p1.lerp(p2, (t - t1) / (t2 - t1));
+		let a3 = p2.lerp(p3, (t - t2) / (t3 - t2));
+		let b1 = a1.lerp(a2, (t - t0) / (t2 - t0));
+		let b2 = a2.lerp(a3, (t - t1) / (t3 - t1));
+		b1.lerp(b2, (t - t1) / (t2 - t1))
+	}
+}
+
+// Clamped B-spline; passes through the first and last points and is only guided by the rest
+#[derive(Clone, Debug)]
+pub struct BSpline<P: SplinePoint> {
+	pub points: Vec<P>,
+	pub degree: usize,
+	knots: Vec<f32>,
+}
+
+impl<P: SplinePoint> BSpline<P> {
+	// Uniform knots
+	pub fn new(points: Vec<P>, degree: usize) -> BSpline<P> {
+		assert!(degree >= 1 && points.len() > degree, "a B-spline needs more points than its degree");
+
+		let n = points.len();
+		let inner = n - degree;
+		let knots = (0..n + degree + 1)
+			.map(|i| {
+				if i <= degree { 0.0 }
+				else if i >= n { 1.0 }
+				else { (i - degree) as f32 / inner as f32 }
+			})
+			.collect();
+
+		BSpline { points, degree, knots }
+	}
+
+	pub fn knots(&self) -> &[f32] { &self.knots }
+}
+
+impl<P: SplinePoint> Curve<P> for BSpline<P> {
+	// de Boor
+	fn eval(&self, t: f32) -> P {
+		let p = self.degree;
+		let t = t.max(0.0).min(1.0);
+
+		// Knot span containing t, keeping t == 1 in the last non-empty span
+		let mut k = p;
+		while k + 1 < self.points.len() && self.knots[k + 1] <= t {
+			k += 1;
+		}
+
+		let mut d: Vec<P> = (0..p + 1).map(|j| self.points[j + k - p]).collect();
+		for r in 1..p + 1 {
+			for j in (r..p + 1).rev() {
+				let i = j + k - p;
+				let denom = self.knots[i + p + 1 - r] - self.knots[i];
+				let a = if denom > 0.0 { (t - self.knots[i]) / denom } else { 0.0 };
+				d[j] = d[j - 1].lerp(d[j], a);
+			}
+		}
+
+		d[p]
+	}
+}
+
+// Which of `segments` equal parts `t` falls in, and how far along it
+fn segment_at(t: f32, segments: usize) -> (usize, f32) {
+	let s = t.max(0.0).min(1.0) * segments as f32;
+	let i = (s as usize).min(segments - 1);
+	(i, s - i as f32)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn v(x: f32, y: f32) -> Vec2 { Vec2::new(x, y) }
+
+	fn close(a: Vec2, b: Vec2) -> bool { a.approx_eq(b, 1e-4) }
+
+	fn points() -> Vec<Vec2> { vec![v(0.0, 0.0), v(1.0, 2.0), v(3.0, 2.0), v(4.0, 0.0), v(6.0, 1.0)] }
+
+	#[test]
+	fn endpoints() {
+		let ps = points();
+		let (first, last) = (ps[0], ps[ps.len() - 1]);
+
+		let bezier = Bezier::new(ps.clone());
+		let catmull = CatmullRom::new(ps.clone());
+		let bspline = BSpline::new(ps.clone(), 3);
+		let hermite = Hermite::new(ps.clone(), vec![v(1.0, 0.0); ps.len()]);
+
+		for c in [&bezier as &dyn Curve<Vec2>, &catmull, &bspline, &hermite].iter() {
+			assert!(close(c.eval(0.0), first));
+			assert!(close(c.eval(1.0), last));
+		}
+
+		// Interpolating curves also pass through the points in between
+		for (i, &p) in ps.iter().enumerate() {
+			let t = i as f32 / (ps.len() - 1) as f32;
+			assert!(close(catmull.eval(t), p), "{} {:?}", i, catmull.eval(t));
+			assert!(close(hermite.eval(t), p));
+		}
+
+		let closed = CatmullRom::closed(ps.clone());
+		assert!(close(closed.eval(0.0), first));
+		assert!(close(closed.eval(1.0), first));
+	}
+
+	#[test]
+	fn bezier_known_values() {
+		let c = Bezier::cubic(v(0.0, 0.0), v(0.0, 1.0), v(1.0, 1.0), v(1.0, 0.0));
+		assert!(close(c.eval(0.5), v(0.5, 0.75)));
+		assert!(close(c.tangent(0.5), v(1.0, 0.0)));
+		assert_eq!(c.degree(), 3);
+	}
+
+	#[test]
+	fn param_at_follows_arc_length() {
+		// x = t^3 crawls at the start, so half the parameter is only an eighth of the way along
+		let c = Bezier::cubic(v(0.0, 0.0), v(0.0, 0.0), v(0.0, 0.0), v(8.0, 0.0));
+		let table = c.arc_length_table(256);
+		assert!((table.total() - 8.0).abs() < 1e-4);
+
+		assert!((table.param_at(1.0) - 0.5).abs() < 1e-3);
+		assert!((table.param_at_fraction(0.125) - 0.5).abs() < 1e-3);
+		assert_eq!(table.param_at(-1.0), 0.0);
+		assert_eq!(table.param_at(100.0), 1.0);
+
+		for i in 0..9 {
+			let d = i as f32;
+			assert!((c.eval(table.param_at(d)).x - d).abs() < 0.01, "{}", d);
+		}
+	}
+
+	#[test]
+	fn closest_point() {
+		let c = Bezier::new(vec![v(0.0, 0.0), v(10.0, 0.0)]);
+		// The distance is flat around the minimum, so f32 only pins t down to around 1e-4
+		let (t, p) = c.closest_point(v(3.0, 1.0));
+		assert!((t - 0.3).abs() < 1e-3, "{}", t);
+		assert!(p.approx_eq(v(3.0, 0.0), 1e-2));
+
+		let (t, _) = c.closest_point(v(-4.0, 1.0));
+		assert!(t < 1e-4);
+
+		let arc = Bezier::new(vec![v(-1.0, 0.0), v(0.0, 2.0), v(1.0, 0.0)]);
+		let (t, p) = arc.closest_point(v(0.0, 5.0));
+		assert!((t - 0.5).abs() < 1e-3, "{}", t);
+		assert!(p.approx_eq(v(0.0, 1.0), 1e-2), "{:?}", p);
+	}
+
+	#[test]
+	fn tessellation() {
+		let line = Bezier::new(vec![v(0.0, 0.0), v(4.0, 0.0)]);
+		assert_eq!(line.tessellate(0.01).len(), TESSELLATION_SEED + 1);
+
+		let c = Bezier::cubic(v(0.0, 0.0), v(0.0, 4.0), v(4.0, 4.0), v(4.0, 0.0));
+		let coarse = c.tessellate(0.1);
+		let fine = c.tessellate(0.001);
+		assert!(fine.len() > coarse.len());
+
+		for pts in [&coarse, &fine].iter() {
+			assert_eq!(pts[0], v(0.0, 0.0));
+			assert!(close(pts[pts.len() - 1], v(4.0, 0.0)));
+		}
+
+		// Every segment's midpoint is close to the curve
+		for w in fine.windows(2) {
+			let mid = w[0].lerp(w[1], 0.5);
+			assert!(c.closest_point(mid).1.dist(mid) < 0.002);
+		}
+	}
+
+	#[test]
+	fn tangent_is_zero_where_the_curve_stops() {
+		let p = v(1.0, 2.0);
+		let c = Bezier::cubic(p, p, p, p);
+		assert_eq!(c.tangent(0.5), Vec2::zero());
+		assert_eq!(c.tangent(0.0), Vec2::zero());
+
+		let h = Hermite::new(vec![v(0.0, 0.0), v(1.0, 0.0)], vec![v(1.0, 0.0), v(1.0, 0.0)]);
+		assert!(close(h.tangent(0.3), v(1.0, 0.0)));
+	}
+
+	#[test]
+	fn quaternion_curves_slerp() {
+		let a = Quat::ident();
+		let b = Quat::new(Vec3::from_z(1.0), 1.0);
+		let c = Bezier::new(vec![a, b]);
+		assert!(c.eval(0.5).approx_eq(Quat::new(Vec3::from_z(1.0), 0.5), 1e-5));
+		assert!((c.length() - 1.0).abs() < 1e-3, "{}", c.length());
+	}
+}
\ No newline at end of file
@@ -3,7 +3,7 @@ use std::ops::{ Add, Sub, Mul, Div, Neg, Index, IndexMut };
 use std::ops::{ AddAssign, SubAssign, MulAssign, DivAssign };
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec2 { pub x: f32, pub y: f32 }
 
 #[repr(C)]
@@ -11,13 +11,39 @@ pub struct Vec2 { pub x: f32, pub y: f32 }
 pub struct Vec2i { pub x: i32, pub y: i32 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec3 { pub x: f32, pub y: f32, pub z: f32 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec4 { pub x: f32, pub y: f32, pub z: f32, pub w: f32 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Vec3i { pub x: i32, pub y: i32, pub z: i32 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Vec4i { pub x: i32, pub y: i32, pub z: i32, pub w: i32 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Vec2u { pub x: u32, pub y: u32 }
+
+// Double precision, for accumulating positions or time without drift.
+// Convert to the f32 types before handing anything to GL
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vec2d { pub x: f64, pub y: f64 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vec3d { pub x: f64, pub y: f64, pub z: f64 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vec4d { pub x: f64, pub y: f64, pub z: f64, pub w: f64 }
+
 impl Vec2 {
 	pub fn new(x: f32, y: f32) -> Vec2 { Vec2 { x, y } }
 	pub fn uniform(v: f32) -> Vec2 { Vec2::new(v, v) }
@@ -26,6 +52,7 @@ impl Vec2 {
 
 	pub fn as_tuple(self) -> (f32, f32) { (self.x, self.y) }
 	pub fn as_vec2i(self) -> Vec2i { Vec2i::new(self.x as i32, self.y as i32) }
+	pub fn as_vec2d(self) -> Vec2d { Vec2d::new(self.x as f64, self.y as f64) }
 
 	pub fn extend(self, z: f32) -> Vec3 { Vec3::new(self.x, self.y, z) }
 
@@ -41,9 +68,14 @@ impl Vec3 {
 	pub fn new(x: f32, y: f32, z: f32) -> Vec3 { Vec3 { x, y, z } }
 	pub fn uniform(v: f32) -> Vec3 { Vec3::new(v, v, v) }
 	pub fn zero() -> Vec3 { Vec3::uniform(0.0) }
+	pub fn from_x(x: f32) -> Vec3 { Vec3::new(x, 0.0, 0.0) }
+	pub fn from_y(y: f32) -> Vec3 { Vec3::new(0.0, y, 0.0) }
+	pub fn from_z(z: f32) -> Vec3 { Vec3::new(0.0, 0.0, z) }
 
 	pub fn as_tuple(self) -> (f32, f32, f32) { (self.x, self.y, self.z) }
 	pub fn as_vec2(self) -> Vec2 { Vec2::new(self.x, self.y) }
+	pub fn as_vec3i(self) -> Vec3i { Vec3i::new(self.x as i32, self.y as i32, self.z as i32) }
+	pub fn as_vec3d(self) -> Vec3d { Vec3d::new(self.x as f64, self.y as f64, self.z as f64) }
 
 	pub fn extend(self, w: f32) -> Vec4 { Vec4::new(self.x, self.y, self.z, w) }
 
@@ -74,6 +106,8 @@ impl Vec4 {
 
 	pub fn as_tuple(self) -> (f32, f32, f32, f32) { (self.x, self.y, self.z, self.w) }
 	pub fn as_vec3(self) -> Vec3 { Vec3::new(self.x, self.y, self.z) }
+	pub fn as_vec4i(self) -> Vec4i { Vec4i::new(self.x as i32, self.y as i32, self.z as i32, self.w as i32) }
+	pub fn as_vec4d(self) -> Vec4d { Vec4d::new(self.x as f64, self.y as f64, self.z as f64, self.w as f64) }
 
 	pub fn dot(self, o: Vec4) -> f32 { self.x * o.x + self.y * o.y + self.z * o.z + self.w * o.w }
 	
@@ -82,6 +116,46 @@ impl Vec4 {
 	pub fn normalized(self) -> Vec4 { self * (1.0 / self.len()) }
 }
 
+impl Index<usize> for Vec2 {
+	type Output = f32;
+	fn index(&self, i: usize) -> &f32 {
+		match i {
+			0 => { &self.x },
+			_ => { &self.y }
+		}
+	}
+}
+
+impl IndexMut<usize> for Vec2 {
+	fn index_mut(&mut self, i: usize) -> &mut f32 {
+		match i {
+			0 => { &mut self.x },
+			_ => { &mut self.y }
+		}
+	}
+}
+
+impl Index<usize> for Vec3 {
+	type Output = f32;
+	fn index(&self, i: usize) -> &f32 {
+		match i {
+			0 => { &self.x },
+			1 => { &self.y },
+			_ => { &self.z }
+		}
+	}
+}
+
+impl IndexMut<usize> for Vec3 {
+	fn index_mut(&mut self, i: usize) -> &mut f32 {
+		match i {
+			0 => { &mut self.x },
+			1 => { &mut self.y },
+			_ => { &mut self.z }
+		}
+	}
+}
+
 impl Index<usize> for Vec4 {
 	type Output = f32;
 	fn index(&self, i: usize) -> &f32 {
@@ -115,6 +189,100 @@ impl Vec2i {
 	pub fn as_vec2(self) -> Vec2 { Vec2::new(self.x as f32, self.y as f32) }
 
 	pub fn len(self) -> f32 { ((self.x * self.x + self.y * self.y) as f32).sqrt() }
+
+	pub fn extend(self, z: i32) -> Vec3i { Vec3i::new(self.x, self.y, z) }
+}
+
+impl Vec3i {
+	pub fn new(x: i32, y: i32, z: i32) -> Vec3i { Vec3i { x, y, z } }
+	pub fn uniform(v: i32) -> Vec3i { Vec3i::new(v, v, v) }
+	pub fn zero() -> Vec3i { Vec3i::uniform(0) }
+	pub fn from_tuple(t: (i32, i32, i32)) -> Vec3i { Vec3i::new(t.0, t.1, t.2) }
+
+	pub fn as_tuple(self) -> (i32, i32, i32) { (self.x, self.y, self.z) }
+	pub fn as_vec3(self) -> Vec3 { Vec3::new(self.x as f32, self.y as f32, self.z as f32) }
+	pub fn as_vec2i(self) -> Vec2i { Vec2i::new(self.x, self.y) }
+
+	pub fn extend(self, w: i32) -> Vec4i { Vec4i::new(self.x, self.y, self.z, w) }
+}
+
+impl Vec4i {
+	pub fn new(x: i32, y: i32, z: i32, w: i32) -> Vec4i { Vec4i { x, y, z, w } }
+	pub fn uniform(v: i32) -> Vec4i { Vec4i::new(v, v, v, v) }
+	pub fn zero() -> Vec4i { Vec4i::uniform(0) }
+	pub fn from_tuple(t: (i32, i32, i32, i32)) -> Vec4i { Vec4i::new(t.0, t.1, t.2, t.3) }
+
+	pub fn as_tuple(self) -> (i32, i32, i32, i32) { (self.x, self.y, self.z, self.w) }
+	pub fn as_vec4(self) -> Vec4 { Vec4::new(self.x as f32, self.y as f32, self.z as f32, self.w as f32) }
+	pub fn as_vec3i(self) -> Vec3i { Vec3i::new(self.x, self.y, self.z) }
+}
+
+impl Vec2u {
+	pub fn new(x: u32, y: u32) -> Vec2u { Vec2u { x, y } }
+	pub fn uniform(v: u32) -> Vec2u { Vec2u::new(v, v) }
+	pub fn zero() -> Vec2u { Vec2u::uniform(0) }
+	pub fn from_tuple(t: (u32, u32)) -> Vec2u { Vec2u::new(t.0, t.1) }
+
+	pub fn as_tuple(self) -> (u32, u32) { (self.x, self.y) }
+	pub fn as_vec2(self) -> Vec2 { Vec2::new(self.x as f32, self.y as f32) }
+	pub fn as_vec2i(self) -> Vec2i { Vec2i::new(self.x as i32, self.y as i32) }
+
+	// Number of cells in a grid of this size
+	pub fn area(self) -> u32 { self.x * self.y }
+}
+
+impl Vec2d {
+	pub fn new(x: f64, y: f64) -> Vec2d { Vec2d { x, y } }
+	pub fn uniform(v: f64) -> Vec2d { Vec2d::new(v, v) }
+	pub fn zero() -> Vec2d { Vec2d::uniform(0.0) }
+
+	pub fn as_tuple(self) -> (f64, f64) { (self.x, self.y) }
+	pub fn as_vec2(self) -> Vec2 { Vec2::new(self.x as f32, self.y as f32) }
+
+	pub fn extend(self, z: f64) -> Vec3d { Vec3d::new(self.x, self.y, z) }
+
+	pub fn dot(self, o: Vec2d) -> f64 { self.x * o.x + self.y * o.y }
+	pub fn perp(self) -> Vec2d { Vec2d::new(-self.y, self.x) }
+	pub fn len(self) -> f64 { self.dot(self).sqrt() }
+	pub fn normalized(self) -> Vec2d { self * (1.0 / self.len()) }
+}
+
+impl Vec3d {
+	pub fn new(x: f64, y: f64, z: f64) -> Vec3d { Vec3d { x, y, z } }
+	pub fn uniform(v: f64) -> Vec3d { Vec3d::new(v, v, v) }
+	pub fn zero() -> Vec3d { Vec3d::uniform(0.0) }
+
+	pub fn as_tuple(self) -> (f64, f64, f64) { (self.x, self.y, self.z) }
+	pub fn as_vec3(self) -> Vec3 { Vec3::new(self.x as f32, self.y as f32, self.z as f32) }
+
+	pub fn extend(self, w: f64) -> Vec4d { Vec4d::new(self.x, self.y, self.z, w) }
+
+	pub fn dot(self, o: Vec3d) -> f64 { self.x * o.x + self.y * o.y + self.z * o.z }
+
+	pub fn cross(self, o: Vec3d) -> Vec3d {
+		Vec3d::new(
+			self.y * o.z - self.z * o.y,
+			self.z * o.x - self.x * o.z,
+			self.x * o.y - self.y * o.x
+		)
+	}
+
+	pub fn len(self) -> f64 { self.dot(self).sqrt() }
+	pub fn normalized(self) -> Vec3d { self * (1.0 / self.len()) }
+}
+
+impl Vec4d {
+	pub fn new(x: f64, y: f64, z: f64, w: f64) -> Vec4d { Vec4d { x, y, z, w } }
+	pub fn uniform(v: f64) -> Vec4d { Vec4d::new(v, v, v, v) }
+	pub fn zero() -> Vec4d { Vec4d::uniform(0.0) }
+
+	pub fn as_tuple(self) -> (f64, f64, f64, f64) { (self.x, self.y, self.z, self.w) }
+	pub fn as_vec4(self) -> Vec4 { Vec4::new(self.x as f32, self.y as f32, self.z as f32, self.w as f32) }
+	pub fn as_vec3d(self) -> Vec3d { Vec3d::new(self.x, self.y, self.z) }
+
+	pub fn dot(self, o: Vec4d) -> f64 { self.x * o.x + self.y * o.y + self.z * o.z + self.w * o.w }
+	pub fn len(self) -> f64 { self.dot(self).sqrt() }
+	pub fn normalized(self) -> Vec4d { self * (1.0 / self.len()) }
 }
 
 macro_rules! impl_vector_bin_op {
@@ -155,6 +323,17 @@ macro_rules! impl_vector_bin_op {
 
 macro_rules! bulk_impl_vector_ops {
 	($ty:ident, $scalar:ty, $($els:ident),+) => {
+		bulk_impl_vector_ops!(unsigned $ty, $scalar, $($els),+);
+
+		impl Neg for $ty {
+			type Output = $ty;
+			fn neg(self) -> $ty {
+				$ty::new($(-self.$els),+)
+			}
+		}
+	};
+
+	(unsigned $ty:ident, $scalar:ty, $($els:ident),+) => {
 		impl_vector_bin_op!($ty, Add<$scalar>, add, +, $($els),+);
 		impl_vector_bin_op!($ty, Sub<$scalar>, sub, -, $($els),+);
 		impl_vector_bin_op!($ty, Mul<$scalar>, mul, *, $($els),+);
@@ -164,17 +343,303 @@ macro_rules! bulk_impl_vector_ops {
 		impl_vector_bin_op!(ass $ty, SubAssign<$scalar>, sub_assign, -=, $($els),+);
 		impl_vector_bin_op!(ass $ty, MulAssign<$scalar>, mul_assign, *=, $($els),+);
 		impl_vector_bin_op!(ass $ty, DivAssign<$scalar>, div_assign, /=, $($els),+);
-
-		impl Neg for $ty {
-			type Output = $ty;
-			fn neg(self) -> $ty {
-				$ty::new($(-self.$els),+)
-			}
-		}
 	};
 }
 
 bulk_impl_vector_ops!(Vec2, f32, x, y);
 bulk_impl_vector_ops!(Vec3, f32, x, y, z);
 bulk_impl_vector_ops!(Vec4, f32, x, y, z, w);
-bulk_impl_vector_ops!(Vec2i, i32, x, y);
\ No newline at end of file
+bulk_impl_vector_ops!(Vec2i, i32, x, y);
+bulk_impl_vector_ops!(Vec3i, i32, x, y, z);
+bulk_impl_vector_ops!(Vec4i, i32, x, y, z, w);
+bulk_impl_vector_ops!(unsigned Vec2u, u32, x, y);
+
+bulk_impl_vector_ops!(Vec2d, f64, x, y);
+bulk_impl_vector_ops!(Vec3d, f64, x, y, z);
+bulk_impl_vector_ops!(Vec4d, f64, x, y, z, w);
+
+// Whether `a` and `b` are at most `max_ulps` representable floats apart.
+// Unlike a fixed epsilon this scales with magnitude, but is useless near zero
+pub fn ulps_eq(a: f32, b: f32, max_ulps: u32) -> bool {
+	if a.is_nan() || b.is_nan() { return false }
+	if a == b { return true }
+	if a.is_sign_positive() != b.is_sign_positive() { return false }
+
+	let diff = (a.to_bits() as i64 - b.to_bits() as i64).abs();
+	diff <= max_ulps as i64
+}
+
+pub fn ulps_eq_f64(a: f64, b: f64, max_ulps: u64) -> bool {
+	if a.is_nan() || b.is_nan() { return false }
+	if a == b { return true }
+	if a.is_sign_positive() != b.is_sign_positive() { return false }
+
+	let (a, b) = (a.to_bits(), b.to_bits());
+	let diff = if a > b { a - b } else { b - a };
+	diff <= max_ulps
+}
+
+macro_rules! impl_float_vector {
+	($ty:ident, $scalar:ident, $ulps:ty, $ulps_eq:ident, $($els:ident),+) => {
+		impl $ty {
+			pub fn len_sq(self) -> $scalar { self.dot(self) }
+			pub fn dist(self, o: $ty) -> $scalar { (o - self).len() }
+			pub fn dist_sq(self, o: $ty) -> $scalar { (o - self).len_sq() }
+
+			// Zero stays zero instead of becoming NaN
+			pub fn normalized_or_zero(self) -> $ty {
+				let l = self.len();
+				if l > 0.0 { self * (1.0 / l) } else { $ty::zero() }
+			}
+
+			pub fn lerp(self, o: $ty, t: $scalar) -> $ty { self + (o - self) * t }
+
+			pub fn min(self, o: $ty) -> $ty { $ty::new($(self.$els.min(o.$els)),+) }
+			pub fn max(self, o: $ty) -> $ty { $ty::new($(self.$els.max(o.$els)),+) }
+			pub fn clamp(self, lo: $ty, hi: $ty) -> $ty { self.max(lo).min(hi) }
+
+			pub fn abs(self) -> $ty { $ty::new($(self.$els.abs()),+) }
+			pub fn floor(self) -> $ty { $ty::new($(self.$els.floor()),+) }
+			pub fn ceil(self) -> $ty { $ty::new($(self.$els.ceil()),+) }
+			pub fn round(self) -> $ty { $ty::new($(self.$els.round()),+) }
+			pub fn signum(self) -> $ty { $ty::new($(self.$els.signum()),+) }
+			pub fn recip(self) -> $ty { $ty::new($(self.$els.recip()),+) }
+
+			pub fn min_element(self) -> $scalar { [$(self.$els),+].iter().cloned().fold(::std::$scalar::INFINITY, $scalar::min) }
+			pub fn max_element(self) -> $scalar { [$(self.$els),+].iter().cloned().fold(::std::$scalar::NEG_INFINITY, $scalar::max) }
+
+			// Component-wise product
+			pub fn scale(self, o: $ty) -> $ty { $ty::new($(self.$els * o.$els),+) }
+
+			// Longer vectors are shortened to `max`, shorter ones are left alone
+			pub fn clamp_len(self, max: $scalar) -> $ty {
+				let l = self.len();
+				if l > max { self * (max / l) } else { self }
+			}
+
+			// `n` must be normalized
+			pub fn reflect(self, n: $ty) -> $ty { self - n * (2.0 * self.dot(n)) }
+
+			// `self` and `n` must be normalized, `eta` is the ratio of refractive indices.
+			// None on total internal reflection
+			pub fn refract(self, n: $ty, eta: $scalar) -> Option<$ty> {
+				let d = self.dot(n);
+				let k = 1.0 - eta * eta * (1.0 - d * d);
+				if k < 0.0 {
+					None
+				} else {
+					Some(self * eta - n * (eta * d + k.sqrt()))
+				}
+			}
+
+			pub fn project_onto(self, o: $ty) -> $ty { o * (self.dot(o) / o.len_sq()) }
+			pub fn reject_from(self, o: $ty) -> $ty { self - self.project_onto(o) }
+
+			// Unsigned, in [0, pi]
+			pub fn angle_between(self, o: $ty) -> $scalar {
+				let d = self.dot(o) / (self.len() * o.len());
+				d.max(-1.0).min(1.0).acos()
+			}
+
+			pub fn is_finite(self) -> bool { $(self.$els.is_finite())&&+ }
+
+			pub fn approx_eq(self, o: $ty, epsilon: $scalar) -> bool {
+				$((self.$els - o.$els).abs() <= epsilon)&&+
+			}
+
+			pub fn approx_eq_ulps(self, o: $ty, max_ulps: $ulps) -> bool {
+				$($ulps_eq(self.$els, o.$els, max_ulps))&&+
+			}
+		}
+	};
+}
+
+impl_float_vector!(Vec2, f32, u32, ulps_eq, x, y);
+impl_float_vector!(Vec3, f32, u32, ulps_eq, x, y, z);
+impl_float_vector!(Vec4, f32, u32, ulps_eq, x, y, z, w);
+
+impl_float_vector!(Vec2d, f64, u64, ulps_eq_f64, x, y);
+impl_float_vector!(Vec3d, f64, u64, ulps_eq_f64, x, y, z);
+impl_float_vector!(Vec4d, f64, u64, ulps_eq_f64, x, y, z, w);
+
+macro_rules! impl_swizzles {
+	($ty:ident => $out:ident { $($name:ident: $($els:ident),+;)+ }) => {
+		impl $ty {
+			$(
+				pub fn $name(self) -> $out { $out::new($(self.$els),+) }
+			)+
+		}
+	};
+}
+
+impl_swizzles!(Vec2 => Vec2 { yx: y, x; });
+
+impl_swizzles!(Vec3 => Vec2 {
+	xy: x, y;  xz: x, z;  yz: y, z;
+	yx: y, x;  zx: z, x;  zy: z, y;
+});
+
+impl_swizzles!(Vec3 => Vec3 {
+	xzy: x, z, y;  yxz: y, x, z;  yzx: y, z, x;
+	zxy: z, x, y;  zyx: z, y, x;
+});
+
+impl_swizzles!(Vec4 => Vec2 { xy: x, y;  zw: z, w; });
+impl_swizzles!(Vec4 => Vec3 { xyz: x, y, z;  xyw: x, y, w; });
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn arithmetic() {
+		let a = Vec3::new(1.0, 2.0, 3.0);
+		let b = Vec3::new(4.0, -5.0, 6.0);
+		assert_eq!(a + b, Vec3::new(5.0, -3.0, 9.0));
+		assert_eq!(a - b, Vec3::new(-3.0, 7.0, -3.0));
+		assert_eq!(a * 2.0, Vec3::new(2.0, 4.0, 6.0));
+		assert_eq!(-a, Vec3::new(-1.0, -2.0, -3.0));
+		assert_eq!(a.dot(b), 12.0);
+		assert_eq!(Vec3::from_x(1.0).cross(Vec3::from_y(1.0)), Vec3::from_z(1.0));
+
+		let mut c = Vec2i::new(1, 2);
+		c += Vec2i::new(3, 4);
+		c *= 2;
+		assert_eq!(c, Vec2i::new(8, 12));
+	}
+
+	#[test]
+	fn lengths_and_distances() {
+		let v = Vec3::new(3.0, 4.0, 12.0);
+		assert_eq!(v.len(), 13.0);
+		assert_eq!(v.len_sq(), 169.0);
+		assert_eq!(Vec2::new(1.0, 1.0).dist(Vec2::new(4.0, 5.0)), 5.0);
+		assert_eq!(Vec2::new(1.0, 1.0).dist_sq(Vec2::new(4.0, 5.0)), 25.0);
+		assert!((v.normalized().len() - 1.0).abs() < 1e-6);
+	}
+
+	#[test]
+	fn normalizing_zero() {
+		assert_eq!(Vec3::zero().normalized_or_zero(), Vec3::zero());
+		assert_eq!(Vec2d::zero().normalized_or_zero(), Vec2d::zero());
+		assert_eq!(Vec4::new(0.0, 0.0, 2.0, 0.0).normalized_or_zero(), Vec4::new(0.0, 0.0, 1.0, 0.0));
+
+		// The plain version divides by zero
+		assert!(!Vec3::zero().normalized().is_finite());
+	}
+
+	#[test]
+	fn lerp_endpoints() {
+		let a = Vec3::new(1.0, -2.0, 3.0);
+		let b = Vec3::new(-4.0, 8.0, 0.5);
+		assert_eq!(a.lerp(b, 0.0), a);
+		assert_eq!(a.lerp(b, 1.0), b);
+		assert_eq!(a.lerp(b, 0.5), Vec3::new(-1.5, 3.0, 1.75));
+		assert_eq!(Vec2d::new(0.0, 10.0).lerp(Vec2d::new(10.0, 0.0), 0.25), Vec2d::new(2.5, 7.5));
+	}
+
+	#[test]
+	fn component_wise() {
+		let a = Vec3::new(-1.5, 2.5, 0.2);
+		let b = Vec3::new(1.0, 1.0, 1.0);
+		assert_eq!(a.min(b), Vec3::new(-1.5, 1.0, 0.2));
+		assert_eq!(a.max(b), Vec3::new(1.0, 2.5, 1.0));
+		assert_eq!(a.clamp(Vec3::uniform(-1.0), Vec3::uniform(1.0)), Vec3::new(-1.0, 1.0, 0.2));
+		assert_eq!(a.abs(), Vec3::new(1.5, 2.5, 0.2));
+		assert_eq!(a.floor(), Vec3::new(-2.0, 2.0, 0.0));
+		assert_eq!(a.ceil(), Vec3::new(-1.0, 3.0, 1.0));
+		assert_eq!(a.signum(), Vec3::new(-1.0, 1.0, 1.0));
+		assert_eq!(Vec2::new(2.0, 4.0).recip(), Vec2::new(0.5, 0.25));
+		assert_eq!(a.min_element(), -1.5);
+		assert_eq!(a.max_element(), 2.5);
+		assert_eq!(a.scale(Vec3::new(2.0, 2.0, 10.0)), Vec3::new(-3.0, 5.0, 2.0));
+	}
+
+	#[test]
+	fn clamp_len() {
+		assert_eq!(Vec2::new(3.0, 4.0).clamp_len(1.0), Vec2::new(0.6, 0.8));
+		assert_eq!(Vec2::new(0.3, 0.4).clamp_len(1.0), Vec2::new(0.3, 0.4));
+	}
+
+	#[test]
+	fn reflect_and_refract() {
+		let n = Vec3::from_y(1.0);
+		assert_eq!(Vec3::new(1.0, -1.0, 0.0).reflect(n), Vec3::new(1.0, 1.0, 0.0));
+
+		// Straight through when the indices match
+		let d = Vec3::new(1.0, -1.0, 0.0).normalized();
+		assert!(d.refract(n, 1.0).unwrap().approx_eq(d, 1e-6));
+
+		// Total internal reflection at a grazing angle into a less dense medium
+		let grazing = Vec3::new(1.0, -0.1, 0.0).normalized();
+		assert!(grazing.refract(n, 1.5).is_none());
+	}
+
+	#[test]
+	fn projection_and_angles() {
+		let v = Vec3::new(2.0, 3.0, 0.0);
+		let x = Vec3::from_x(5.0);
+		assert_eq!(v.project_onto(x), Vec3::from_x(2.0));
+		assert_eq!(v.reject_from(x), Vec3::from_y(3.0));
+
+		assert!((Vec2::new(1.0, 0.0).angle_between(Vec2::new(0.0, 3.0)) - ::std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+		assert!((Vec3::from_x(1.0).angle_between(Vec3::from_x(-2.0)) - ::std::f32::consts::PI).abs() < 1e-6);
+
+		// Parallel vectors whose dot rounds past 1 give about 0 rather than NaN
+		let p = Vec3::new(0.1, 0.2, 0.3);
+		assert!(p.angle_between(p * 3.0) < 1e-3);
+	}
+
+	#[test]
+	fn approximate_equality() {
+		let a = Vec3::new(1.0, 2.0, 3.0);
+		assert!(a.approx_eq(a + Vec3::uniform(1e-4), 1e-3));
+		assert!(!a.approx_eq(a + Vec3::from_z(1e-2), 1e-3));
+
+		let next = f32::from_bits(1.0f32.to_bits() + 2);
+		assert!(ulps_eq(1.0, next, 2));
+		assert!(!ulps_eq(1.0, next, 1));
+		assert!(!ulps_eq(0.0, ::std::f32::NAN, 10));
+		assert!(ulps_eq(0.0, -0.0, 0));
+		assert!(ulps_eq_f64(1.0, f64::from_bits(1.0f64.to_bits() + 1), 1));
+		assert!(Vec2::new(1.0, 2.0).approx_eq_ulps(Vec2::new(1.0, 2.0), 0));
+
+		assert!(!Vec2::new(1.0, ::std::f32::INFINITY).is_finite());
+	}
+
+	#[test]
+	fn swizzles() {
+		let v = Vec4::new(1.0, 2.0, 3.0, 4.0);
+		assert_eq!(v.xy(), Vec2::new(1.0, 2.0));
+		assert_eq!(v.zw(), Vec2::new(3.0, 4.0));
+		assert_eq!(v.xyz(), Vec3::new(1.0, 2.0, 3.0));
+		assert_eq!(v.xyw(), Vec3::new(1.0, 2.0, 4.0));
+
+		let u = Vec3::new(1.0, 2.0, 3.0);
+		assert_eq!(u.xz(), Vec2::new(1.0, 3.0));
+		assert_eq!(u.zy(), Vec2::new(3.0, 2.0));
+		assert_eq!(u.zyx(), Vec3::new(3.0, 2.0, 1.0));
+		assert_eq!(u.yzx(), Vec3::new(2.0, 3.0, 1.0));
+		assert_eq!(Vec2::new(1.0, 2.0).yx(), Vec2::new(2.0, 1.0));
+	}
+
+	#[test]
+	fn conversions() {
+		assert_eq!(Vec2::new(1.9, -1.9).as_vec2i(), Vec2i::new(1, -1));
+		assert_eq!(Vec2i::new(3, 4).as_vec2(), Vec2::new(3.0, 4.0));
+		assert_eq!(Vec3::new(1.0, 2.0, 3.0).extend(4.0), Vec4::new(1.0, 2.0, 3.0, 4.0));
+		assert_eq!(Vec4::new(1.0, 2.0, 3.0, 4.0).as_vec3(), Vec3::new(1.0, 2.0, 3.0));
+		assert_eq!(Vec4::from_slice(&[1.0, 2.0, 3.0, 4.0, 5.0]), Vec4::new(1.0, 2.0, 3.0, 4.0));
+		assert_eq!(Vec3::new(1.5, 2.5, 3.5).as_vec3d().as_vec3(), Vec3::new(1.5, 2.5, 3.5));
+		assert_eq!(Vec3i::new(1, 2, 3).extend(4).as_vec3i(), Vec3i::new(1, 2, 3));
+		assert_eq!(Vec2u::new(3, 5).area(), 15);
+		assert_eq!(Vec2u::new(3, 5).as_vec2i(), Vec2i::new(3, 5));
+		assert_eq!(Vec4d::new(1.0, 2.0, 3.0, 4.0).as_vec3d(), Vec3d::new(1.0, 2.0, 3.0));
+		assert_eq!(Vec2::new(1.0, 2.0).as_tuple(), (1.0, 2.0));
+
+		let mut v = Vec4::zero();
+		v[2] = 5.0;
+		assert_eq!(v[2], 5.0);
+		assert_eq!(v.z, 5.0);
+	}
+}
\ No newline at end of file
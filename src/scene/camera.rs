@@ -0,0 +1,450 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::f32::consts::PI;
+
+use math::vec::*;
+use math::mat::*;
+use math::quat::*;
+use math::geom::*;
+use core::event::*;
+use core::context::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+	// `fov` is the full vertical field of view in radians
+	Perspective { fov: f32, near: f32, far: f32 },
+
+	// `height` is the vertical extent in world units; the width follows the aspect ratio
+	Orthographic { height: f32, near: f32, far: f32 },
+}
+
+#[derive(Copy, Clone)]
+struct Matrices {
+	view: Mat4,
+	proj: Mat4,
+	view_proj: Mat4,
+	inv_view_proj: Mat4,
+}
+
+// A viewpoint with a projection and a viewport on the screen. Looks down its local -z with +y up.
+// Screen coordinates are the same as mouse events: pixels from the top-left of the canvas
+pub struct Camera {
+	position: Vec3,
+	rotation: Quat,
+	projection: Projection,
+
+	// Fraction of the screen covered, so it follows resizes
+	viewport: Rect,
+	screen_size: Vec2,
+
+	matrices: Cell<Option<Matrices>>,
+}
+
+impl Camera {
+	pub fn new(projection: Projection) -> Camera {
+		Camera {
+			position: Vec3::zero(),
+			rotation: Quat::ident(),
+			projection,
+			viewport: Rect::new(Vec2::zero(), Vec2::uniform(1.0)),
+			screen_size: Vec2::uniform(1.0),
+			matrices: Cell::new(None),
+		}
+	}
+
+	pub fn perspective(fov: f32, near: f32, far: f32) -> Camera {
+		Camera::new(Projection::Perspective { fov, near, far })
+	}
+
+	pub fn orthographic(height: f32, near: f32, far: f32) -> Camera {
+		Camera::new(Projection::Orthographic { height, near, far })
+	}
+
+	fn invalidate(&mut self) {
+		self.matrices.set(None);
+	}
+
+	pub fn position(&self) -> Vec3 { self.position }
+	pub fn rotation(&self) -> Quat { self.rotation }
+	pub fn projection(&self) -> Projection { self.projection }
+
+	pub fn set_position(&mut self, position: Vec3) {
+		self.position = position;
+		self.invalidate();
+	}
+
+	pub fn set_rotation(&mut self, rotation: Quat) {
+		self.rotation = rotation;
+		self.invalidate();
+	}
+
+	pub fn set_projection(&mut self, projection: Projection) {
+		self.projection = projection;
+		self.invalidate();
+	}
+
+	// Turns to face `target`. `up` must not be parallel to the view direction
+	pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+		let rotation = Quat::look_rotation(target - self.position, up);
+		self.set_rotation(rotation);
+	}
+
+	// Places the camera using a view matrix, e.g. from Mat4::look_at
+	pub fn set_view(&mut self, view: Mat4) {
+		let (position, rotation, _) = view.affine_inverted().decompose();
+		self.position = position;
+		self.rotation = rotation;
+		self.invalidate();
+	}
+
+	pub fn forward(&self) -> Vec3 { self.rotation * Vec3::from_z(-1.0) }
+	pub fn right(&self) -> Vec3 { self.rotation * Vec3::from_x(1.0) }
+	pub fn up(&self) -> Vec3 { self.rotation * Vec3::from_y(1.0) }
+
+	// `viewport` is in fractions of the screen, with (0, 0) at the top-left
+	pub fn set_viewport(&mut self, viewport: Rect) {
+		self.viewport = viewport;
+		self.invalidate();
+	}
+
+	pub fn set_screen_size(&mut self, size: Vec2i) {
+		self.screen_size = size.as_vec2();
+		self.invalidate();
+	}
+
+	// Keeps the aspect ratio in step with the canvas
+	pub fn handle_event(&mut self, e: &Event) {
+		if let Event::Resize(size) = *e {
+			self.set_screen_size(size);
+		}
+	}
+
+	// Viewport in screen pixels
+	pub fn viewport(&self) -> Rect {
+		Rect::new(self.viewport.min.scale(self.screen_size), self.viewport.max.scale(self.screen_size))
+	}
+
+	pub fn aspect(&self) -> f32 {
+		let size = self.viewport().size();
+		if size.y > 0.0 { size.x / size.y } else { 1.0 }
+	}
+
+	// Sets the GL viewport, whose origin is the bottom-left
+	pub fn apply_viewport(&self, ctx: &Context) {
+		let vp = self.viewport();
+		let y = self.screen_size.y - vp.max.y;
+		ctx.set_viewport(vp.min.x as i32, y as i32, vp.width() as i32, vp.height() as i32);
+	}
+
+	fn matrices(&self) -> Matrices {
+		if let Some(m) = self.matrices.get() {
+			return m
+		}
+
+		let aspect = self.aspect();
+		let view = Mat4::trs(self.position, self.rotation, Vec3::uniform(1.0)).affine_inverted();
+		let proj = match self.projection {
+			Projection::Perspective { fov, near, far } => Mat4::perspective(fov, aspect, near, far),
+			Projection::Orthographic { height, near, far } => {
+				let (hw, hh) = (height * aspect * 0.5, height * 0.5);
+				Mat4::ortho(-hw, hw, hh, -hh, near, far)
+			}
+		};
+
+		let view_proj = view * proj;
+		let m = Matrices { view, proj, view_proj, inv_view_proj: view_proj.inverted() };
+		self.matrices.set(Some(m));
+		m
+	}
+
+	pub fn view(&self) -> Mat4 { self.matrices().view }
+	pub fn projection_matrix(&self) -> Mat4 { self.matrices().proj }
+	pub fn view_projection(&self) -> Mat4 { self.matrices().view_proj }
+	pub fn inverse_view_projection(&self) -> Mat4 { self.matrices().inv_view_proj }
+
+	// World space, planes facing inwards
+	pub fn frustum(&self) -> Frustum {
+		Frustum::from_matrix(&self.view_projection())
+	}
+
+	pub fn screen_to_ndc(&self, p: Vec2) -> Vec2 {
+		let vp = self.viewport();
+		Vec2::new(
+			(p.x - vp.min.x) / vp.width() * 2.0 - 1.0,
+			1.0 - (p.y - vp.min.y) / vp.height() * 2.0
+		)
+	}
+
+	pub fn ndc_to_screen(&self, p: Vec2) -> Vec2 {
+		let vp = self.viewport();
+		Vec2::new(
+			vp.min.x + (p.x + 1.0) * 0.5 * vp.width(),
+			vp.min.y + (1.0 - p.y) * 0.5 * vp.height()
+		)
+	}
+
+	// Screen position in x and y, and NDC depth in [-1, 1] in z. None behind the camera
+	pub fn world_to_screen(&self, p: Vec3) -> Option<Vec3> {
+		let clip = p.extend(1.0) * self.view_projection();
+		if clip.w <= 0.0 { return None }
+
+		let ndc = clip.as_vec3() * (1.0 / clip.w);
+		Some(self.ndc_to_screen(ndc.xy()).extend(ndc.z))
+	}
+
+	// The world point at a screen position and NDC depth: -1 on the near plane, 1 on the far
+	pub fn screen_to_world(&self, p: Vec2, depth: f32) -> Vec3 {
+		let ndc = self.screen_to_ndc(p);
+		self.inverse_view_projection().transform_point(ndc.extend(depth))
+	}
+
+	// Ray from the near plane through a screen position, e.g. for picking under the mouse
+	pub fn screen_ray(&self, p: Vec2) -> Ray {
+		Ray::from_points(self.screen_to_world(p, -1.0), self.screen_to_world(p, 1.0))
+	}
+}
+
+// Tracks the position and drag deltas of one mouse button across events
+#[derive(Copy, Clone, Debug)]
+struct Drag {
+	held: bool,
+	last: Vec2i,
+}
+
+impl Drag {
+	fn new() -> Drag {
+		Drag { held: false, last: Vec2i::zero() }
+	}
+
+	// Movement this event while the button is held
+	fn handle(&mut self, e: &Event, button: MouseButton) -> Option<Vec2> {
+		match *e {
+			Event::Down(ref m) if m.button == button => { self.held = true; self.last = m.pos; None }
+			Event::Up(ref m) if m.button == button => { self.held = false; None }
+			Event::MouseLeave(_) => { self.held = false; None }
+			Event::Move(pos) => {
+				let delta = pos - self.last;
+				self.last = pos;
+				if self.held { Some(delta.as_vec2()) } else { None }
+			}
+			_ => None
+		}
+	}
+}
+
+fn wheel_steps(e: &Event) -> Option<(Vec2i, f32)> {
+	match *e {
+		// One notch is around 100 pixels in most browsers
+		Event::Wheel(ref w) => Some((w.pos, w.pixel_delta(600.0).y / 100.0)),
+		_ => None
+	}
+}
+
+fn yaw_pitch(yaw: f32, pitch: f32) -> Quat {
+	Quat::new(Vec3::from_y(1.0), yaw) * Quat::new(Vec3::from_x(1.0), pitch)
+}
+
+// Circles a target point: drag to rotate, drag with the pan button to move the target, wheel to zoom
+pub struct OrbitController {
+	pub target: Vec3,
+	pub distance: f32,
+	// Radians about +y, and about the camera's x axis with negative looking down
+	pub yaw: f32,
+	pub pitch: f32,
+
+	pub min_distance: f32,
+	pub max_distance: f32,
+
+	// Radians per pixel
+	pub rotate_speed: f32,
+	// Distance factor per wheel notch
+	pub zoom_speed: f32,
+
+	pub rotate_button: MouseButton,
+	pub pan_button: MouseButton,
+
+	rotate_drag: Drag,
+	pan_drag: Drag,
+}
+
+impl OrbitController {
+	pub fn new(target: Vec3, distance: f32) -> OrbitController {
+		OrbitController {
+			target,
+			distance,
+			yaw: 0.0,
+			pitch: -0.4,
+			min_distance: 0.1,
+			max_distance: 1000.0,
+			rotate_speed: 0.008,
+			zoom_speed: 1.1,
+			rotate_button: MouseButton::Left,
+			pan_button: MouseButton::Right,
+			rotate_drag: Drag::new(),
+			pan_drag: Drag::new(),
+		}
+	}
+
+	pub fn update(&mut self, camera: &mut Camera, events: &[Event]) {
+		for e in events {
+			if let Some(d) = self.rotate_drag.handle(e, self.rotate_button) {
+				self.yaw -= d.x * self.rotate_speed;
+				self.pitch = (self.pitch - d.y * self.rotate_speed).max(-PI * 0.49).min(PI * 0.49);
+			}
+
+			// Moves the target so the point under the cursor follows it, at the target's depth
+			if let Some(d) = self.pan_drag.handle(e, self.pan_button) {
+				let world_per_pixel = match camera.projection() {
+					Projection::Perspective { fov, .. } => 2.0 * self.distance * (fov * 0.5).tan(),
+					Projection::Orthographic { height, .. } => height,
+				} / camera.viewport().height().max(1.0);
+
+				self.target = self.target + (camera.up() * d.y - camera.right() * d.x) * world_per_pixel;
+			}
+
+			if let Some((_, steps)) = wheel_steps(e) {
+				self.distance = (self.distance * self.zoom_speed.powf(steps))
+					.max(self.min_distance).min(self.max_distance);
+			}
+		}
+
+		self.apply(camera);
+	}
+
+	pub fn apply(&self, camera: &mut Camera) {
+		let rotation = yaw_pitch(self.yaw, self.pitch);
+		camera.set_rotation(rotation);
+		camera.set_position(self.target + rotation * Vec3::from_z(self.distance));
+	}
+}
+
+// First person movement: WASD along the view, E/Q or Space/Ctrl up and down, Shift to go faster.
+// Mouse look while the look button is held or the pointer is locked
+pub struct FlyController {
+	pub yaw: f32,
+	pub pitch: f32,
+
+	// Units per second
+	pub speed: f32,
+	pub fast_multiplier: f32,
+	// Radians per pixel
+	pub look_speed: f32,
+
+	pub look_button: MouseButton,
+
+	look_drag: Drag,
+	keys: HashSet<KeyCode>,
+}
+
+impl FlyController {
+	pub fn new() -> FlyController {
+		FlyController {
+			yaw: 0.0,
+			pitch: 0.0,
+			speed: 5.0,
+			fast_multiplier: 4.0,
+			look_speed: 0.004,
+			look_button: MouseButton::Right,
+			look_drag: Drag::new(),
+			keys: HashSet::new(),
+		}
+	}
+
+	fn held(&self, codes: &[KeyCode]) -> bool {
+		codes.iter().any(|k| self.keys.contains(k))
+	}
+
+	fn look(&mut self, d: Vec2) {
+		self.yaw -= d.x * self.look_speed;
+		self.pitch = (self.pitch - d.y * self.look_speed).max(-PI * 0.49).min(PI * 0.49);
+	}
+
+	pub fn update(&mut self, camera: &mut Camera, events: &[Event], dt: f32) {
+		for e in events {
+			match *e {
+				Event::KeyDown(ref k) => { self.keys.insert(k.code); }
+				Event::KeyUp(ref k) => { self.keys.remove(&k.code); }
+				Event::FocusLost | Event::Hidden => self.keys.clear(),
+				Event::RawMotion(d) => self.look(d.as_vec2()),
+				_ => {}
+			}
+
+			if let Some(d) = self.look_drag.handle(e, self.look_button) {
+				self.look(d);
+			}
+		}
+
+		let rotation = yaw_pitch(self.yaw, self.pitch);
+		camera.set_rotation(rotation);
+
+		let axis = |pos: &[KeyCode], neg: &[KeyCode]| {
+			(if self.held(pos) { 1.0 } else { 0.0 }) - (if self.held(neg) { 1.0 } else { 0.0 })
+		};
+
+		let local = Vec3::new(
+			axis(&[KeyCode::KeyD, KeyCode::ArrowRight], &[KeyCode::KeyA, KeyCode::ArrowLeft]),
+			axis(&[KeyCode::KeyE, KeyCode::Space], &[KeyCode::KeyQ, KeyCode::ControlLeft]),
+			axis(&[KeyCode::KeyS, KeyCode::ArrowDown], &[KeyCode::KeyW, KeyCode::ArrowUp])
+		);
+
+		if local.len_sq() > 0.0 {
+			let mut speed = self.speed * dt;
+			if self.held(&[KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+				speed *= self.fast_multiplier;
+			}
+
+			let p = camera.position();
+			camera.set_position(p + rotation * local.normalized() * speed);
+		}
+	}
+}
+
+// 2D navigation for an orthographic camera looking down -z: drag to pan, wheel to zoom about the cursor
+pub struct PanZoomController {
+	pub min_height: f32,
+	pub max_height: f32,
+	// Height factor per wheel notch
+	pub zoom_speed: f32,
+
+	pub pan_button: MouseButton,
+
+	drag: Drag,
+}
+
+impl PanZoomController {
+	pub fn new() -> PanZoomController {
+		PanZoomController {
+			min_height: 0.01,
+			max_height: 100000.0,
+			zoom_speed: 1.1,
+			pan_button: MouseButton::Left,
+			drag: Drag::new(),
+		}
+	}
+
+	pub fn update(&mut self, camera: &mut Camera, events: &[Event]) {
+		for e in events {
+			if let Some(d) = self.drag.handle(e, self.pan_button) {
+				let start = camera.screen_to_world(self.drag.last.as_vec2() - d, 0.0);
+				let end = camera.screen_to_world(self.drag.last.as_vec2(), 0.0);
+				let p = camera.position();
+				camera.set_position(p - (end - start));
+			}
+
+			if let Some((pos, steps)) = wheel_steps(e) {
+				if let Projection::Orthographic { height, near, far } = camera.projection() {
+					let pos = pos.as_vec2();
+					let before = camera.screen_to_world(pos, 0.0);
+
+					let height = (height * self.zoom_speed.powf(steps)).max(self.min_height).min(self.max_height);
+					camera.set_projection(Projection::Orthographic { height, near, far });
+
+					// Keep the point under the cursor fixed
+					let after = camera.screen_to_world(pos, 0.0);
+					let p = camera.position();
+					camera.set_position(p + (before - after));
+				}
+			}
+		}
+	}
+}
\ No newline at end of file
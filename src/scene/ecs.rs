@@ -0,0 +1,884 @@
+use std::any::{ Any, TypeId };
+use std::cell::{ Cell, Ref, RefMut, RefCell };
+use std::collections::HashMap;
+use std::vec;
+
+use core::event::*;
+
+// A generational index: slots are reused once an entity is despawned, and the generation
+// tells a stale handle apart from the slot's new occupant
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+	index: u32,
+	generation: u32,
+}
+
+impl Entity {
+	pub fn index(&self) -> u32 { self.index }
+	pub fn generation(&self) -> u32 { self.generation }
+}
+
+// Entity allocator. Entities can be reserved through a shared reference, which is what lets
+// Commands hand out ids mid-system; they only become alive at the next `World::maintain`
+pub struct Entities {
+	generations: Vec<u32>,
+	alive: Vec<bool>,
+	free: Vec<u32>,
+
+	// Reservations taken from the end of `free`, then past the end of `generations`
+	reserved_free: Cell<usize>,
+	reserved_new: Cell<u32>,
+}
+
+impl Entities {
+	fn new() -> Entities {
+		Entities {
+			generations: Vec::new(),
+			alive: Vec::new(),
+			free: Vec::new(),
+			reserved_free: Cell::new(0),
+			reserved_new: Cell::new(0),
+		}
+	}
+
+	pub fn is_alive(&self, e: Entity) -> bool {
+		let i = e.index as usize;
+		i < self.alive.len() && self.alive[i] && self.generations[i] == e.generation
+	}
+
+	pub fn len(&self) -> usize {
+		self.alive.len() - self.free.len()
+	}
+
+	fn reserve(&self) -> Entity {
+		let rf = self.reserved_free.get();
+		if rf < self.free.len() {
+			self.reserved_free.set(rf + 1);
+			let index = self.free[self.free.len() - 1 - rf];
+			Entity { index, generation: self.generations[index as usize] }
+		} else {
+			let rn = self.reserved_new.get();
+			self.reserved_new.set(rn + 1);
+			Entity { index: self.generations.len() as u32 + rn, generation: 0 }
+		}
+	}
+
+	// Makes every reserved entity alive
+	fn flush_reserved(&mut self) {
+		let rf = self.reserved_free.replace(0);
+		for _ in 0..rf {
+			let index = self.free.pop().unwrap();
+			self.alive[index as usize] = true;
+		}
+
+		let rn = self.reserved_new.replace(0);
+		for _ in 0..rn {
+			self.generations.push(0);
+			self.alive.push(true);
+		}
+	}
+
+	fn create(&mut self) -> Entity {
+		let e = self.reserve();
+		self.flush_reserved();
+		e
+	}
+
+	fn kill(&mut self, e: Entity) -> bool {
+		// Reservations index into `free` from the end, so settle them before it grows
+		self.flush_reserved();
+		if !self.is_alive(e) { return false }
+
+		let i = e.index as usize;
+		self.alive[i] = false;
+		self.generations[i] = self.generations[i].wrapping_add(1);
+		self.free.push(e.index);
+		true
+	}
+}
+
+// Components are plain structs; the associated storage picks the layout. DenseStorage suits
+// components most entities have, SparseStorage rare ones like tags or markers
+//	struct Velocity(Vec3);
+//	impl Component for Velocity { type Storage = DenseStorage<Velocity>; }
+pub trait Component: Any + Sized {
+	type Storage: Storage<Self> + Default + Any;
+}
+
+// Components keyed by entity index. Whether the entity is still alive is checked by the caller
+pub trait Storage<T> {
+	fn insert(&mut self, index: u32, value: T) -> Option<T>;
+	fn remove(&mut self, index: u32) -> Option<T>;
+	fn get(&self, index: u32) -> Option<&T>;
+	fn get_mut(&mut self, index: u32) -> Option<&mut T>;
+	fn len(&self) -> usize;
+
+	// Indices that have a component, in no particular order
+	fn indices(&self) -> Vec<u32>;
+
+	fn contains(&self, index: u32) -> bool { self.get(index).is_some() }
+}
+
+// Sparse set: components packed contiguously, with a lookup table indexed by entity
+pub struct DenseStorage<T> {
+	sparse: Vec<u32>,
+	entities: Vec<u32>,
+	values: Vec<T>,
+}
+
+const EMPTY: u32 = ::std::u32::MAX;
+
+impl<T> Default for DenseStorage<T> {
+	fn default() -> DenseStorage<T> {
+		DenseStorage { sparse: Vec::new(), entities: Vec::new(), values: Vec::new() }
+	}
+}
+
+impl<T> DenseStorage<T> {
+	// Components in packed order, for bulk work that doesn't need the entity
+	pub fn values(&self) -> &[T] { &self.values }
+	pub fn values_mut(&mut self) -> &mut [T] { &mut self.values }
+
+	fn slot(&self, index: u32) -> Option<usize> {
+		match self.sparse.get(index as usize) {
+			Some(&d) if d != EMPTY => Some(d as usize),
+			_ => None
+		}
+	}
+}
+
+impl<T> Storage<T> for DenseStorage<T> {
+	fn insert(&mut self, index: u32, value: T) -> Option<T> {
+		if let Some(d) = self.slot(index) {
+			return Some(::std::mem::replace(&mut self.values[d], value))
+		}
+
+		if self.sparse.len() <= index as usize {
+			self.sparse.resize(index as usize + 1, EMPTY);
+		}
+
+		self.sparse[index as usize] = self.values.len() as u32;
+		self.entities.push(index);
+		self.values.push(value);
+		None
+	}
+
+	fn remove(&mut self, index: u32) -> Option<T> {
+		let d = self.slot(index)?;
+
+		let last = *self.entities.last().unwrap();
+		self.sparse[last as usize] = d as u32;
+		self.sparse[index as usize] = EMPTY;
+		self.entities.swap_remove(d);
+		Some(self.values.swap_remove(d))
+	}
+
+	fn get(&self, index: u32) -> Option<&T> {
+		self.slot(index).map(|d| &self.values[d])
+	}
+
+	fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+		match self.slot(index) {
+			Some(d) => Some(&mut self.values[d]),
+			None => None
+		}
+	}
+
+	fn len(&self) -> usize { self.values.len() }
+	fn indices(&self) -> Vec<u32> { self.entities.clone() }
+	fn contains(&self, index: u32) -> bool { self.slot(index).is_some() }
+}
+
+pub struct SparseStorage<T> {
+	values: HashMap<u32, T>,
+}
+
+impl<T> Default for SparseStorage<T> {
+	fn default() -> SparseStorage<T> {
+		SparseStorage { values: HashMap::new() }
+	}
+}
+
+impl<T> Storage<T> for SparseStorage<T> {
+	fn insert(&mut self, index: u32, value: T) -> Option<T> { self.values.insert(index, value) }
+	fn remove(&mut self, index: u32) -> Option<T> { self.values.remove(&index) }
+	fn get(&self, index: u32) -> Option<&T> { self.values.get(&index) }
+	fn get_mut(&mut self, index: u32) -> Option<&mut T> { self.values.get_mut(&index) }
+	fn len(&self) -> usize { self.values.len() }
+	fn indices(&self) -> Vec<u32> { self.values.keys().cloned().collect() }
+	fn contains(&self, index: u32) -> bool { self.values.contains_key(&index) }
+}
+
+// Type-erased storage, so despawning can strip every component without knowing their types
+trait AnyStorage {
+	fn remove_index(&self, index: u32);
+	fn as_any(&self) -> &dyn Any;
+}
+
+struct StorageCell<T: Component>(RefCell<T::Storage>);
+
+impl<T: Component> AnyStorage for StorageCell<T> {
+	fn remove_index(&self, index: u32) {
+		self.0.borrow_mut().remove(index);
+	}
+
+	fn as_any(&self) -> &dyn Any { self }
+}
+
+pub struct World {
+	entities: Entities,
+	storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+	resources: HashMap<TypeId, Box<dyn Any>>,
+	commands: RefCell<Vec<Box<dyn FnMut(&mut World)>>>,
+}
+
+impl World {
+	pub fn new() -> World {
+		World {
+			entities: Entities::new(),
+			storages: HashMap::new(),
+			resources: HashMap::new(),
+			commands: RefCell::new(Vec::new()),
+		}
+	}
+
+	// Storages are created on first insert, but queries over a component nothing has had yet need it registered
+	pub fn register<T: Component>(&mut self) {
+		self.storages.entry(TypeId::of::<T>())
+			.or_insert_with(|| Box::new(StorageCell::<T>(RefCell::new(T::Storage::default()))) as Box<dyn AnyStorage>);
+	}
+
+	pub fn spawn(&mut self) -> Entity {
+		self.entities.create()
+	}
+
+	// Removes the entity and all its components. False if it was already dead
+	pub fn despawn(&mut self, e: Entity) -> bool {
+		if !self.entities.kill(e) { return false }
+
+		for s in self.storages.values() {
+			s.remove_index(e.index);
+		}
+		true
+	}
+
+	pub fn is_alive(&self, e: Entity) -> bool { self.entities.is_alive(e) }
+	pub fn entities(&self) -> &Entities { &self.entities }
+
+	// Returns the component it replaced
+	pub fn insert<T: Component>(&mut self, e: Entity, component: T) -> Option<T> {
+		if !self.is_alive(e) { return None }
+
+		self.register::<T>();
+		self.write::<T>().data.insert(e.index, component)
+	}
+
+	pub fn remove<T: Component>(&mut self, e: Entity) -> Option<T> {
+		if !self.is_alive(e) || !self.storages.contains_key(&TypeId::of::<T>()) { return None }
+		self.write::<T>().data.remove(e.index)
+	}
+
+	pub fn has<T: Component>(&self, e: Entity) -> bool {
+		self.is_alive(e) && self.storage_cell::<T>().map_or(false, |s| s.borrow().contains(e.index))
+	}
+
+	pub fn get<T: Component>(&self, e: Entity) -> Option<Ref<'_, T>> {
+		if !self.has::<T>(e) { return None }
+		Some(Ref::map(self.read::<T>().data, |s| s.get(e.index).unwrap()))
+	}
+
+	pub fn get_mut<T: Component>(&self, e: Entity) -> Option<RefMut<'_, T>> {
+		if !self.has::<T>(e) { return None }
+		Some(RefMut::map(self.write::<T>().data, |s| s.get_mut(e.index).unwrap()))
+	}
+
+	fn storage_cell<T: Component>(&self) -> Option<&RefCell<T::Storage>> {
+		self.storages.get(&TypeId::of::<T>())
+			.map(|s| &s.as_any().downcast_ref::<StorageCell<T>>().unwrap().0)
+	}
+
+	// Shared access to every T. Panics if the storage is already borrowed mutably or T isn't registered
+	pub fn read<T: Component>(&self) -> ReadStorage<'_, T> {
+		let cell = self.storage_cell::<T>()
+			.expect("Component isn't registered; see World::register");
+		ReadStorage { entities: &self.entities, data: cell.borrow() }
+	}
+
+	// Exclusive access to every T. Panics if the storage is already borrowed or T isn't registered
+	pub fn write<T: Component>(&self) -> WriteStorage<'_, T> {
+		let cell = self.storage_cell::<T>()
+			.expect("Component isn't registered; see World::register");
+		WriteStorage { entities: &self.entities, data: cell.borrow_mut() }
+	}
+
+	// Resources are singletons shared by systems: time, input, asset caches and the like
+	pub fn insert_resource<R: Any>(&mut self, resource: R) {
+		self.resources.insert(TypeId::of::<R>(), Box::new(RefCell::new(resource)));
+	}
+
+	pub fn remove_resource<R: Any>(&mut self) -> Option<R> {
+		self.resources.remove(&TypeId::of::<R>())
+			.map(|r| r.downcast::<RefCell<R>>().ok().unwrap().into_inner())
+	}
+
+	pub fn has_resource<R: Any>(&self) -> bool {
+		self.resources.contains_key(&TypeId::of::<R>())
+	}
+
+	fn resource_cell<R: Any>(&self) -> &RefCell<R> {
+		self.resources.get(&TypeId::of::<R>())
+			.and_then(|r| r.downcast_ref::<RefCell<R>>())
+			.expect("Resource doesn't exist")
+	}
+
+	pub fn resource<R: Any>(&self) -> Ref<'_, R> { self.resource_cell::<R>().borrow() }
+	pub fn resource_mut<R: Any>(&self) -> RefMut<'_, R> { self.resource_cell::<R>().borrow_mut() }
+
+	// Changes to apply at the next `maintain`, usable while storages are borrowed
+	pub fn commands(&self) -> Commands<'_> {
+		Commands { world: self }
+	}
+
+	// Applies queued commands in the order they were issued
+	pub fn maintain(&mut self) {
+		self.entities.flush_reserved();
+
+		// Commands may queue more commands
+		loop {
+			let queued = ::std::mem::replace(self.commands.get_mut(), Vec::new());
+			if queued.is_empty() { break }
+
+			for mut c in queued {
+				c(self);
+			}
+		}
+	}
+}
+
+pub struct Commands<'a> {
+	world: &'a World,
+}
+
+impl<'a> Commands<'a> {
+	// The entity can be used in further commands straight away, but is only alive after `maintain`
+	pub fn spawn(&self) -> Entity {
+		self.world.entities.reserve()
+	}
+
+	pub fn despawn(&self, e: Entity) {
+		self.run(move |w| { w.despawn(e); });
+	}
+
+	pub fn insert<T: Component>(&self, e: Entity, component: T) {
+		let mut component = Some(component);
+		self.run(move |w| { w.insert(e, component.take().unwrap()); });
+	}
+
+	pub fn remove<T: Component>(&self, e: Entity) {
+		self.run(move |w| { w.remove::<T>(e); });
+	}
+
+	pub fn insert_resource<R: Any>(&self, resource: R) {
+		let mut resource = Some(resource);
+		self.run(move |w| w.insert_resource(resource.take().unwrap()));
+	}
+
+	// Arbitrary deferred access. Called exactly once
+	pub fn run<F: FnMut(&mut World) + 'static>(&self, f: F) {
+		self.world.commands.borrow_mut().push(Box::new(f));
+	}
+}
+
+pub struct ReadStorage<'a, T: Component> {
+	entities: &'a Entities,
+	data: Ref<'a, T::Storage>,
+}
+
+pub struct WriteStorage<'a, T: Component> {
+	entities: &'a Entities,
+	data: RefMut<'a, T::Storage>,
+}
+
+impl<'a, T: Component> ReadStorage<'a, T> {
+	pub fn get(&self, e: Entity) -> Option<&T> {
+		if self.entities.is_alive(e) { self.data.get(e.index) } else { None }
+	}
+
+	pub fn contains(&self, e: Entity) -> bool { self.get(e).is_some() }
+	pub fn len(&self) -> usize { self.data.len() }
+	pub fn storage(&self) -> &T::Storage { &self.data }
+}
+
+impl<'a, T: Component> WriteStorage<'a, T> {
+	pub fn get(&self, e: Entity) -> Option<&T> {
+		if self.entities.is_alive(e) { self.data.get(e.index) } else { None }
+	}
+
+	pub fn get_mut(&mut self, e: Entity) -> Option<&mut T> {
+		if self.entities.is_alive(e) { self.data.get_mut(e.index) } else { None }
+	}
+
+	// Unlike World::insert, takes effect while other storages are borrowed
+	pub fn insert(&mut self, e: Entity, component: T) -> Option<T> {
+		if self.entities.is_alive(e) { self.data.insert(e.index, component) } else { None }
+	}
+
+	pub fn remove(&mut self, e: Entity) -> Option<T> {
+		if self.entities.is_alive(e) { self.data.remove(e.index) } else { None }
+	}
+
+	pub fn contains(&self, e: Entity) -> bool { self.get(e).is_some() }
+	pub fn len(&self) -> usize { self.data.len() }
+	pub fn storage(&self) -> &T::Storage { &self.data }
+	pub fn storage_mut(&mut self) -> &mut T::Storage { &mut self.data }
+}
+
+// Iterates the entities that match every part of a tuple of storages
+//	let (mut pos, vel) = (world.write::<Position>(), world.read::<Velocity>());
+//	for (e, p, v, _) in (world.entities(), &mut pos, &vel, Not(&frozen)).join() { .. }
+pub trait Join: Sized {
+	type Item;
+
+	// Indices worth testing; None for filters, which can't drive a join on their own
+	fn candidates(&self) -> Option<Vec<u32>>;
+	fn matches(&self, index: u32) -> bool;
+
+	// `index` must match, and each index may only be fetched once per join
+	unsafe fn fetch(&mut self, index: u32) -> Self::Item;
+
+	fn join(self) -> JoinIter<Self> {
+		let candidates = self.candidates()
+			.expect("A join needs at least one part that isn't a filter");
+		JoinIter { join: self, candidates: candidates.into_iter() }
+	}
+}
+
+pub struct JoinIter<J: Join> {
+	join: J,
+	candidates: vec::IntoIter<u32>,
+}
+
+impl<J: Join> Iterator for JoinIter<J> {
+	type Item = J::Item;
+
+	fn next(&mut self) -> Option<J::Item> {
+		loop {
+			let index = self.candidates.next()?;
+			if self.join.matches(index) {
+				return Some(unsafe { self.join.fetch(index) })
+			}
+		}
+	}
+}
+
+impl<'a> Join for &'a Entities {
+	type Item = Entity;
+
+	fn candidates(&self) -> Option<Vec<u32>> {
+		Some((0..self.alive.len() as u32).filter(|&i| self.alive[i as usize]).collect())
+	}
+
+	fn matches(&self, index: u32) -> bool {
+		self.alive.get(index as usize).cloned().unwrap_or(false)
+	}
+
+	unsafe fn fetch(&mut self, index: u32) -> Entity {
+		Entity { index, generation: self.generations[index as usize] }
+	}
+}
+
+impl<'a, 'b, T: Component> Join for &'a ReadStorage<'b, T> {
+	type Item = &'a T;
+
+	fn candidates(&self) -> Option<Vec<u32>> { Some(self.data.indices()) }
+	fn matches(&self, index: u32) -> bool { self.data.contains(index) }
+
+	unsafe fn fetch(&mut self, index: u32) -> &'a T {
+		&*(self.data.get(index).unwrap() as *const T)
+	}
+}
+
+impl<'a, 'b, T: Component> Join for &'a WriteStorage<'b, T> {
+	type Item = &'a T;
+
+	fn candidates(&self) -> Option<Vec<u32>> { Some(self.data.indices()) }
+	fn matches(&self, index: u32) -> bool { self.data.contains(index) }
+
+	unsafe fn fetch(&mut self, index: u32) -> &'a T {
+		&*(self.data.get(index).unwrap() as *const T)
+	}
+}
+
+impl<'a, 'b, T: Component> Join for &'a mut WriteStorage<'b, T> {
+	type Item = &'a mut T;
+
+	fn candidates(&self) -> Option<Vec<u32>> { Some(self.data.indices()) }
+	fn matches(&self, index: u32) -> bool { self.data.contains(index) }
+
+	// Sound because every index is fetched at most once, so the references never alias
+	unsafe fn fetch(&mut self, index: u32) -> &'a mut T {
+		&mut *(self.data.get_mut(index).unwrap() as *mut T)
+	}
+}
+
+// Matches entities that don't match the inner join
+pub struct Not<J>(pub J);
+
+impl<J: Join> Join for Not<J> {
+	type Item = ();
+
+	fn candidates(&self) -> Option<Vec<u32>> { None }
+	fn matches(&self, index: u32) -> bool { !self.0.matches(index) }
+	unsafe fn fetch(&mut self, _: u32) {}
+}
+
+// Matches everything, yielding the inner item where there is one
+pub struct Maybe<J>(pub J);
+
+impl<J: Join> Join for Maybe<J> {
+	type Item = Option<J::Item>;
+
+	fn candidates(&self) -> Option<Vec<u32>> { None }
+	fn matches(&self, _: u32) -> bool { true }
+
+	unsafe fn fetch(&mut self, index: u32) -> Option<J::Item> {
+		if self.0.matches(index) { Some(self.0.fetch(index)) } else { None }
+	}
+}
+
+macro_rules! impl_join_tuple {
+	($($j:ident),+) => {
+		#[allow(non_snake_case)]
+		impl<$($j: Join),+> Join for ($($j,)+) {
+			type Item = ($($j::Item,)+);
+
+			// The smallest candidate list does the least work
+			fn candidates(&self) -> Option<Vec<u32>> {
+				let ($(ref $j,)+) = *self;
+				let mut best: Option<Vec<u32>> = None;
+				$(
+					if let Some(c) = $j.candidates() {
+						if best.as_ref().map_or(true, |b| c.len() < b.len()) {
+							best = Some(c);
+						}
+					}
+				)+
+				best
+			}
+
+			fn matches(&self, index: u32) -> bool {
+				let ($(ref $j,)+) = *self;
+				$($j.matches(index))&&+
+			}
+
+			unsafe fn fetch(&mut self, index: u32) -> Self::Item {
+				let ($(ref mut $j,)+) = *self;
+				($($j.fetch(index),)+)
+			}
+		}
+	}
+}
+
+impl_join_tuple!(A);
+impl_join_tuple!(A, B);
+impl_join_tuple!(A, B, C);
+impl_join_tuple!(A, B, C, D);
+impl_join_tuple!(A, B, C, D, E);
+impl_join_tuple!(A, B, C, D, E, F);
+
+pub trait System {
+	fn run(&mut self, world: &World);
+}
+
+impl<F: FnMut(&World)> System for F {
+	fn run(&mut self, world: &World) { self(world) }
+}
+
+// When a system runs, relative to the App callbacks. PreUpdate, Update and PostUpdate run in that order
+// from App::update, FixedUpdate from App::fixed_update and Render from App::render
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+	PreUpdate,
+	Update,
+	PostUpdate,
+	FixedUpdate,
+	Render,
+}
+
+// Resource updated by the schedule before each stage runs
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Time {
+	// Frame time in Update stages, the fixed step in FixedUpdate
+	pub dt: f32,
+	// Interpolation between the last two fixed updates, set for Render
+	pub alpha: f32,
+	// Sum of every frame's dt
+	pub elapsed: f64,
+}
+
+// Resource holding the events of the current frame
+#[derive(Clone, Debug, Default)]
+pub struct FrameEvents(pub Vec<Event>);
+
+// Systems grouped by stage, run in the order they were added. Commands are applied after each stage
+//	fn update(&mut self, _: &mut LoopState, events: &[Event], dt: f32) {
+//		self.schedule.update(&mut self.world, events, dt);
+//	}
+pub struct Schedule {
+	systems: Vec<(Stage, Box<dyn System>)>,
+}
+
+impl Schedule {
+	pub fn new() -> Schedule {
+		Schedule { systems: Vec::new() }
+	}
+
+	pub fn add_system<S: System + 'static>(&mut self, stage: Stage, system: S) {
+		self.systems.push((stage, Box::new(system)));
+	}
+
+	pub fn run_stage(&mut self, stage: Stage, world: &mut World) {
+		world.maintain();
+		for &mut (s, ref mut system) in self.systems.iter_mut() {
+			if s == stage {
+				system.run(world);
+			}
+		}
+		world.maintain();
+	}
+
+	fn time(world: &mut World) -> RefMut<'_, Time> {
+		if !world.has_resource::<Time>() {
+			world.insert_resource(Time::default());
+		}
+		world.resource_mut::<Time>()
+	}
+
+	pub fn update(&mut self, world: &mut World, events: &[Event], dt: f32) {
+		{
+			let mut time = Schedule::time(world);
+			time.dt = dt;
+			time.elapsed += dt as f64;
+		}
+		world.insert_resource(FrameEvents(events.to_vec()));
+
+		self.run_stage(Stage::PreUpdate, world);
+		self.run_stage(Stage::Update, world);
+		self.run_stage(Stage::PostUpdate, world);
+	}
+
+	pub fn fixed_update(&mut self, world: &mut World, dt: f32) {
+		let frame_dt = {
+			let mut time = Schedule::time(world);
+			::std::mem::replace(&mut time.dt, dt)
+		};
+
+		self.run_stage(Stage::FixedUpdate, world);
+		Schedule::time(world).dt = frame_dt;
+	}
+
+	pub fn render(&mut self, world: &mut World, alpha: f32) {
+		Schedule::time(world).alpha = alpha;
+		self.run_stage(Stage::Render, world);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Debug, PartialEq)]
+	struct Pos(i32);
+	impl Component for Pos { type Storage = DenseStorage<Pos>; }
+
+	#[derive(Debug, PartialEq)]
+	struct Tag(&'static str);
+	impl Component for Tag { type Storage = SparseStorage<Tag>; }
+
+	fn sorted<T: Ord>(mut v: Vec<T>) -> Vec<T> {
+		v.sort();
+		v
+	}
+
+	#[test]
+	fn reused_slots_bump_the_generation() {
+		let mut w = World::new();
+		let a = w.spawn();
+		let b = w.spawn();
+		w.insert(a, Pos(1));
+
+		assert!(w.despawn(a));
+		assert!(!w.despawn(a));
+		assert!(!w.is_alive(a));
+
+		let c = w.spawn();
+		assert_eq!(c.index(), a.index());
+		assert_eq!(c.generation(), a.generation() + 1);
+		assert!(w.is_alive(c) && w.is_alive(b));
+
+		// The stale handle doesn't reach the slot's new occupant
+		w.insert(c, Pos(3));
+		assert_eq!(w.insert(a, Pos(9)), None);
+		assert!(w.get::<Pos>(a).is_none());
+		assert!(w.read::<Pos>().get(a).is_none());
+		assert!(!w.has::<Pos>(a));
+		assert!(w.write::<Pos>().remove(a).is_none());
+		assert_eq!(*w.get::<Pos>(c).unwrap(), Pos(3));
+		assert_eq!(w.entities().len(), 2);
+	}
+
+	#[test]
+	fn commands_spawn_on_maintain() {
+		let mut w = World::new();
+		let old = w.spawn();
+		w.despawn(old);
+
+		let (a, b) = {
+			let c = w.commands();
+			let a = c.spawn();
+			let b = c.spawn();
+			c.insert(a, Pos(1));
+			c.insert(b, Tag("b"));
+			(a, b)
+		};
+
+		// Reserved ids are distinct, reuse the free slot first and aren't alive yet
+		assert!(a != b);
+		assert_eq!(a.index(), old.index());
+		assert_eq!(a.generation(), old.generation() + 1);
+		assert!(!w.is_alive(a) && !w.is_alive(b));
+
+		w.maintain();
+		assert!(w.is_alive(a) && w.is_alive(b));
+		assert_eq!(*w.get::<Pos>(a).unwrap(), Pos(1));
+		assert_eq!(*w.get::<Tag>(b).unwrap(), Tag("b"));
+		assert!(w.spawn() != b);
+		assert_eq!(w.entities().len(), 3);
+
+		w.commands().despawn(a);
+		w.commands().remove::<Tag>(b);
+		assert!(w.is_alive(a));
+		w.maintain();
+		assert!(!w.is_alive(a));
+		assert!(!w.has::<Tag>(b));
+	}
+
+	#[test]
+	fn despawn_clears_every_storage() {
+		let mut w = World::new();
+		let es: Vec<Entity> = (0..4).map(|_| w.spawn()).collect();
+		for (i, &e) in es.iter().enumerate() {
+			w.insert(e, Pos(i as i32));
+			w.insert(e, Tag("t"));
+		}
+
+		w.despawn(es[1]);
+		assert_eq!(w.read::<Pos>().len(), 3);
+		assert_eq!(w.read::<Tag>().len(), 3);
+		assert!(!w.read::<Pos>().storage().contains(es[1].index()));
+		assert!(!w.read::<Tag>().storage().contains(es[1].index()));
+
+		// The packed values were swapped around, but each still belongs to its entity
+		for &i in [0, 2, 3].iter() {
+			assert_eq!(*w.get::<Pos>(es[i]).unwrap(), Pos(i as i32));
+		}
+
+		// A new entity in the old slot starts out empty
+		let e = w.spawn();
+		assert_eq!(e.index(), es[1].index());
+		assert!(!w.has::<Pos>(e) && !w.has::<Tag>(e));
+	}
+
+	#[test]
+	fn joins() {
+		let mut w = World::new();
+		let es: Vec<Entity> = (0..10).map(|_| w.spawn()).collect();
+		for (i, &e) in es.iter().enumerate() {
+			w.insert(e, Pos(i as i32));
+		}
+		w.insert(es[3], Tag("a"));
+		w.insert(es[7], Tag("b"));
+
+		let (mut pos, tag) = (w.write::<Pos>(), w.read::<Tag>());
+
+		// The smaller storage drives the join, whichever side it's on
+		assert_eq!(sorted((&pos, &tag).candidates().unwrap()), vec![3, 7]);
+		assert_eq!(sorted((&tag, &pos).candidates().unwrap()), vec![3, 7]);
+		assert_eq!((w.entities(), Not(&tag)).candidates().unwrap().len(), 10);
+
+		for (p, _) in (&mut pos, &tag).join() {
+			p.0 += 100;
+		}
+
+		let tagged: Vec<(Entity, i32)> = (w.entities(), &pos, &tag).join().map(|(e, p, _)| (e, p.0)).collect();
+		assert_eq!(sorted(tagged), vec![(es[3], 103), (es[7], 107)]);
+
+		let untagged: Vec<i32> = (&pos, Not(&tag)).join().map(|(p, _)| p.0).collect();
+		assert_eq!(sorted(untagged), vec![0, 1, 2, 4, 5, 6, 8, 9]);
+
+		let maybe: Vec<(i32, Option<&'static str>)> = (&pos, Maybe(&tag)).join()
+			.map(|(p, t)| (p.0, t.map(|t| t.0)))
+			.collect();
+		assert_eq!(maybe.len(), 10);
+		assert!(maybe.contains(&(107, Some("b"))));
+		assert!(maybe.contains(&(5, None)));
+	}
+
+	#[test]
+	#[should_panic]
+	fn filters_cant_drive_a_join() {
+		let mut w = World::new();
+		w.register::<Tag>();
+		let tag = w.read::<Tag>();
+		for _ in (Not(&tag),).join() {}
+	}
+
+	#[test]
+	fn commands_can_queue_commands() {
+		let mut w = World::new();
+		w.insert_resource(Vec::<u32>::new());
+
+		w.commands().run(|w| {
+			w.resource_mut::<Vec<u32>>().push(1);
+			w.commands().run(|w| {
+				w.resource_mut::<Vec<u32>>().push(3);
+				w.commands().run(|w| w.resource_mut::<Vec<u32>>().push(4));
+			});
+		});
+		w.commands().run(|w| w.resource_mut::<Vec<u32>>().push(2));
+
+		w.maintain();
+		assert_eq!(*w.resource::<Vec<u32>>(), vec![1, 2, 3, 4]);
+	}
+
+	#[test]
+	fn fixed_update_restores_dt() {
+		let mut w = World::new();
+		let mut s = Schedule::new();
+		w.insert_resource(Vec::<(Stage, f32)>::new());
+
+		for &stage in [Stage::Update, Stage::FixedUpdate, Stage::Render].iter() {
+			s.add_system(stage, move |w: &World| {
+				let dt = w.resource::<Time>().dt;
+				w.resource_mut::<Vec<(Stage, f32)>>().push((stage, dt));
+			});
+		}
+
+		s.update(&mut w, &[], 0.25);
+		s.fixed_update(&mut w, 0.125);
+		s.fixed_update(&mut w, 0.125);
+		s.render(&mut w, 0.5);
+
+		assert_eq!(*w.resource::<Vec<(Stage, f32)>>(), vec![
+			(Stage::Update, 0.25),
+			(Stage::FixedUpdate, 0.125),
+			(Stage::FixedUpdate, 0.125),
+			(Stage::Render, 0.25),
+		]);
+
+		let time = *w.resource::<Time>();
+		assert_eq!(time.dt, 0.25);
+		assert_eq!(time.alpha, 0.5);
+		assert_eq!(time.elapsed, 0.25);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,466 @@
+use std::cell::Cell;
+
+use math::vec::*;
+use math::mat::*;
+use math::quat::*;
+
+// Handle to a node in a SceneGraph. The generation catches handles to removed nodes whose slot was reused
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+	index: u32,
+	generation: u32,
+}
+
+struct Node {
+	position: Vec3,
+	rotation: Quat,
+	scale: Vec3,
+
+	parent: Option<NodeId>,
+	children: Vec<NodeId>,
+
+	// Cached local * parent world. A dirty node's descendants are always dirty too,
+	// so marking can stop at the first node that already is
+	world: Cell<Mat4>,
+	dirty: Cell<bool>,
+}
+
+struct Slot {
+	generation: u32,
+	node: Option<Node>,
+}
+
+// Nodes with a local position, rotation and scale relative to their parent.
+// World matrices are computed on demand and cached until something above them moves
+pub struct SceneGraph {
+	slots: Vec<Slot>,
+	free: Vec<u32>,
+	roots: Vec<NodeId>,
+}
+
+impl SceneGraph {
+	pub fn new() -> SceneGraph {
+		SceneGraph {
+			slots: Vec::new(),
+			free: Vec::new(),
+			roots: Vec::new(),
+		}
+	}
+
+	pub fn len(&self) -> usize { self.slots.len() - self.free.len() }
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	// A new root node with the identity transform
+	pub fn create(&mut self) -> NodeId {
+		let node = Node {
+			position: Vec3::zero(),
+			rotation: Quat::ident(),
+			scale: Vec3::uniform(1.0),
+			parent: None,
+			children: Vec::new(),
+			world: Cell::new(Mat4::ident()),
+			dirty: Cell::new(true),
+		};
+
+		let id = match self.free.pop() {
+			Some(index) => {
+				let slot = &mut self.slots[index as usize];
+				slot.node = Some(node);
+				NodeId { index, generation: slot.generation }
+			}
+
+			None => {
+				self.slots.push(Slot { generation: 0, node: Some(node) });
+				NodeId { index: self.slots.len() as u32 - 1, generation: 0 }
+			}
+		};
+
+		self.roots.push(id);
+		id
+	}
+
+	pub fn create_child(&mut self, parent: NodeId) -> NodeId {
+		let id = self.create();
+		self.attach(id, Some(parent));
+		id
+	}
+
+	// Removes the node along with its whole subtree
+	pub fn remove(&mut self, id: NodeId) {
+		self.detach(id);
+
+		let subtree: Vec<NodeId> = self.descendants(id).collect();
+		for n in subtree {
+			let slot = &mut self.slots[n.index as usize];
+			slot.node = None;
+			slot.generation = slot.generation.wrapping_add(1);
+			self.free.push(n.index);
+		}
+	}
+
+	pub fn contains(&self, id: NodeId) -> bool {
+		self.slots.get(id.index as usize)
+			.map_or(false, |s| s.generation == id.generation && s.node.is_some())
+	}
+
+	fn node(&self, id: NodeId) -> &Node {
+		match self.slots.get(id.index as usize) {
+			Some(&Slot { generation, node: Some(ref node) }) if generation == id.generation => node,
+			_ => panic!("Stale or invalid NodeId {:?}", id)
+		}
+	}
+
+	fn node_mut(&mut self, id: NodeId) -> &mut Node {
+		match self.slots.get_mut(id.index as usize) {
+			Some(&mut Slot { generation, node: Some(ref mut node) }) if generation == id.generation => node,
+			_ => panic!("Stale or invalid NodeId {:?}", id)
+		}
+	}
+
+	pub fn parent(&self, id: NodeId) -> Option<NodeId> { self.node(id).parent }
+	pub fn children(&self, id: NodeId) -> &[NodeId] { &self.node(id).children }
+	pub fn roots(&self) -> &[NodeId] { &self.roots }
+
+	// Moves `id` under `parent`, or to the roots if None. With `keep_world` the local transform is
+	// recomputed so the node stays where it is; otherwise it keeps its local transform and moves with the new parent
+	pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>, keep_world: bool) -> Result<(), String> {
+		if let Some(p) = parent {
+			if p == id || self.ancestors(p).any(|a| a == id) {
+				return Err(format!("Can't parent {:?} to its own descendant {:?}", id, p))
+			}
+		}
+
+		let world = self.world_matrix(id);
+		self.detach(id);
+		self.attach(id, parent);
+
+		if keep_world {
+			self.set_world_matrix(id, world);
+		}
+
+		Ok(())
+	}
+
+	fn detach(&mut self, id: NodeId) {
+		match self.node_mut(id).parent.take() {
+			Some(p) => self.node_mut(p).children.retain(|&c| c != id),
+			None => self.roots.retain(|&r| r != id),
+		}
+		self.mark_dirty(id);
+	}
+
+	// `id` must currently be detached or a root
+	fn attach(&mut self, id: NodeId, parent: Option<NodeId>) {
+		match parent {
+			Some(p) => {
+				self.roots.retain(|&r| r != id);
+				self.node_mut(p).children.push(id);
+			}
+
+			None => if !self.roots.contains(&id) {
+				self.roots.push(id);
+			}
+		}
+
+		self.node_mut(id).parent = parent;
+		self.mark_dirty(id);
+	}
+
+	fn mark_dirty(&self, id: NodeId) {
+		let node = self.node(id);
+		if node.dirty.get() { return }
+
+		node.dirty.set(true);
+		for &c in node.children.iter() {
+			self.mark_dirty(c);
+		}
+	}
+
+	pub fn position(&self, id: NodeId) -> Vec3 { self.node(id).position }
+	pub fn rotation(&self, id: NodeId) -> Quat { self.node(id).rotation }
+	pub fn scale(&self, id: NodeId) -> Vec3 { self.node(id).scale }
+
+	pub fn set_position(&mut self, id: NodeId, position: Vec3) {
+		self.node_mut(id).position = position;
+		self.mark_dirty(id);
+	}
+
+	pub fn set_rotation(&mut self, id: NodeId, rotation: Quat) {
+		self.node_mut(id).rotation = rotation;
+		self.mark_dirty(id);
+	}
+
+	pub fn set_scale(&mut self, id: NodeId, scale: Vec3) {
+		self.node_mut(id).scale = scale;
+		self.mark_dirty(id);
+	}
+
+	pub fn set_local(&mut self, id: NodeId, position: Vec3, rotation: Quat, scale: Vec3) {
+		{
+			let node = self.node_mut(id);
+			node.position = position;
+			node.rotation = rotation;
+			node.scale = scale;
+		}
+		self.mark_dirty(id);
+	}
+
+	// Moves along the parent's axes
+	pub fn translate(&mut self, id: NodeId, delta: Vec3) {
+		let p = self.position(id);
+		self.set_position(id, p + delta);
+	}
+
+	// Rotates about the node's own axes
+	pub fn rotate(&mut self, id: NodeId, rotation: Quat) {
+		let r = self.rotation(id);
+		self.set_rotation(id, r * rotation);
+	}
+
+	pub fn local_matrix(&self, id: NodeId) -> Mat4 {
+		let node = self.node(id);
+		Mat4::trs(node.position, node.rotation, node.scale)
+	}
+
+	pub fn world_matrix(&self, id: NodeId) -> Mat4 {
+		let node = self.node(id);
+		if node.dirty.get() {
+			let local = self.local_matrix(id);
+			let world = match node.parent {
+				Some(p) => local * self.world_matrix(p),
+				None => local,
+			};
+
+			node.world.set(world);
+			node.dirty.set(false);
+		}
+
+		node.world.get()
+	}
+
+	// Sets the local transform that puts the node at `world`. Shear from non-uniformly
+	// scaled parents can't be represented and is lost
+	pub fn set_world_matrix(&mut self, id: NodeId, world: Mat4) {
+		let local = match self.parent(id) {
+			Some(p) => world * self.world_matrix(p).inverted(),
+			None => world,
+		};
+
+		let (position, rotation, scale) = local.decompose();
+		self.set_local(id, position, rotation, scale);
+	}
+
+	pub fn world_position(&self, id: NodeId) -> Vec3 {
+		self.world_matrix(id).translation_part()
+	}
+
+	pub fn set_world_position(&mut self, id: NodeId, position: Vec3) {
+		let local = match self.parent(id) {
+			Some(p) => self.world_matrix(p).affine_inverted().transform_point(position),
+			None => position,
+		};
+		self.set_position(id, local);
+	}
+
+	pub fn world_rotation(&self, id: NodeId) -> Quat {
+		let mut r = self.rotation(id);
+		for a in self.ancestors(id) {
+			r = self.rotation(a) * r;
+		}
+		r
+	}
+
+	// Brings every world matrix up to date, parents before children. Worth calling once per
+	// frame before handing matrices to the renderer, though `world_matrix` works without it
+	pub fn update(&self) {
+		for id in self.iter() {
+			self.world_matrix(id);
+		}
+	}
+
+	// Parent, grandparent and so on up to the root
+	pub fn ancestors(&self, id: NodeId) -> Ancestors<'_> {
+		Ancestors { graph: self, next: self.parent(id) }
+	}
+
+	// Depth-first, parents before children, starting with `id` itself
+	pub fn descendants(&self, id: NodeId) -> DepthFirst<'_> {
+		DepthFirst { graph: self, stack: vec![id] }
+	}
+
+	// Every node, depth-first from each root in turn
+	pub fn iter(&self) -> DepthFirst<'_> {
+		DepthFirst { graph: self, stack: self.roots.iter().rev().cloned().collect() }
+	}
+}
+
+pub struct Ancestors<'a> {
+	graph: &'a SceneGraph,
+	next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+	type Item = NodeId;
+
+	fn next(&mut self) -> Option<NodeId> {
+		let id = self.next?;
+		self.next = self.graph.parent(id);
+		Some(id)
+	}
+}
+
+pub struct DepthFirst<'a> {
+	graph: &'a SceneGraph,
+	stack: Vec<NodeId>,
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+	type Item = NodeId;
+
+	fn next(&mut self) -> Option<NodeId> {
+		let id = self.stack.pop()?;
+		self.stack.extend(self.graph.children(id).iter().rev());
+		Some(id)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f32::consts::PI;
+
+	fn turn_y(degrees: f32) -> Quat {
+		Quat::new(Vec3::from_y(1.0), degrees * PI / 180.0)
+	}
+
+	#[test]
+	fn nested_world_position() {
+		let mut g = SceneGraph::new();
+		let root = g.create();
+		let child = g.create_child(root);
+		let leaf = g.create_child(child);
+
+		g.set_position(root, Vec3::new(10.0, 0.0, 0.0));
+		g.set_position(child, Vec3::new(1.0, 0.0, 0.0));
+		g.set_local(leaf, Vec3::new(0.0, 2.0, 1.0), Quat::ident(), Vec3::uniform(3.0));
+		assert!(g.world_position(leaf).approx_eq(Vec3::new(11.0, 2.0, 1.0), 1e-5));
+
+		// A quarter turn about y takes +x to -z and +z to +x
+		g.set_rotation(root, turn_y(90.0));
+		assert!(g.world_position(child).approx_eq(Vec3::new(10.0, 0.0, -1.0), 1e-5));
+		assert!(g.world_position(leaf).approx_eq(Vec3::new(11.0, 2.0, -1.0), 1e-5));
+		assert!(g.world_rotation(leaf).approx_eq(turn_y(90.0), 1e-5));
+
+		g.set_scale(root, Vec3::uniform(2.0));
+		assert!(g.world_position(leaf).approx_eq(Vec3::new(12.0, 4.0, -2.0), 1e-5));
+	}
+
+	#[test]
+	fn cached_child_is_invalidated() {
+		let mut g = SceneGraph::new();
+		let parent = g.create();
+		let child = g.create_child(parent);
+		g.set_position(child, Vec3::new(0.0, 1.0, 0.0));
+
+		// Parent cached while the child is still dirty, then the child too
+		g.world_matrix(parent);
+		assert!(g.world_position(child).approx_eq(Vec3::new(0.0, 1.0, 0.0), 1e-6));
+
+		g.set_position(parent, Vec3::new(5.0, 0.0, 0.0));
+		g.world_matrix(parent);
+		assert!(g.world_position(child).approx_eq(Vec3::new(5.0, 1.0, 0.0), 1e-6));
+
+		// Same again with only the parent read in between
+		g.set_position(child, Vec3::new(0.0, 2.0, 0.0));
+		g.world_matrix(parent);
+		g.set_position(parent, Vec3::new(7.0, 0.0, 0.0));
+		assert!(g.world_position(child).approx_eq(Vec3::new(7.0, 2.0, 0.0), 1e-6));
+
+		g.update();
+		g.set_rotation(parent, turn_y(180.0));
+		assert!(g.world_position(child).approx_eq(Vec3::new(7.0, 2.0, 0.0), 1e-5));
+		g.set_position(child, Vec3::new(1.0, 0.0, 0.0));
+		assert!(g.world_position(child).approx_eq(Vec3::new(6.0, 0.0, 0.0), 1e-5));
+	}
+
+	#[test]
+	fn reparenting() {
+		let mut g = SceneGraph::new();
+		let a = g.create();
+		let b = g.create();
+		let n = g.create_child(a);
+		g.set_position(a, Vec3::new(1.0, 0.0, 0.0));
+		g.set_local(b, Vec3::new(0.0, 0.0, 5.0), turn_y(90.0), Vec3::uniform(2.0));
+		g.set_position(n, Vec3::new(0.0, 1.0, 0.0));
+		assert!(g.world_position(n).approx_eq(Vec3::new(1.0, 1.0, 0.0), 1e-6));
+
+		g.set_parent(n, Some(b), true).unwrap();
+		assert_eq!(g.parent(n), Some(b));
+		assert_eq!(g.children(a), &[]);
+		assert_eq!(g.children(b), &[n]);
+		assert!(g.world_position(n).approx_eq(Vec3::new(1.0, 1.0, 0.0), 1e-5));
+		assert!(g.world_rotation(n).approx_eq(Quat::ident(), 1e-5));
+		assert!(g.scale(n).approx_eq(Vec3::uniform(0.5), 1e-5));
+
+		// Without keep_world the local transform comes along and the node moves
+		let local = g.position(n);
+		g.set_parent(n, Some(a), false).unwrap();
+		assert!(g.position(n).approx_eq(local, 1e-6));
+		assert!(!g.world_position(n).approx_eq(Vec3::new(1.0, 1.0, 0.0), 1e-3));
+
+		g.set_parent(n, None, true).unwrap();
+		assert_eq!(g.parent(n), None);
+		assert_eq!(g.roots(), &[a, b, n]);
+		assert_eq!(g.children(a), &[]);
+	}
+
+	#[test]
+	fn cycles_are_rejected() {
+		let mut g = SceneGraph::new();
+		let a = g.create();
+		let b = g.create_child(a);
+		let c = g.create_child(b);
+
+		assert!(g.set_parent(a, Some(c), false).is_err());
+		assert!(g.set_parent(a, Some(b), true).is_err());
+		assert!(g.set_parent(b, Some(b), false).is_err());
+
+		// Nothing moved
+		assert_eq!(g.roots(), &[a]);
+		assert_eq!(g.iter().collect::<Vec<_>>(), vec![a, b, c]);
+		assert_eq!(g.ancestors(c).collect::<Vec<_>>(), vec![b, a]);
+	}
+
+	#[test]
+	fn removed_ids_go_stale() {
+		let mut g = SceneGraph::new();
+		let a = g.create();
+		let b = g.create_child(a);
+		let c = g.create_child(b);
+		let d = g.create();
+
+		g.remove(b);
+		assert!(!g.contains(b) && !g.contains(c));
+		assert!(g.contains(a) && g.contains(d));
+		assert_eq!(g.children(a), &[]);
+		assert_eq!(g.len(), 2);
+
+		// The slots are reused with a new generation, so the old handles stay dead
+		let e = g.create();
+		let f = g.create();
+		assert!(g.contains(e) && g.contains(f));
+		assert!(!g.contains(b) && !g.contains(c));
+		assert!(e != b && e != c && f != b && f != c);
+		assert_eq!(g.len(), 4);
+	}
+
+	#[test]
+	#[should_panic]
+	fn using_a_stale_id_panics() {
+		let mut g = SceneGraph::new();
+		let a = g.create();
+		g.remove(a);
+		g.create();
+		g.position(a);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+pub mod graph;
+pub mod ecs;
+pub mod camera;
\ No newline at end of file